@@ -0,0 +1,174 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicI64, Ordering},
+        Mutex,
+    },
+};
+
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+
+use super::{Carrier, CarrierResult};
+use crate::model;
+
+/// `(service level, base price in cents, price per started kg in cents, transit days)`
+const SERVICES: [(&str, i64, i64, i64); 3] = [
+    ("Economy", 400, 50, 3),
+    ("Express", 900, 125, 1),
+    ("SameDay", 1500, 200, 0),
+];
+
+/// Fake carrier for development and tests, nothing leaves the process.
+///
+/// Prices only depend on the package weight and tracking numbers count up from
+/// `LC00000001`. A label shows as shipped once bought. Each time it's tracked counts as a
+/// day in transit, so it's delivered on the first lookup after its transit days: straight
+/// away for `SameDay`, on the fourth lookup for `Economy`. That keeps tests off the clock.
+#[derive(Default)]
+pub struct LocalCarrier {
+    next_label: AtomicI64,
+    labels: Mutex<BTreeMap<String, LocalLabel>>,
+}
+
+struct LocalLabel {
+    transit_days: i64,
+    created_at: DateTime<Utc>,
+    days_tracked: i64,
+    delivered_at: Option<DateTime<Utc>>,
+    voided: bool,
+}
+
+impl LocalCarrier {
+    fn quotes(&self, request: &model::RateRequest) -> Result<Vec<model::RateQuote>, String> {
+        let Some(weight_grams) = request.weight_grams else {
+            return Err("the local carrier needs the package weight".to_string());
+        };
+        let started_kg = (weight_grams + 999) / 1000;
+
+        Ok(SERVICES
+            .iter()
+            .map(
+                |(service_level, base, per_kg, transit_days)| model::RateQuote {
+                    carrier: self.name().to_string(),
+                    service_level: service_level.to_string(),
+                    amount: Decimal::new(base + per_kg * started_kg, 2),
+                    currency: model::BASE_CURRENCY.to_string(),
+                    transit_days: *transit_days,
+                },
+            )
+            .collect())
+    }
+}
+
+impl Carrier for LocalCarrier {
+    fn name(&self) -> &str {
+        "Local"
+    }
+
+    fn quote_rates<'a>(
+        &'a self,
+        request: &'a model::RateRequest,
+    ) -> CarrierResult<'a, Vec<model::RateQuote>> {
+        Box::pin(async move { self.quotes(request) })
+    }
+
+    fn buy_label<'a>(
+        &'a self,
+        request: &'a model::RateRequest,
+        service_level: &'a str,
+    ) -> CarrierResult<'a, model::Label> {
+        Box::pin(async move {
+            let Some(quote) = self
+                .quotes(request)?
+                .into_iter()
+                .find(|q| q.service_level == service_level)
+            else {
+                return Err(format!("unknown service level {}", service_level));
+            };
+
+            let number = self.next_label.fetch_add(1, Ordering::SeqCst) + 1;
+            let tracking_number = format!("LC{:08}", number);
+            let ship_to = &request.ship_to;
+            let data = format!(
+                "LOCAL CARRIER {}\n{}\n{}\n{} {}\n{}\n",
+                quote.service_level,
+                tracking_number,
+                ship_to.recipient,
+                ship_to.postal_code,
+                ship_to.city,
+                ship_to.country
+            );
+
+            self.labels.lock().unwrap().insert(
+                tracking_number.clone(),
+                LocalLabel {
+                    transit_days: quote.transit_days,
+                    created_at: Utc::now(),
+                    days_tracked: 0,
+                    delivered_at: None,
+                    voided: false,
+                },
+            );
+
+            Ok(model::Label {
+                tracking_number,
+                service_level: quote.service_level,
+                amount: quote.amount,
+                currency: quote.currency,
+                data,
+            })
+        })
+    }
+
+    fn void_label<'a>(&'a self, tracking_number: &'a str) -> CarrierResult<'a, ()> {
+        Box::pin(async move {
+            let mut labels = self.labels.lock().unwrap();
+            match labels.get_mut(tracking_number) {
+                Some(label) if !label.voided => {
+                    label.voided = true;
+                    Ok(())
+                }
+                Some(_) => Err(format!("label {} is already void", tracking_number)),
+                None => Err(format!("unknown label {}", tracking_number)),
+            }
+        })
+    }
+
+    fn track<'a>(
+        &'a self,
+        tracking_number: &'a str,
+    ) -> CarrierResult<'a, Vec<model::TrackingEvent>> {
+        Box::pin(async move {
+            let mut labels = self.labels.lock().unwrap();
+            let Some(label) = labels.get_mut(tracking_number) else {
+                return Err(format!("unknown label {}", tracking_number));
+            };
+            if label.voided {
+                return Err(format!("label {} is void", tracking_number));
+            }
+
+            let mut events = vec![model::TrackingEvent {
+                status: model::ShipmentStatus::Shipped,
+                occurred_at: label.created_at,
+                description: "Collected".to_string(),
+            }];
+            if label.delivered_at.is_none() {
+                if label.days_tracked >= label.transit_days {
+                    label.delivered_at = Some(Utc::now());
+                } else {
+                    label.days_tracked += 1;
+                }
+            }
+            if let Some(delivered_at) = label.delivered_at {
+                events.push(model::TrackingEvent {
+                    status: model::ShipmentStatus::Delivered,
+                    occurred_at: delivered_at,
+                    description: "Delivered".to_string(),
+                });
+            }
+
+            Ok(events)
+        })
+    }
+}
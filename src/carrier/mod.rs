@@ -0,0 +1,68 @@
+use std::{fmt, sync::Arc};
+
+use futures::future::BoxFuture;
+
+use crate::model;
+
+mod local;
+
+pub use local::LocalCarrier;
+
+/// Carrier failures are reported as a message, services decide how to surface them
+pub type CarrierResult<'a, T> = BoxFuture<'a, Result<T, String>>;
+
+/// Integration with a shipping carrier's rating, labelling and tracking APIs.
+///
+/// Shipments pick their integration by carrier name, so adding a carrier only means
+/// implementing this and registering it with the provider.
+pub trait Carrier: Send + Sync {
+    /// Name shipments use to refer to this carrier
+    fn name(&self) -> &str;
+
+    fn quote_rates<'a>(
+        &'a self,
+        request: &'a model::RateRequest,
+    ) -> CarrierResult<'a, Vec<model::RateQuote>>;
+
+    fn buy_label<'a>(
+        &'a self,
+        request: &'a model::RateRequest,
+        service_level: &'a str,
+    ) -> CarrierResult<'a, model::Label>;
+
+    fn void_label<'a>(&'a self, tracking_number: &'a str) -> CarrierResult<'a, ()>;
+
+    /// Events so far for a label, oldest first
+    fn track<'a>(
+        &'a self,
+        tracking_number: &'a str,
+    ) -> CarrierResult<'a, Vec<model::TrackingEvent>>;
+}
+
+/// Carrier integrations available to shipments
+#[derive(Clone)]
+pub struct Carriers {
+    carriers: Vec<Arc<dyn Carrier>>,
+}
+
+impl Default for Carriers {
+    fn default() -> Self {
+        Self {
+            carriers: vec![Arc::new(LocalCarrier::default())],
+        }
+    }
+}
+
+impl fmt::Debug for Carriers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.carriers.iter().map(|c| c.name()))
+            .finish()
+    }
+}
+
+impl Carriers {
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Carrier>> {
+        self.carriers.iter().find(|c| c.name() == name).cloned()
+    }
+}
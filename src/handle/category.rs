@@ -0,0 +1,59 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use log::warn;
+
+use crate::{
+    model::{self, ToRecord},
+    service::category::CategoryService,
+};
+
+type JsonResult<T> = Result<(StatusCode, Json<T>), StatusCode>;
+
+pub struct CategoryHandler;
+
+impl CategoryHandler {
+    pub async fn create_category<T: CategoryService>(
+        State(mut service): State<T>,
+        Json(payload): Json<model::CategoryDetails>,
+    ) -> JsonResult<model::Record<model::CategoryDetails>> {
+        match service.create_category(&payload).await {
+            Ok(id) => Ok((StatusCode::CREATED, Json(payload.to_record(id)))),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error creating category");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn get_category<T: CategoryService>(
+        State(mut service): State<T>,
+        Path(category_id): Path<i64>,
+    ) -> JsonResult<model::Record<model::CategoryDetails>> {
+        match service.get_category(category_id).await {
+            Ok(Some(record)) => Ok((StatusCode::OK, Json(record))),
+            Ok(None) => Err(StatusCode::NOT_FOUND),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error getting category");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn get_categories<T: CategoryService>(
+        State(mut service): State<T>,
+    ) -> JsonResult<Vec<model::Record<model::CategoryDetails>>> {
+        match service.get_categories().await {
+            Ok(records) => Ok((StatusCode::OK, Json(records))),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error getting categories");
+                Err(e.into())
+            }
+        }
+    }
+}
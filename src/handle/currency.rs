@@ -0,0 +1,53 @@
+use axum::{extract::State, http::StatusCode, Json};
+use log::warn;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::{model, service::currency::ExchangeRateService};
+
+type JsonResult<T> = Result<(StatusCode, Json<T>), StatusCode>;
+
+pub struct ExchangeRateHandler;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetExchangeRate {
+    pub base_currency: String,
+    pub quote_currency: String,
+    pub rate: Decimal,
+}
+
+impl ExchangeRateHandler {
+    pub async fn set_exchange_rate<T: ExchangeRateService>(
+        State(mut service): State<T>,
+        Json(payload): Json<SetExchangeRate>,
+    ) -> JsonResult<model::Record<model::ExchangeRateDetails>> {
+        match service
+            .set_exchange_rate(
+                &payload.base_currency,
+                &payload.quote_currency,
+                &payload.rate,
+            )
+            .await
+        {
+            Ok(record) => Ok((StatusCode::CREATED, Json(record))),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error setting exchange rate");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn get_exchange_rates<T: ExchangeRateService>(
+        State(mut service): State<T>,
+    ) -> JsonResult<Vec<model::Record<model::ExchangeRateDetails>>> {
+        match service.get_exchange_rates().await {
+            Ok(records) => Ok((StatusCode::OK, Json(records))),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error getting exchange rates");
+                Err(e.into())
+            }
+        }
+    }
+}
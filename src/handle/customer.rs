@@ -0,0 +1,92 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    model::{self, ToRecord},
+    service::customer::CustomerService,
+};
+
+type JsonResult<T> = Result<(StatusCode, Json<T>), StatusCode>;
+
+pub struct CustomerHandler;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddAddress {
+    pub label: Option<String>,
+    #[serde(default)]
+    pub is_default: bool,
+    #[serde(flatten)]
+    pub address: model::Address,
+}
+
+impl CustomerHandler {
+    pub async fn create_customer<T: CustomerService>(
+        State(mut service): State<T>,
+        Json(payload): Json<model::CustomerDetails>,
+    ) -> JsonResult<model::Record<model::CustomerDetails>> {
+        match service.create_customer(&payload).await {
+            Ok(id) => Ok((StatusCode::CREATED, Json(payload.to_record(id)))),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error creating customer");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn get_customer<T: CustomerService>(
+        State(mut service): State<T>,
+        Path(customer_id): Path<i64>,
+    ) -> JsonResult<model::Record<model::CustomerDetails>> {
+        match service.get_customer(customer_id).await {
+            Ok(Some(record)) => Ok((StatusCode::OK, Json(record))),
+            Ok(None) => Err(StatusCode::NOT_FOUND),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error getting customer");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn add_address<T: CustomerService>(
+        State(mut service): State<T>,
+        Path(customer_id): Path<i64>,
+        Json(payload): Json<AddAddress>,
+    ) -> JsonResult<model::Record<model::AddressDetails>> {
+        let address = model::AddressDetails {
+            customer_id,
+            label: payload.label,
+            is_default: payload.is_default,
+            address: payload.address,
+        };
+
+        match service.add_address(&address).await {
+            Ok(record) => Ok((StatusCode::CREATED, Json(record))),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error adding customer address");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn get_addresses<T: CustomerService>(
+        State(mut service): State<T>,
+        Path(customer_id): Path<i64>,
+    ) -> JsonResult<Vec<model::Record<model::AddressDetails>>> {
+        match service.get_addresses(customer_id).await {
+            Ok(records) => Ok((StatusCode::OK, Json(records))),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error getting customer addresses");
+                Err(e.into())
+            }
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::NaiveDate;
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::{model, service::delivery_run::DeliveryRunService};
+
+type JsonResult<T> = Result<(StatusCode, Json<T>), StatusCode>;
+
+pub struct DeliveryRunHandler;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeliveryRunDateQuery {
+    pub date: NaiveDate,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewDeliveryRunStatusRequest {
+    pub status: model::DeliveryRunStatus,
+}
+
+impl DeliveryRunHandler {
+    pub async fn create_delivery_run<T: DeliveryRunService>(
+        State(mut service): State<T>,
+        Json(payload): Json<model::NewDeliveryRun>,
+    ) -> JsonResult<model::Record<model::DeliveryRunDetails>> {
+        match service.create_delivery_run(&payload).await {
+            Ok(id) => match service.get_delivery_run(id).await {
+                Ok(Some(record)) => Ok((StatusCode::CREATED, Json(record))),
+                Ok(None) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+                Err(e) => {
+                    warn!("{}", e);
+                    Err(e.into())
+                }
+            },
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error creating delivery run");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn get_delivery_run<T: DeliveryRunService>(
+        State(mut service): State<T>,
+        Path(delivery_run_id): Path<i64>,
+    ) -> JsonResult<model::Record<model::DeliveryRunDetails>> {
+        match service.get_delivery_run(delivery_run_id).await {
+            Ok(Some(record)) => Ok((StatusCode::OK, Json(record))),
+            Ok(None) => Err(StatusCode::NOT_FOUND),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error getting delivery run");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn get_delivery_runs<T: DeliveryRunService>(
+        State(mut service): State<T>,
+        Query(query): Query<DeliveryRunDateQuery>,
+    ) -> JsonResult<Vec<model::Record<model::DeliveryRunDetails>>> {
+        match service.get_delivery_runs_by_date(query.date).await {
+            Ok(records) => Ok((StatusCode::OK, Json(records))),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error getting delivery runs");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn update_delivery_run_status<T: DeliveryRunService>(
+        State(mut service): State<T>,
+        Path(delivery_run_id): Path<i64>,
+        Json(payload): Json<NewDeliveryRunStatusRequest>,
+    ) -> Result<StatusCode, StatusCode> {
+        match service
+            .set_delivery_run_status(delivery_run_id, payload.status)
+            .await
+        {
+            Ok(_) => Ok(StatusCode::ACCEPTED),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error setting delivery run status");
+                Err(e.into())
+            }
+        }
+    }
+}
@@ -1,7 +1,9 @@
-use crate::model::{self, FulfillmentStatus, FulfillmentType, ToRecord};
+use crate::model;
+use crate::render;
 use crate::service::fulfillment::FulfillmentService;
-use axum::extract::Path;
-use axum::{extract::State, http::StatusCode, Json};
+use axum::extract::{Path, Query};
+use axum::{extract::State, http::StatusCode, response::Html, Json};
+use log::warn;
 use serde::{Deserialize, Serialize};
 
 pub struct FulfillmentHandler;
@@ -9,46 +11,66 @@ pub struct FulfillmentHandler;
 type JsonResult<T> = Result<(StatusCode, Json<T>), StatusCode>;
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct NewFulfillmentRequest {
-    fulfillment_type: FulfillmentType,
+pub struct SetSupplierReference {
+    supplier_reference: String,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct NewFulfillmentStatusRequest {
-    fulfillment_status: FulfillmentStatus,
+pub struct SetAssignee {
+    assignee: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WorkQueueQuery {
+    assignee: Option<String>,
 }
 
 impl FulfillmentHandler {
     pub async fn create_fulfillment<T: FulfillmentService>(
         State(mut service): State<T>,
-        Json(payload): Json<NewFulfillmentRequest>,
+        Json(payload): Json<model::NewFulfillment>,
     ) -> JsonResult<model::Record<model::FulfillmentDetails>> {
-        match service
-            .create_fulfillment(payload.fulfillment_type.clone())
-            .await
-        {
-            Ok(id) => Ok((
-                StatusCode::CREATED,
-                Json(
-                    model::FulfillmentDetails {
-                        fulfillment_type: payload.fulfillment_type,
-                        status: model::FulfillmentStatus::New,
-                    }
-                    .to_record(id),
-                ),
-            )),
-            // FIXME: handle abstract error type 400 errors not covered
-            Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+        let result = service.create_fulfillment(&payload).await;
+
+        match result {
+            Ok(id) => match service.get_fulfillment(&id).await {
+                Ok(Some(record)) => Ok((StatusCode::CREATED, Json(record))),
+                Ok(None) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+                Err(e) => {
+                    warn!("{}", e);
+                    Err(e.into())
+                }
+            },
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error creating fulfillment");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn get_fulfillment<T: FulfillmentService>(
+        State(mut service): State<T>,
+        Path(fulfillment_id): Path<i64>,
+    ) -> JsonResult<model::Record<model::FulfillmentDetails>> {
+        match service.get_fulfillment(&fulfillment_id).await {
+            Ok(Some(record)) => Ok((StatusCode::OK, Json(record))),
+            Ok(None) => Err(StatusCode::NOT_FOUND),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error getting fulfillment");
+                Err(e.into())
+            }
         }
     }
     pub async fn update_fulfillment_status<T: FulfillmentService>(
         State(mut service): State<T>,
         Path(fulfillment_id): Path<i64>,
-        Json(payload): Json<NewFulfillmentStatusRequest>,
+        Json(payload): Json<model::FulfillmentStatusChange>,
     ) -> Result<StatusCode, StatusCode> {
         println!("aaaaa");
         match service
-            .set_fulfillment_status(&fulfillment_id, payload.fulfillment_status)
+            .set_fulfillment_status(&fulfillment_id, &payload)
             .await
         {
             Ok(_) => Ok(StatusCode::ACCEPTED),
@@ -59,4 +81,157 @@ impl FulfillmentHandler {
             }
         }
     }
+
+    pub async fn set_supplier_reference<T: FulfillmentService>(
+        State(mut service): State<T>,
+        Path(fulfillment_id): Path<i64>,
+        Json(payload): Json<SetSupplierReference>,
+    ) -> Result<StatusCode, StatusCode> {
+        match service
+            .set_supplier_reference(&fulfillment_id, &payload.supplier_reference)
+            .await
+        {
+            Ok(_) => Ok(StatusCode::ACCEPTED),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error setting supplier reference");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn assign_fulfillment<T: FulfillmentService>(
+        State(mut service): State<T>,
+        Path(fulfillment_id): Path<i64>,
+        Json(payload): Json<SetAssignee>,
+    ) -> Result<StatusCode, StatusCode> {
+        match service
+            .assign_fulfillment(&fulfillment_id, payload.assignee.as_deref())
+            .await
+        {
+            Ok(_) => Ok(StatusCode::ACCEPTED),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error assigning fulfillment");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn get_work_queue<T: FulfillmentService>(
+        State(mut service): State<T>,
+        Query(query): Query<WorkQueueQuery>,
+    ) -> JsonResult<Vec<model::Record<model::FulfillmentDetails>>> {
+        match service.get_work_queue(query.assignee.as_deref()).await {
+            Ok(records) => Ok((StatusCode::OK, Json(records))),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error getting work queue");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn scan_item<T: FulfillmentService>(
+        State(mut service): State<T>,
+        Path(fulfillment_id): Path<i64>,
+        Json(payload): Json<model::Scan>,
+    ) -> JsonResult<model::ScanResult> {
+        match service.scan_item(&fulfillment_id, &payload).await {
+            Ok(result) => Ok((StatusCode::OK, Json(result))),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error scanning item");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn get_fulfillment_status_history<T: FulfillmentService>(
+        State(mut service): State<T>,
+        Path(fulfillment_id): Path<i64>,
+    ) -> JsonResult<Vec<model::FulfillmentStatusEvent>> {
+        match service
+            .get_fulfillment_status_history(&fulfillment_id)
+            .await
+        {
+            Ok(events) => Ok((StatusCode::OK, Json(events))),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error getting fulfillment status history");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn get_workflows<T: FulfillmentService>(
+        State(mut service): State<T>,
+    ) -> JsonResult<model::Workflows> {
+        match service.get_workflows().await {
+            Ok(workflows) => Ok((StatusCode::OK, Json(workflows))),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error getting workflows");
+                Err(e.into())
+            }
+        }
+    }
+
+    async fn find_shipping_label<T: FulfillmentService>(
+        service: &mut T,
+        fulfillment_id: i64,
+    ) -> Result<model::ShippingLabel, StatusCode> {
+        match service.get_shipping_label(&fulfillment_id).await {
+            Ok(label) => Ok(label),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error getting shipping label");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn get_shipping_label_zpl<T: FulfillmentService>(
+        State(mut service): State<T>,
+        Path(fulfillment_id): Path<i64>,
+    ) -> Result<String, StatusCode> {
+        let label = Self::find_shipping_label(&mut service, fulfillment_id).await?;
+        Ok(render::label::label_zpl(&label))
+    }
+
+    pub async fn get_shipping_label_text<T: FulfillmentService>(
+        State(mut service): State<T>,
+        Path(fulfillment_id): Path<i64>,
+    ) -> Result<String, StatusCode> {
+        let label = Self::find_shipping_label(&mut service, fulfillment_id).await?;
+        Ok(render::label::label_text(&label))
+    }
+
+    pub async fn get_packing_slip<T: FulfillmentService>(
+        State(mut service): State<T>,
+        Path(fulfillment_id): Path<i64>,
+    ) -> JsonResult<model::PackingSlip> {
+        match service.get_packing_slip(&fulfillment_id).await {
+            Ok(slip) => Ok((StatusCode::OK, Json(slip))),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error getting packing slip");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn get_packing_slip_html<T: FulfillmentService>(
+        State(mut service): State<T>,
+        Path(fulfillment_id): Path<i64>,
+    ) -> Result<Html<String>, StatusCode> {
+        match service.get_packing_slip(&fulfillment_id).await {
+            Ok(slip) => Ok(Html(render::packing_slip::packing_slip_html(&slip))),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error getting packing slip");
+                Err(e.into())
+            }
+        }
+    }
 }
@@ -0,0 +1,135 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    model::{self, ToRecord},
+    service::inventory::InventoryService,
+};
+
+type JsonResult<T> = Result<(StatusCode, Json<T>), StatusCode>;
+
+pub struct InventoryHandler;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordStockMovement {
+    /// Default location if not given
+    pub location_id: Option<i64>,
+    pub quantity: i64,
+    pub reason: model::StockMovementReason,
+    pub reference: Option<String>,
+}
+
+impl InventoryHandler {
+    pub async fn create_location<T: InventoryService>(
+        State(mut service): State<T>,
+        Json(payload): Json<model::LocationDetails>,
+    ) -> JsonResult<model::Record<model::LocationDetails>> {
+        match service.create_location(&payload.name).await {
+            Ok(id) => Ok((StatusCode::CREATED, Json(payload.to_record(id)))),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error creating location");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn get_location<T: InventoryService>(
+        State(mut service): State<T>,
+        Path(location_id): Path<i64>,
+    ) -> JsonResult<model::Record<model::LocationDetails>> {
+        match service.get_location(location_id).await {
+            Ok(Some(record)) => Ok((StatusCode::OK, Json(record))),
+            Ok(None) => Err(StatusCode::NOT_FOUND),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error getting location");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn get_locations<T: InventoryService>(
+        State(mut service): State<T>,
+    ) -> JsonResult<Vec<model::Record<model::LocationDetails>>> {
+        match service.get_locations().await {
+            Ok(records) => Ok((StatusCode::OK, Json(records))),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error getting locations");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn get_stock_level<T: InventoryService>(
+        State(mut service): State<T>,
+        Path(product_id): Path<i64>,
+    ) -> JsonResult<model::StockLevel> {
+        match service.get_stock_level(product_id).await {
+            Ok(level) => Ok((StatusCode::OK, Json(level))),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error getting stock level");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn get_stock_movements<T: InventoryService>(
+        State(mut service): State<T>,
+        Path(product_id): Path<i64>,
+    ) -> JsonResult<Vec<model::Record<model::StockMovementDetails>>> {
+        match service.get_stock_movements(product_id).await {
+            Ok(movements) => Ok((StatusCode::OK, Json(movements))),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error getting stock movements");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn record_stock_movement<T: InventoryService>(
+        State(mut service): State<T>,
+        Path(product_id): Path<i64>,
+        Json(payload): Json<RecordStockMovement>,
+    ) -> JsonResult<model::Record<model::StockMovementDetails>> {
+        match service
+            .record_stock_movement(
+                product_id,
+                payload.location_id,
+                payload.quantity,
+                payload.reason,
+                payload.reference,
+            )
+            .await
+        {
+            Ok(movement) => Ok((StatusCode::CREATED, Json(movement))),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error recording stock movement");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn get_backorders<T: InventoryService>(
+        State(mut service): State<T>,
+        Path(product_id): Path<i64>,
+    ) -> JsonResult<Vec<model::Record<model::LineItemDetails>>> {
+        match service.get_backorders(product_id).await {
+            Ok(backorders) => Ok((StatusCode::OK, Json(backorders))),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error getting backorders");
+                Err(e.into())
+            }
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Html,
+    Json,
+};
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::{model, render, service::invoice::InvoiceService};
+
+type JsonResult<T> = Result<(StatusCode, Json<T>), StatusCode>;
+
+pub struct InvoiceHandler;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateInvoiceSequence {
+    pub name: String,
+    pub prefix: String,
+}
+
+impl InvoiceHandler {
+    async fn find_invoice<T: InvoiceService>(
+        service: &mut T,
+        order_id: i64,
+    ) -> Result<model::Record<model::InvoiceDetails>, StatusCode> {
+        match service.get_invoice_by_order_id(order_id).await {
+            Ok(Some(invoice)) => Ok(invoice),
+            Ok(None) => Err(StatusCode::NOT_FOUND),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error getting invoice");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn get_invoice<T: InvoiceService>(
+        State(mut service): State<T>,
+        Path(order_id): Path<i64>,
+    ) -> JsonResult<model::Record<model::InvoiceDetails>> {
+        let invoice = Self::find_invoice(&mut service, order_id).await?;
+        Ok((StatusCode::OK, Json(invoice)))
+    }
+
+    pub async fn get_invoice_html<T: InvoiceService>(
+        State(mut service): State<T>,
+        Path(order_id): Path<i64>,
+    ) -> Result<Html<String>, StatusCode> {
+        let invoice = Self::find_invoice(&mut service, order_id).await?;
+        Ok(Html(render::invoice::invoice_html(&invoice.data)))
+    }
+
+    pub async fn get_invoice_text<T: InvoiceService>(
+        State(mut service): State<T>,
+        Path(order_id): Path<i64>,
+    ) -> Result<String, StatusCode> {
+        let invoice = Self::find_invoice(&mut service, order_id).await?;
+        Ok(render::invoice::invoice_text(&invoice.data))
+    }
+
+    pub async fn create_invoice_sequence<T: InvoiceService>(
+        State(mut service): State<T>,
+        Json(payload): Json<CreateInvoiceSequence>,
+    ) -> JsonResult<model::InvoiceSequenceDetails> {
+        match service
+            .create_invoice_sequence(&payload.name, &payload.prefix)
+            .await
+        {
+            Ok(sequence) => Ok((StatusCode::CREATED, Json(sequence))),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error creating invoice sequence");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn get_invoice_sequences<T: InvoiceService>(
+        State(mut service): State<T>,
+    ) -> JsonResult<Vec<model::InvoiceSequenceDetails>> {
+        match service.get_invoice_sequences().await {
+            Ok(sequences) => Ok((StatusCode::OK, Json(sequences))),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error getting invoice sequences");
+                Err(e.into())
+            }
+        }
+    }
+}
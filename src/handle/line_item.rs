@@ -6,10 +6,7 @@ use axum::{
 use log::warn;
 use serde::{Deserialize, Serialize};
 
-use crate::{
-    model::{self, ToRecord},
-    service::line_item::LineItemService,
-};
+use crate::{model, service::line_item::LineItemService};
 
 type JsonResult<T> = Result<(StatusCode, Json<T>), StatusCode>;
 
@@ -20,35 +17,52 @@ pub struct CreateLineItem {
     pub fulfillment_id: i64,
     pub product_id: i64,
     pub quantity: i64,
+    /// Defaults to the product's base unit
+    #[serde(default)]
+    pub unit: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetQuantityFulfilled {
+    pub quantity_fulfilled: i64,
 }
 
 impl LineItemHandler {
     pub async fn create_line_item<T: LineItemService>(
         State(mut service): State<T>,
         Json(payload): Json<CreateLineItem>,
-    ) -> JsonResult<model::Record<model::LineItemDetails>> {
-        match service
-            .create_line_item(payload.fulfillment_id, payload.product_id, payload.quantity)
+    ) -> JsonResult<Vec<model::Record<model::LineItemDetails>>> {
+        let line_item_ids = match service
+            .create_line_item(
+                payload.fulfillment_id,
+                payload.product_id,
+                payload.quantity,
+                payload.unit.as_deref(),
+            )
             .await
         {
-            Ok(id) => Ok((
-                StatusCode::CREATED,
-                Json(
-                    model::LineItemDetails {
-                        product_id: payload.product_id,
-                        quantity: payload.quantity,
-                        quantity_fulfilled: 0,
-                        fulfillment_id: payload.fulfillment_id,
-                    }
-                    .to_record(id),
-                ),
-            )),
+            Ok(ids) => ids,
             Err(e) => {
                 warn!("{}", e);
                 warn!("error creating line item");
-                Err(e.into())
+                return Err(e.into());
+            }
+        };
+
+        // kits are exploded, so read back what was actually added
+        let mut records = Vec::new();
+        for id in line_item_ids {
+            match service.get_line_item(id).await {
+                Ok(Some(record)) => records.push(record),
+                Ok(None) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+                Err(e) => {
+                    warn!("{}", e);
+                    warn!("error getting created line item");
+                    return Err(e.into());
+                }
             }
         }
+        Ok((StatusCode::CREATED, Json(records)))
     }
 
     pub async fn get_line_item<T: LineItemService>(
@@ -82,4 +96,22 @@ impl LineItemHandler {
             }
         }
     }
+
+    pub async fn set_quantity_fulfilled<T: LineItemService>(
+        State(mut service): State<T>,
+        Path(line_item_id): Path<i64>,
+        Json(payload): Json<SetQuantityFulfilled>,
+    ) -> Result<StatusCode, StatusCode> {
+        match service
+            .set_quantity_fulfilled(line_item_id, payload.quantity_fulfilled)
+            .await
+        {
+            Ok(_) => Ok(StatusCode::ACCEPTED),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error setting quantity fulfilled");
+                Err(e.into())
+            }
+        }
+    }
 }
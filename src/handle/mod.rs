@@ -1,4 +1,17 @@
 // pub mod command;
+pub mod category;
+pub mod currency;
+pub mod customer;
+pub mod delivery_run;
 pub mod fulfillment;
+pub mod inventory;
+pub mod invoice;
 pub mod line_item;
+pub mod order;
+pub mod pickup;
+pub mod price_list;
 pub mod product;
+pub mod rma;
+pub mod shipment;
+pub mod tax;
+pub mod wave;
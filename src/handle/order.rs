@@ -0,0 +1,225 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use log::warn;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::{model, service::order::OrderService};
+
+type JsonResult<T> = Result<(StatusCode, Json<T>), StatusCode>;
+
+pub struct OrderHandler;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateOrder {
+    pub customer_id: Option<i64>,
+    pub price_list_id: Option<i64>,
+    #[serde(default = "model::base_currency")]
+    pub currency: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddOrderLine {
+    pub product_id: i64,
+    pub quantity: i64,
+    pub discount: Option<model::Discount>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetDiscount {
+    pub discount: Option<model::Discount>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordPayment {
+    pub method: model::PaymentMethod,
+    pub amount: Decimal,
+    pub reference: Option<String>,
+    pub received_at: Option<DateTime<Utc>>,
+}
+
+impl OrderHandler {
+    pub async fn create_order<T: OrderService>(
+        State(mut service): State<T>,
+        Json(payload): Json<CreateOrder>,
+    ) -> JsonResult<model::Record<model::OrderDetails>> {
+        match service
+            .create_order(
+                payload.customer_id,
+                payload.price_list_id,
+                &payload.currency,
+            )
+            .await
+        {
+            Ok(id) => match service.get_order(id).await {
+                Ok(Some(record)) => Ok((StatusCode::CREATED, Json(record))),
+                Ok(None) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+                Err(e) => {
+                    warn!("{}", e);
+                    Err(e.into())
+                }
+            },
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error creating order");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn get_order<T: OrderService>(
+        State(mut service): State<T>,
+        Path(order_id): Path<i64>,
+    ) -> JsonResult<model::Record<model::OrderDetails>> {
+        match service.get_order(order_id).await {
+            Ok(Some(record)) => Ok((StatusCode::OK, Json(record))),
+            Ok(None) => Err(StatusCode::NOT_FOUND),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error getting order");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn add_order_line<T: OrderService>(
+        State(mut service): State<T>,
+        Path(order_id): Path<i64>,
+        Json(payload): Json<AddOrderLine>,
+    ) -> JsonResult<model::Record<model::OrderLineDetails>> {
+        match service
+            .add_order_line(
+                order_id,
+                payload.product_id,
+                payload.quantity,
+                payload.discount,
+            )
+            .await
+        {
+            Ok(record) => Ok((StatusCode::CREATED, Json(record))),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error adding order line");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn get_order_lines<T: OrderService>(
+        State(mut service): State<T>,
+        Path(order_id): Path<i64>,
+    ) -> JsonResult<Vec<model::Record<model::OrderLineDetails>>> {
+        match service.get_order_lines(order_id).await {
+            Ok(lines) => Ok((StatusCode::OK, Json(lines))),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error getting order lines");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn set_order_line_discount<T: OrderService>(
+        State(mut service): State<T>,
+        Path((order_id, order_line_id)): Path<(i64, i64)>,
+        Json(payload): Json<SetDiscount>,
+    ) -> Result<StatusCode, StatusCode> {
+        match service
+            .set_order_line_discount(order_id, order_line_id, payload.discount)
+            .await
+        {
+            Ok(_) => Ok(StatusCode::ACCEPTED),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error setting order line discount");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn remove_order_line<T: OrderService>(
+        State(mut service): State<T>,
+        Path((order_id, order_line_id)): Path<(i64, i64)>,
+    ) -> Result<StatusCode, StatusCode> {
+        match service.remove_order_line(order_id, order_line_id).await {
+            Ok(_) => Ok(StatusCode::NO_CONTENT),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error removing order line");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn set_order_discount<T: OrderService>(
+        State(mut service): State<T>,
+        Path(order_id): Path<i64>,
+        Json(payload): Json<SetDiscount>,
+    ) -> Result<StatusCode, StatusCode> {
+        match service.set_order_discount(order_id, payload.discount).await {
+            Ok(_) => Ok(StatusCode::ACCEPTED),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error setting order discount");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn update_order_status<T: OrderService>(
+        State(mut service): State<T>,
+        Path(order_id): Path<i64>,
+        Json(payload): Json<model::OrderStatusChange>,
+    ) -> Result<StatusCode, StatusCode> {
+        match service.set_order_status(order_id, &payload).await {
+            Ok(_) => Ok(StatusCode::ACCEPTED),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error setting order status");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn record_payment<T: OrderService>(
+        State(mut service): State<T>,
+        Path(order_id): Path<i64>,
+        Json(payload): Json<RecordPayment>,
+    ) -> JsonResult<model::Record<model::PaymentDetails>> {
+        match service
+            .record_payment(
+                order_id,
+                payload.method,
+                payload.amount,
+                payload.reference,
+                payload.received_at,
+            )
+            .await
+        {
+            Ok(record) => Ok((StatusCode::CREATED, Json(record))),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error recording payment");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn get_payments<T: OrderService>(
+        State(mut service): State<T>,
+        Path(order_id): Path<i64>,
+    ) -> JsonResult<Vec<model::Record<model::PaymentDetails>>> {
+        match service.get_payments(order_id).await {
+            Ok(records) => Ok((StatusCode::OK, Json(records))),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error getting payments");
+                Err(e.into())
+            }
+        }
+    }
+}
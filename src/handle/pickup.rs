@@ -0,0 +1,95 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::NaiveDate;
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::{model, service::pickup::PickupService};
+
+type JsonResult<T> = Result<(StatusCode, Json<T>), StatusCode>;
+
+pub struct PickupHandler;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PickupDayQuery {
+    pub date: NaiveDate,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BookPickupSlot {
+    pub pickup_slot_id: i64,
+}
+
+impl PickupHandler {
+    pub async fn create_pickup_slot<T: PickupService>(
+        State(mut service): State<T>,
+        Json(payload): Json<model::NewPickupSlot>,
+    ) -> JsonResult<model::Record<model::PickupSlotDetails>> {
+        match service.create_pickup_slot(&payload).await {
+            Ok(id) => match service.get_pickup_slot(id).await {
+                Ok(Some(record)) => Ok((StatusCode::CREATED, Json(record))),
+                Ok(None) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+                Err(e) => {
+                    warn!("{}", e);
+                    Err(e.into())
+                }
+            },
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error creating pickup slot");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn get_pickup_slot<T: PickupService>(
+        State(mut service): State<T>,
+        Path(pickup_slot_id): Path<i64>,
+    ) -> JsonResult<model::Record<model::PickupSlotDetails>> {
+        match service.get_pickup_slot(pickup_slot_id).await {
+            Ok(Some(record)) => Ok((StatusCode::OK, Json(record))),
+            Ok(None) => Err(StatusCode::NOT_FOUND),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error getting pickup slot");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn get_pickup_day<T: PickupService>(
+        State(mut service): State<T>,
+        Path(location_id): Path<i64>,
+        Query(query): Query<PickupDayQuery>,
+    ) -> JsonResult<Vec<model::Record<model::PickupSlotDetails>>> {
+        match service.get_pickup_day(location_id, query.date).await {
+            Ok(records) => Ok((StatusCode::OK, Json(records))),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error getting pickups for day");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn book_pickup_slot<T: PickupService>(
+        State(mut service): State<T>,
+        Path(fulfillment_id): Path<i64>,
+        Json(payload): Json<BookPickupSlot>,
+    ) -> Result<StatusCode, StatusCode> {
+        match service
+            .book_pickup_slot(fulfillment_id, payload.pickup_slot_id)
+            .await
+        {
+            Ok(_) => Ok(StatusCode::ACCEPTED),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error booking pickup slot");
+                Err(e.into())
+            }
+        }
+    }
+}
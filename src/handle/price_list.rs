@@ -0,0 +1,151 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::NaiveDate;
+use log::warn;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    model::{self, ToRecord},
+    service::price_list::PriceListService,
+};
+
+type JsonResult<T> = Result<(StatusCode, Json<T>), StatusCode>;
+
+pub struct PriceListHandler;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetProductPrice {
+    pub product_id: i64,
+    pub price: Decimal,
+    pub effective_from: Option<NaiveDate>,
+    pub effective_to: Option<NaiveDate>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PriceQuery {
+    pub price_list_id: Option<i64>,
+    pub date: Option<NaiveDate>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResolvedPrice {
+    pub product_id: i64,
+    pub price_list_id: Option<i64>,
+    pub date: NaiveDate,
+    #[serde(flatten)]
+    pub price: model::Price,
+}
+
+impl PriceListHandler {
+    pub async fn create_price_list<T: PriceListService>(
+        State(mut service): State<T>,
+        Json(payload): Json<model::PriceListDetails>,
+    ) -> JsonResult<model::Record<model::PriceListDetails>> {
+        match service
+            .create_price_list(&payload.name, &payload.currency)
+            .await
+        {
+            Ok(id) => Ok((
+                StatusCode::CREATED,
+                Json(
+                    model::PriceListDetails {
+                        currency: payload.currency.trim().to_ascii_uppercase(),
+                        ..payload
+                    }
+                    .to_record(id),
+                ),
+            )),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error creating price list");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn get_price_list<T: PriceListService>(
+        State(mut service): State<T>,
+        Path(price_list_id): Path<i64>,
+    ) -> JsonResult<model::Record<model::PriceListDetails>> {
+        match service.get_price_list(price_list_id).await {
+            Ok(Some(record)) => Ok((StatusCode::OK, Json(record))),
+            Ok(None) => Err(StatusCode::NOT_FOUND),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error getting price list");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn set_product_price<T: PriceListService>(
+        State(mut service): State<T>,
+        Path(price_list_id): Path<i64>,
+        Json(payload): Json<SetProductPrice>,
+    ) -> JsonResult<model::Record<model::PriceListEntryDetails>> {
+        let entry = model::PriceListEntryDetails {
+            price_list_id,
+            product_id: payload.product_id,
+            price: payload.price,
+            effective_from: payload.effective_from,
+            effective_to: payload.effective_to,
+        };
+
+        match service.set_product_price(entry.clone()).await {
+            Ok(id) => Ok((StatusCode::CREATED, Json(entry.to_record(id)))),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error setting product price");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn get_price_list_entries<T: PriceListService>(
+        State(mut service): State<T>,
+        Path(price_list_id): Path<i64>,
+    ) -> JsonResult<Vec<model::Record<model::PriceListEntryDetails>>> {
+        match service.get_price_list_entries(price_list_id).await {
+            Ok(entries) => Ok((StatusCode::OK, Json(entries))),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error getting price list entries");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn get_product_price<T: PriceListService>(
+        State(mut service): State<T>,
+        Path(product_id): Path<i64>,
+        Query(query): Query<PriceQuery>,
+    ) -> JsonResult<ResolvedPrice> {
+        let date = query
+            .date
+            .unwrap_or_else(|| chrono::Utc::now().date_naive());
+
+        match service
+            .resolve_price(product_id, query.price_list_id, date)
+            .await
+        {
+            Ok(price) => Ok((
+                StatusCode::OK,
+                Json(ResolvedPrice {
+                    product_id,
+                    price_list_id: query.price_list_id,
+                    date,
+                    price,
+                }),
+            )),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error resolving product price");
+                Err(e.into())
+            }
+        }
+    }
+}
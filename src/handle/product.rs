@@ -1,11 +1,20 @@
-use crate::model::{self, ToRecord};
+use std::collections::BTreeMap;
+
+use crate::model;
 use crate::service::product::ProductService;
-use axum::extract::Path;
+use axum::extract::{Path, Query};
 use axum::Json;
 use axum::{extract::State, http::StatusCode};
+use log::warn;
+use serde::{Deserialize, Serialize};
 
 pub struct ProductHandler;
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProductSearchQuery {
+    pub q: String,
+}
+
 type JsonResult<T> = Result<(StatusCode, Json<T>), StatusCode>;
 
 impl ProductHandler {
@@ -13,25 +22,132 @@ impl ProductHandler {
         State(mut service): State<T>,
         Json(payload): Json<model::ProductDetails>,
     ) -> JsonResult<model::Record<model::ProductDetails>> {
-        match service
-            .create_product(&payload.sku, &payload.description)
-            .await
-        {
-            Ok(id) => Ok((StatusCode::CREATED, Json(payload.to_record(id)))),
-            // FIXME: handle abstract error type 400 errors not covered
-            Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+        // barcodes are normalised on the way in, so read back what was stored
+        let product = match service.create_product(&payload).await {
+            Ok(id) => service.get_product(&id).await,
+            Err(e) => Err(e),
+        };
+        match product {
+            Ok(record) => Ok((StatusCode::CREATED, Json(record))),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error creating product");
+                Err(e.into())
+            }
         }
     }
 
     pub async fn get_product<T: ProductService>(
         State(mut service): State<T>,
-        Path(product_id): Path<u32>,
+        Path(product_id): Path<i64>,
     ) -> JsonResult<model::Record<model::ProductDetails>> {
-        // FIXME: Dirty conversion here fix
-        match service.get_product(&product_id.into()).await {
+        match service.get_product(&product_id).await {
             Ok(record) => Ok((StatusCode::OK, Json(record))),
-            // FIXME: handle abstract error type 400 errors not covered
-            Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error getting product");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn add_product_barcode<T: ProductService>(
+        State(mut service): State<T>,
+        Path(product_id): Path<i64>,
+        Json(payload): Json<model::Barcode>,
+    ) -> Result<StatusCode, StatusCode> {
+        match service.add_product_barcode(product_id, &payload).await {
+            Ok(_) => Ok(StatusCode::CREATED),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error adding product barcode");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn search_products<T: ProductService>(
+        State(mut service): State<T>,
+        Query(query): Query<ProductSearchQuery>,
+    ) -> JsonResult<Vec<model::ProductSearchHit>> {
+        match service.search_products(&query.q).await {
+            Ok(hits) => Ok((StatusCode::OK, Json(hits))),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error searching products");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn find_product_by_barcode<T: ProductService>(
+        State(mut service): State<T>,
+        Path(code): Path<String>,
+    ) -> JsonResult<model::Record<model::ProductDetails>> {
+        match service.find_product_by_code(&code).await {
+            Ok(Some(record)) => Ok((StatusCode::OK, Json(record))),
+            Ok(None) => Err(StatusCode::NOT_FOUND),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error finding product by barcode");
+                Err(e.into())
+            }
+        }
+    }
+
+    /// `category_id` narrows to a category and those below it, any other parameter has to
+    /// match the product attribute of that name
+    pub async fn get_catalog<T: ProductService>(
+        State(mut service): State<T>,
+        Query(mut query): Query<BTreeMap<String, String>>,
+    ) -> JsonResult<Vec<model::CatalogEntry>> {
+        let category_id = match query.remove("category_id").map(|id| id.parse()) {
+            Some(Ok(id)) => Some(id),
+            Some(Err(_)) => return Err(StatusCode::BAD_REQUEST),
+            None => None,
+        };
+        let filter = model::ProductFilter {
+            category_id,
+            attributes: query,
+        };
+        match service.get_catalog(&filter).await {
+            Ok(catalog) => Ok((StatusCode::OK, Json(catalog))),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error getting catalog");
+                Err(e.into())
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use axum::{extract::State, http::StatusCode, Json};
+
+    use crate::{
+        handle::product::ProductHandler, model, provider::SqliteProvider,
+        service::product::ProductService,
+    };
+
+    #[tokio::test]
+    async fn test_create_product_bad_input() {
+        let mut provider = SqliteProvider::new_memory().await.unwrap();
+        ProductService::init_provider(&mut provider).await.unwrap();
+
+        let result = ProductHandler::create_product(
+            State(provider),
+            Json(model::ProductDetails {
+                sku: "SKU-1".to_string(),
+                description: "widget".to_string(),
+                barcodes: vec![model::Barcode {
+                    symbology: model::BarcodeSymbology::Ean13,
+                    code: "4006381333932".to_string(),
+                }],
+                ..Default::default()
+            }),
+        )
+        .await;
+        assert_eq!(result.err(), Some(StatusCode::BAD_REQUEST));
+    }
+}
@@ -0,0 +1,113 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use log::warn;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::{model, service::rma::ReturnService};
+
+type JsonResult<T> = Result<(StatusCode, Json<T>), StatusCode>;
+
+pub struct ReturnHandler;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReturnItem {
+    pub line_item_id: i64,
+    pub quantity: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateReturn {
+    pub fulfillment_id: i64,
+    pub reason: Option<String>,
+    pub lines: Vec<ReturnItem>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewReturnStatusRequest {
+    pub return_status: model::ReturnStatus,
+    pub refund_amount: Option<Decimal>,
+}
+
+impl ReturnHandler {
+    pub async fn create_return<T: ReturnService>(
+        State(mut service): State<T>,
+        Json(payload): Json<CreateReturn>,
+    ) -> JsonResult<model::Record<model::ReturnDetails>> {
+        let lines: Vec<(i64, i64)> = payload
+            .lines
+            .iter()
+            .map(|l| (l.line_item_id, l.quantity))
+            .collect();
+
+        let result = service
+            .create_return(payload.fulfillment_id, payload.reason, &lines)
+            .await;
+
+        match result {
+            Ok(id) => match service.get_return(id).await {
+                Ok(Some(record)) => Ok((StatusCode::CREATED, Json(record))),
+                Ok(None) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+                Err(e) => {
+                    warn!("{}", e);
+                    Err(e.into())
+                }
+            },
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error creating return");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn get_return<T: ReturnService>(
+        State(mut service): State<T>,
+        Path(return_id): Path<i64>,
+    ) -> JsonResult<model::Record<model::ReturnDetails>> {
+        match service.get_return(return_id).await {
+            Ok(Some(record)) => Ok((StatusCode::OK, Json(record))),
+            Ok(None) => Err(StatusCode::NOT_FOUND),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error getting return");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn get_returns_by_order_id<T: ReturnService>(
+        State(mut service): State<T>,
+        Path(order_id): Path<i64>,
+    ) -> JsonResult<Vec<model::Record<model::ReturnDetails>>> {
+        match service.get_returns_by_order_id(order_id).await {
+            Ok(records) => Ok((StatusCode::OK, Json(records))),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error getting returns for order");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn update_return_status<T: ReturnService>(
+        State(mut service): State<T>,
+        Path(return_id): Path<i64>,
+        Json(payload): Json<NewReturnStatusRequest>,
+    ) -> Result<StatusCode, StatusCode> {
+        match service
+            .set_return_status(return_id, payload.return_status, payload.refund_amount)
+            .await
+        {
+            Ok(_) => Ok(StatusCode::ACCEPTED),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error setting return status");
+                Err(e.into())
+            }
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::{model, service::shipment::ShipmentService};
+
+type JsonResult<T> = Result<(StatusCode, Json<T>), StatusCode>;
+
+pub struct ShipmentHandler;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShipmentEventRequest {
+    pub shipment_status: model::ShipmentStatus,
+    pub occurred_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BuyLabelRequest {
+    pub service_level: Option<String>,
+}
+
+impl ShipmentHandler {
+    pub async fn create_shipment<T: ShipmentService>(
+        State(mut service): State<T>,
+        Json(payload): Json<model::NewShipment>,
+    ) -> JsonResult<model::Record<model::ShipmentDetails>> {
+        match service.create_shipment(&payload).await {
+            Ok(id) => match service.get_shipment(id).await {
+                Ok(Some(record)) => Ok((StatusCode::CREATED, Json(record))),
+                Ok(None) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+                Err(e) => {
+                    warn!("{}", e);
+                    Err(e.into())
+                }
+            },
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error creating shipment");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn get_shipment<T: ShipmentService>(
+        State(mut service): State<T>,
+        Path(shipment_id): Path<i64>,
+    ) -> JsonResult<model::Record<model::ShipmentDetails>> {
+        match service.get_shipment(shipment_id).await {
+            Ok(Some(record)) => Ok((StatusCode::OK, Json(record))),
+            Ok(None) => Err(StatusCode::NOT_FOUND),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error getting shipment");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn get_shipments_by_fulfillment_id<T: ShipmentService>(
+        State(mut service): State<T>,
+        Path(fulfillment_id): Path<i64>,
+    ) -> JsonResult<Vec<model::Record<model::ShipmentDetails>>> {
+        match service
+            .get_shipments_by_fulfillment_id(fulfillment_id)
+            .await
+        {
+            Ok(records) => Ok((StatusCode::OK, Json(records))),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error getting shipments for fulfillment");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn record_shipment_event<T: ShipmentService>(
+        State(mut service): State<T>,
+        Path(shipment_id): Path<i64>,
+        Json(payload): Json<ShipmentEventRequest>,
+    ) -> Result<StatusCode, StatusCode> {
+        match service
+            .record_shipment_event(shipment_id, payload.shipment_status, payload.occurred_at)
+            .await
+        {
+            Ok(_) => Ok(StatusCode::ACCEPTED),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error recording shipment event");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn quote_shipment_rates<T: ShipmentService>(
+        State(mut service): State<T>,
+        Path(shipment_id): Path<i64>,
+    ) -> JsonResult<Vec<model::RateQuote>> {
+        match service.quote_shipment_rates(shipment_id).await {
+            Ok(quotes) => Ok((StatusCode::OK, Json(quotes))),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error quoting shipment rates");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn buy_shipment_label<T: ShipmentService>(
+        State(mut service): State<T>,
+        Path(shipment_id): Path<i64>,
+        Json(payload): Json<BuyLabelRequest>,
+    ) -> JsonResult<model::Label> {
+        match service
+            .buy_shipment_label(shipment_id, payload.service_level)
+            .await
+        {
+            Ok(label) => Ok((StatusCode::CREATED, Json(label))),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error buying shipment label");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn void_shipment_label<T: ShipmentService>(
+        State(mut service): State<T>,
+        Path(shipment_id): Path<i64>,
+    ) -> Result<StatusCode, StatusCode> {
+        match service.void_shipment_label(shipment_id).await {
+            Ok(_) => Ok(StatusCode::ACCEPTED),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error voiding shipment label");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn sync_shipment_tracking<T: ShipmentService>(
+        State(mut service): State<T>,
+        Path(shipment_id): Path<i64>,
+    ) -> JsonResult<model::Record<model::ShipmentDetails>> {
+        match service.sync_shipment_tracking(shipment_id).await {
+            Ok(record) => Ok((StatusCode::OK, Json(record))),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error syncing shipment tracking");
+                Err(e.into())
+            }
+        }
+    }
+}
@@ -0,0 +1,40 @@
+use axum::{extract::State, http::StatusCode, Json};
+use log::warn;
+
+use crate::{
+    model::{self, ToRecord},
+    service::tax::TaxService,
+};
+
+type JsonResult<T> = Result<(StatusCode, Json<T>), StatusCode>;
+
+pub struct TaxHandler;
+
+impl TaxHandler {
+    pub async fn create_tax_class<T: TaxService>(
+        State(mut service): State<T>,
+        Json(payload): Json<model::TaxClassDetails>,
+    ) -> JsonResult<model::Record<model::TaxClassDetails>> {
+        match service.create_tax_class(&payload.name, &payload.rate).await {
+            Ok(id) => Ok((StatusCode::CREATED, Json(payload.to_record(id)))),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error creating tax class");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn get_tax_classes<T: TaxService>(
+        State(mut service): State<T>,
+    ) -> JsonResult<Vec<model::Record<model::TaxClassDetails>>> {
+        match service.get_tax_classes().await {
+            Ok(records) => Ok((StatusCode::OK, Json(records))),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error getting tax classes");
+                Err(e.into())
+            }
+        }
+    }
+}
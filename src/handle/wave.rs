@@ -0,0 +1,84 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::{model, service::wave::WaveService};
+
+type JsonResult<T> = Result<(StatusCode, Json<T>), StatusCode>;
+
+pub struct WaveHandler;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateWave {
+    pub fulfillment_ids: Vec<i64>,
+}
+
+impl WaveHandler {
+    pub async fn create_wave<T: WaveService>(
+        State(mut service): State<T>,
+        Json(payload): Json<CreateWave>,
+    ) -> JsonResult<model::Record<model::WaveDetails>> {
+        match service.create_wave(&payload.fulfillment_ids).await {
+            Ok(id) => match service.get_wave(id).await {
+                Ok(Some(record)) => Ok((StatusCode::CREATED, Json(record))),
+                Ok(None) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+                Err(e) => {
+                    warn!("{}", e);
+                    Err(e.into())
+                }
+            },
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error creating wave");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn get_wave<T: WaveService>(
+        State(mut service): State<T>,
+        Path(wave_id): Path<i64>,
+    ) -> JsonResult<model::Record<model::WaveDetails>> {
+        match service.get_wave(wave_id).await {
+            Ok(Some(record)) => Ok((StatusCode::OK, Json(record))),
+            Ok(None) => Err(StatusCode::NOT_FOUND),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error getting wave");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn get_pick_list<T: WaveService>(
+        State(mut service): State<T>,
+        Path(wave_id): Path<i64>,
+    ) -> JsonResult<model::PickList> {
+        match service.get_pick_list(wave_id).await {
+            Ok(pick_list) => Ok((StatusCode::OK, Json(pick_list))),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error getting pick list");
+                Err(e.into())
+            }
+        }
+    }
+
+    pub async fn start_wave<T: WaveService>(
+        State(mut service): State<T>,
+        Path(wave_id): Path<i64>,
+    ) -> Result<StatusCode, StatusCode> {
+        match service.start_wave(wave_id).await {
+            Ok(_) => Ok(StatusCode::ACCEPTED),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("error starting wave");
+                Err(e.into())
+            }
+        }
+    }
+}
@@ -1,19 +1,28 @@
 use axum::{
-    routing::{get, post, put},
+    routing::{delete, get, post, put},
     Router,
 };
 use log::info;
 use provider::SqliteProvider;
 use service::{
-    fulfillment::FulfillmentService, line_item::LineItemService, product::ProductService,
+    category::CategoryService, currency::ExchangeRateService, customer::CustomerService,
+    delivery_run::DeliveryRunService, fulfillment::FulfillmentService, inventory::InventoryService,
+    invoice::InvoiceService, line_item::LineItemService, order::OrderService,
+    pickup::PickupService, price_list::PriceListService, product::ProductService,
+    rma::ReturnService, shipment::ShipmentService, tax::TaxService, wave::WaveService,
 };
 
+mod carrier;
 mod handle;
 mod model;
 mod provider;
+mod render;
 mod service;
 
-use handle::{fulfillment, line_item, product};
+use handle::{
+    category, currency, customer, delivery_run, fulfillment, inventory, invoice, line_item, order,
+    pickup, price_list, product, rma, shipment, tax, wave,
+};
 use tower_http::cors::CorsLayer;
 
 #[tokio::main]
@@ -22,35 +31,164 @@ async fn main() {
 
     let mut sqlite_provider = provider::SqliteProvider::new_memory().await.unwrap();
 
+    // Fulfillment workflows can be replaced per type from a JSON config file
+    if let Ok(path) = std::env::var("FULFILLMENT_WORKFLOWS") {
+        let config = std::fs::read_to_string(&path).unwrap();
+        let workflows = model::Workflows::from_json(&config).unwrap();
+        info!("Loaded fulfillment workflows from {}", path);
+        sqlite_provider = sqlite_provider.with_workflows(workflows);
+    }
+
     // Init provider for each backend
 
     FulfillmentService::init_provider(&mut sqlite_provider)
         .await
         .unwrap();
+    CategoryService::init_provider(&mut sqlite_provider)
+        .await
+        .unwrap();
     ProductService::init_provider(&mut sqlite_provider)
         .await
         .unwrap();
     LineItemService::init_provider(&mut sqlite_provider)
         .await
         .unwrap();
+    PriceListService::init_provider(&mut sqlite_provider)
+        .await
+        .unwrap();
+    TaxService::init_provider(&mut sqlite_provider)
+        .await
+        .unwrap();
+    ExchangeRateService::init_provider(&mut sqlite_provider)
+        .await
+        .unwrap();
+    CustomerService::init_provider(&mut sqlite_provider)
+        .await
+        .unwrap();
+    OrderService::init_provider(&mut sqlite_provider)
+        .await
+        .unwrap();
+    InvoiceService::init_provider(&mut sqlite_provider)
+        .await
+        .unwrap();
+    InventoryService::init_provider(&mut sqlite_provider)
+        .await
+        .unwrap();
+    ReturnService::init_provider(&mut sqlite_provider)
+        .await
+        .unwrap();
+    ShipmentService::init_provider(&mut sqlite_provider)
+        .await
+        .unwrap();
+    WaveService::init_provider(&mut sqlite_provider)
+        .await
+        .unwrap();
+    PickupService::init_provider(&mut sqlite_provider)
+        .await
+        .unwrap();
+    DeliveryRunService::init_provider(&mut sqlite_provider)
+        .await
+        .unwrap();
 
     let app: Router<()> = Router::new()
         .route(
             "/product",
-            post(product::ProductHandler::create_product::<SqliteProvider>),
+            get(product::ProductHandler::get_catalog::<SqliteProvider>)
+                .post(product::ProductHandler::create_product::<SqliteProvider>),
+        )
+        .route(
+            "/product/search",
+            get(product::ProductHandler::search_products::<SqliteProvider>),
         )
         .route(
             "/product/:product_id",
             get(product::ProductHandler::get_product::<SqliteProvider>),
         )
+        .route(
+            "/product/:product_id/barcodes",
+            post(product::ProductHandler::add_product_barcode::<SqliteProvider>),
+        )
+        .route(
+            "/barcode/:code",
+            get(product::ProductHandler::find_product_by_barcode::<SqliteProvider>),
+        )
+        .route(
+            "/category",
+            get(category::CategoryHandler::get_categories::<SqliteProvider>)
+                .post(category::CategoryHandler::create_category::<SqliteProvider>),
+        )
+        .route(
+            "/category/:category_id",
+            get(category::CategoryHandler::get_category::<SqliteProvider>),
+        )
         .route(
             "/fulfillment",
             post(fulfillment::FulfillmentHandler::create_fulfillment::<SqliteProvider>),
         )
+        .route(
+            "/fulfillment/:fulfillment_id",
+            get(fulfillment::FulfillmentHandler::get_fulfillment::<SqliteProvider>),
+        )
         .route(
             "/fulfillment/:fulfillment_id/status",
             put(fulfillment::FulfillmentHandler::update_fulfillment_status::<SqliteProvider>),
         )
+        .route(
+            "/fulfillment/:fulfillment_id/statusHistory",
+            get(fulfillment::FulfillmentHandler::get_fulfillment_status_history::<SqliteProvider>),
+        )
+        .route(
+            "/fulfillment/:fulfillment_id/scan",
+            post(fulfillment::FulfillmentHandler::scan_item::<SqliteProvider>),
+        )
+        .route(
+            "/fulfillment/:fulfillment_id/assignee",
+            put(fulfillment::FulfillmentHandler::assign_fulfillment::<SqliteProvider>),
+        )
+        .route(
+            "/workQueue",
+            get(fulfillment::FulfillmentHandler::get_work_queue::<SqliteProvider>),
+        )
+        .route(
+            "/fulfillment/:fulfillment_id/label",
+            get(fulfillment::FulfillmentHandler::get_shipping_label_zpl::<SqliteProvider>),
+        )
+        .route(
+            "/fulfillment/:fulfillment_id/label/text",
+            get(fulfillment::FulfillmentHandler::get_shipping_label_text::<SqliteProvider>),
+        )
+        .route(
+            "/fulfillment/:fulfillment_id/packingSlip",
+            get(fulfillment::FulfillmentHandler::get_packing_slip::<SqliteProvider>),
+        )
+        .route(
+            "/fulfillment/:fulfillment_id/packingSlip/html",
+            get(fulfillment::FulfillmentHandler::get_packing_slip_html::<SqliteProvider>),
+        )
+        .route(
+            "/wave",
+            post(wave::WaveHandler::create_wave::<SqliteProvider>),
+        )
+        .route(
+            "/wave/:wave_id",
+            get(wave::WaveHandler::get_wave::<SqliteProvider>),
+        )
+        .route(
+            "/wave/:wave_id/pickList",
+            get(wave::WaveHandler::get_pick_list::<SqliteProvider>),
+        )
+        .route(
+            "/wave/:wave_id/start",
+            post(wave::WaveHandler::start_wave::<SqliteProvider>),
+        )
+        .route(
+            "/workflow",
+            get(fulfillment::FulfillmentHandler::get_workflows::<SqliteProvider>),
+        )
+        .route(
+            "/fulfillment/:fulfillment_id/supplierReference",
+            put(fulfillment::FulfillmentHandler::set_supplier_reference::<SqliteProvider>),
+        )
         .route(
             "/lineItem",
             post(line_item::LineItemHandler::create_line_item::<SqliteProvider>),
@@ -59,6 +197,197 @@ async fn main() {
             "/fulfillment/:fulfillment_id/lineItems",
             get(line_item::LineItemHandler::get_line_item_by_fulfillment_id::<SqliteProvider>),
         )
+        .route(
+            "/lineItem/:line_item_id/quantityFulfilled",
+            put(line_item::LineItemHandler::set_quantity_fulfilled::<SqliteProvider>),
+        )
+        .route(
+            "/product/:product_id/price",
+            get(price_list::PriceListHandler::get_product_price::<SqliteProvider>),
+        )
+        .route(
+            "/priceList",
+            post(price_list::PriceListHandler::create_price_list::<SqliteProvider>),
+        )
+        .route(
+            "/priceList/:price_list_id",
+            get(price_list::PriceListHandler::get_price_list::<SqliteProvider>),
+        )
+        .route(
+            "/priceList/:price_list_id/prices",
+            get(price_list::PriceListHandler::get_price_list_entries::<SqliteProvider>)
+                .post(price_list::PriceListHandler::set_product_price::<SqliteProvider>),
+        )
+        .route(
+            "/order",
+            post(order::OrderHandler::create_order::<SqliteProvider>),
+        )
+        .route(
+            "/order/:order_id",
+            get(order::OrderHandler::get_order::<SqliteProvider>),
+        )
+        .route(
+            "/order/:order_id/lines",
+            get(order::OrderHandler::get_order_lines::<SqliteProvider>)
+                .post(order::OrderHandler::add_order_line::<SqliteProvider>),
+        )
+        .route(
+            "/order/:order_id/lines/:order_line_id",
+            delete(order::OrderHandler::remove_order_line::<SqliteProvider>),
+        )
+        .route(
+            "/order/:order_id/lines/:order_line_id/discount",
+            put(order::OrderHandler::set_order_line_discount::<SqliteProvider>),
+        )
+        .route(
+            "/order/:order_id/status",
+            put(order::OrderHandler::update_order_status::<SqliteProvider>),
+        )
+        .route(
+            "/order/:order_id/payments",
+            get(order::OrderHandler::get_payments::<SqliteProvider>)
+                .post(order::OrderHandler::record_payment::<SqliteProvider>),
+        )
+        .route(
+            "/order/:order_id/discount",
+            put(order::OrderHandler::set_order_discount::<SqliteProvider>),
+        )
+        .route(
+            "/order/:order_id/invoice",
+            get(invoice::InvoiceHandler::get_invoice::<SqliteProvider>),
+        )
+        .route(
+            "/order/:order_id/invoice/html",
+            get(invoice::InvoiceHandler::get_invoice_html::<SqliteProvider>),
+        )
+        .route(
+            "/order/:order_id/invoice/text",
+            get(invoice::InvoiceHandler::get_invoice_text::<SqliteProvider>),
+        )
+        .route(
+            "/invoiceSequence",
+            get(invoice::InvoiceHandler::get_invoice_sequences::<SqliteProvider>)
+                .post(invoice::InvoiceHandler::create_invoice_sequence::<SqliteProvider>),
+        )
+        .route(
+            "/taxClass",
+            get(tax::TaxHandler::get_tax_classes::<SqliteProvider>)
+                .post(tax::TaxHandler::create_tax_class::<SqliteProvider>),
+        )
+        .route(
+            "/exchangeRate",
+            get(currency::ExchangeRateHandler::get_exchange_rates::<SqliteProvider>)
+                .post(currency::ExchangeRateHandler::set_exchange_rate::<SqliteProvider>),
+        )
+        .route(
+            "/location",
+            get(inventory::InventoryHandler::get_locations::<SqliteProvider>)
+                .post(inventory::InventoryHandler::create_location::<SqliteProvider>),
+        )
+        .route(
+            "/location/:location_id",
+            get(inventory::InventoryHandler::get_location::<SqliteProvider>),
+        )
+        .route(
+            "/pickupSlot",
+            post(pickup::PickupHandler::create_pickup_slot::<SqliteProvider>),
+        )
+        .route(
+            "/pickupSlot/:pickup_slot_id",
+            get(pickup::PickupHandler::get_pickup_slot::<SqliteProvider>),
+        )
+        .route(
+            "/location/:location_id/pickups",
+            get(pickup::PickupHandler::get_pickup_day::<SqliteProvider>),
+        )
+        .route(
+            "/fulfillment/:fulfillment_id/pickupSlot",
+            put(pickup::PickupHandler::book_pickup_slot::<SqliteProvider>),
+        )
+        .route(
+            "/deliveryRun",
+            get(delivery_run::DeliveryRunHandler::get_delivery_runs::<SqliteProvider>)
+                .post(delivery_run::DeliveryRunHandler::create_delivery_run::<SqliteProvider>),
+        )
+        .route(
+            "/deliveryRun/:delivery_run_id",
+            get(delivery_run::DeliveryRunHandler::get_delivery_run::<SqliteProvider>),
+        )
+        .route(
+            "/deliveryRun/:delivery_run_id/status",
+            put(delivery_run::DeliveryRunHandler::update_delivery_run_status::<SqliteProvider>),
+        )
+        .route(
+            "/product/:product_id/stock",
+            get(inventory::InventoryHandler::get_stock_level::<SqliteProvider>),
+        )
+        .route(
+            "/product/:product_id/stockMovements",
+            get(inventory::InventoryHandler::get_stock_movements::<SqliteProvider>)
+                .post(inventory::InventoryHandler::record_stock_movement::<SqliteProvider>),
+        )
+        .route(
+            "/product/:product_id/backorders",
+            get(inventory::InventoryHandler::get_backorders::<SqliteProvider>),
+        )
+        .route(
+            "/return",
+            post(rma::ReturnHandler::create_return::<SqliteProvider>),
+        )
+        .route(
+            "/return/:return_id",
+            get(rma::ReturnHandler::get_return::<SqliteProvider>),
+        )
+        .route(
+            "/return/:return_id/status",
+            put(rma::ReturnHandler::update_return_status::<SqliteProvider>),
+        )
+        .route(
+            "/order/:order_id/returns",
+            get(rma::ReturnHandler::get_returns_by_order_id::<SqliteProvider>),
+        )
+        .route(
+            "/shipment",
+            post(shipment::ShipmentHandler::create_shipment::<SqliteProvider>),
+        )
+        .route(
+            "/shipment/:shipment_id",
+            get(shipment::ShipmentHandler::get_shipment::<SqliteProvider>),
+        )
+        .route(
+            "/shipment/:shipment_id/events",
+            post(shipment::ShipmentHandler::record_shipment_event::<SqliteProvider>),
+        )
+        .route(
+            "/shipment/:shipment_id/rates",
+            get(shipment::ShipmentHandler::quote_shipment_rates::<SqliteProvider>),
+        )
+        .route(
+            "/shipment/:shipment_id/label",
+            post(shipment::ShipmentHandler::buy_shipment_label::<SqliteProvider>)
+                .delete(shipment::ShipmentHandler::void_shipment_label::<SqliteProvider>),
+        )
+        .route(
+            "/shipment/:shipment_id/tracking",
+            post(shipment::ShipmentHandler::sync_shipment_tracking::<SqliteProvider>),
+        )
+        .route(
+            "/fulfillment/:fulfillment_id/shipments",
+            get(shipment::ShipmentHandler::get_shipments_by_fulfillment_id::<SqliteProvider>),
+        )
+        .route(
+            "/customer",
+            post(customer::CustomerHandler::create_customer::<SqliteProvider>),
+        )
+        .route(
+            "/customer/:customer_id",
+            get(customer::CustomerHandler::get_customer::<SqliteProvider>),
+        )
+        .route(
+            "/customer/:customer_id/addresses",
+            get(customer::CustomerHandler::get_addresses::<SqliteProvider>)
+                .post(customer::CustomerHandler::add_address::<SqliteProvider>),
+        )
         .with_state(sqlite_provider)
         .layer(CorsLayer::permissive());
 
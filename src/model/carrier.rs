@@ -0,0 +1,42 @@
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use super::{Address, ShipmentStatus};
+
+/// Package a carrier is asked to price or label
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RateRequest {
+    pub ship_to: Address,
+    pub weight_grams: Option<i64>,
+    pub length_mm: Option<i64>,
+    pub width_mm: Option<i64>,
+    pub height_mm: Option<i64>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RateQuote {
+    pub carrier: String,
+    pub service_level: String,
+    pub amount: Decimal,
+    pub currency: String,
+    pub transit_days: i64,
+}
+
+/// Label bought from a carrier
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Label {
+    pub tracking_number: String,
+    pub service_level: String,
+    pub amount: Decimal,
+    pub currency: String,
+    /// Label document as the carrier returns it, e.g. ZPL
+    pub data: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TrackingEvent {
+    pub status: ShipmentStatus,
+    pub occurred_at: DateTime<Utc>,
+    pub description: String,
+}
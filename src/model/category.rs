@@ -0,0 +1,93 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::ToRecord;
+
+impl ToRecord for CategoryDetails {}
+
+/// Node in the catalog's category tree
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CategoryDetails {
+    pub name: String,
+    #[serde(default)]
+    pub parent_id: Option<i64>,
+    /// Attributes typed by this category, for its products and every category below it
+    #[serde(default)]
+    pub attributes: Vec<AttributeDefinition>,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct AttributeDefinition {
+    pub name: String,
+    pub kind: AttributeKind,
+    /// What an enum attribute can be set to
+    #[serde(default)]
+    pub values: Vec<String>,
+}
+
+impl AttributeDefinition {
+    pub fn check(&self, value: &AttributeValue) -> Result<(), String> {
+        match (&self.kind, value) {
+            (AttributeKind::String, AttributeValue::String(_))
+            | (AttributeKind::Number, AttributeValue::Number(_))
+            | (AttributeKind::Bool, AttributeValue::Bool(_)) => Ok(()),
+            (AttributeKind::Enum, AttributeValue::String(s)) if self.values.contains(s) => Ok(()),
+            (AttributeKind::Enum, _) => Err(format!(
+                "{} must be one of {}",
+                self.name,
+                self.values.join(", ")
+            )),
+            (kind, _) => Err(format!("{} must be a {:?}", self.name, kind)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum AttributeKind {
+    String,
+    Number,
+    Bool,
+    /// A string out of the definition's values
+    Enum,
+}
+
+/// Attribute on a product, typed by the nearest category defining it, or free-form when
+/// none does
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum AttributeValue {
+    Bool(bool),
+    Number(f64),
+    String(String),
+}
+
+/// Narrows the catalog listing, every part has to match
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct ProductFilter {
+    /// Matches products in this category or any below it
+    pub category_id: Option<i64>,
+    pub attributes: BTreeMap<String, String>,
+}
+
+impl ProductFilter {
+    /// Attribute filters keyed by name, each value as `[text, flag, number]` with whichever
+    /// don't parse left null, so it compares against the type the product has
+    pub fn attributes_json(&self) -> String {
+        let attributes: serde_json::Map<String, serde_json::Value> = self
+            .attributes
+            .iter()
+            .map(|(name, value)| {
+                (
+                    name.clone(),
+                    serde_json::json!([
+                        value,
+                        value.parse::<bool>().ok(),
+                        value.parse::<f64>().ok()
+                    ]),
+                )
+            })
+            .collect();
+        serde_json::Value::Object(attributes).to_string()
+    }
+}
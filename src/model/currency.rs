@@ -0,0 +1,51 @@
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use super::ToRecord;
+
+/// Currency product base prices are kept in
+pub const BASE_CURRENCY: &str = "USD";
+
+pub fn base_currency() -> String {
+    BASE_CURRENCY.to_string()
+}
+
+/// Normalizes an ISO 4217 code, e.g. `eur` to `EUR`
+pub fn parse_currency_code(code: &str) -> Result<String, String> {
+    let code = code.trim().to_ascii_uppercase();
+    if code.len() == 3 && code.chars().all(|c| c.is_ascii_alphabetic()) {
+        Ok(code)
+    } else {
+        Err(format!("{} is not a currency code", code))
+    }
+}
+
+/// Number of decimal places amounts in `code` are rounded to
+pub fn minor_units(code: &str) -> u32 {
+    match code {
+        "BIF" | "CLP" | "DJF" | "GNF" | "ISK" | "JPY" | "KMF" | "KRW" | "PYG" | "RWF" | "UGX"
+        | "VND" | "VUV" | "XAF" | "XOF" | "XPF" => 0,
+        "BHD" | "IQD" | "JOD" | "KWD" | "LYD" | "OMR" | "TND" => 3,
+        _ => 2,
+    }
+}
+
+impl ToRecord for ExchangeRateDetails {}
+
+/// One unit of `base_currency` is worth `rate` units of `quote_currency`
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ExchangeRateDetails {
+    pub base_currency: String,
+    pub quote_currency: String,
+    pub rate: Decimal,
+    pub effective_at: DateTime<Utc>,
+}
+
+/// Rate fixed on an order the first time it needed to convert from `from_currency`
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct LockedExchangeRate {
+    pub from_currency: String,
+    pub to_currency: String,
+    pub rate: Decimal,
+}
@@ -0,0 +1,42 @@
+use serde::{Deserialize, Serialize};
+
+use super::ToRecord;
+
+impl ToRecord for CustomerDetails {}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CustomerDetails {
+    pub name: String,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+}
+
+/// Postal address, also used as the ship-to snapshot on delivery fulfillments
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Address {
+    pub recipient: String,
+    pub line1: String,
+    pub line2: Option<String>,
+    pub city: String,
+    pub region: Option<String>,
+    pub postal_code: String,
+    pub country: String,
+    /// Used to order stops on delivery runs
+    #[serde(default)]
+    pub latitude: Option<f64>,
+    #[serde(default)]
+    pub longitude: Option<f64>,
+}
+
+impl ToRecord for AddressDetails {}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AddressDetails {
+    pub customer_id: i64,
+    pub label: Option<String>,
+    /// Used when an order or fulfillment doesn't name an address
+    #[serde(default)]
+    pub is_default: bool,
+    #[serde(flatten)]
+    pub address: Address,
+}
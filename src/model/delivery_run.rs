@@ -0,0 +1,90 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::ToRecord;
+
+impl ToRecord for DeliveryRunDetails {}
+
+/// Vehicle's trip out of a depot to drop off stock deliveries on a day
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct DeliveryRunDetails {
+    pub date: NaiveDate,
+    pub status: DeliveryRunStatus,
+    /// Where the vehicle sets off from
+    pub start_latitude: f64,
+    pub start_longitude: f64,
+    /// Most the vehicle can carry
+    pub capacity_grams: i64,
+    /// What the stops weigh together
+    pub load_grams: i64,
+    /// Straight line distance over every leg of the run
+    pub distance_km: f64,
+    /// In the order they're visited
+    pub stops: Vec<DeliveryStop>,
+    pub dispatched_at: Option<DateTime<Utc>>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct DeliveryStop {
+    pub fulfillment_id: i64,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub weight_grams: i64,
+    /// From the previous stop, or the start for the first one
+    pub distance_km: f64,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct NewDeliveryRun {
+    pub date: NaiveDate,
+    pub start_latitude: f64,
+    pub start_longitude: f64,
+    pub capacity_grams: i64,
+    pub fulfillment_ids: Vec<i64>,
+}
+
+impl From<DeliveryRunStatus> for String {
+    fn from(value: DeliveryRunStatus) -> Self {
+        format!("{:?}", value)
+    }
+}
+
+impl FromStr for DeliveryRunStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Planned" => Ok(Self::Planned),
+            "Dispatched" => Ok(Self::Dispatched),
+            "Completed" => Ok(Self::Completed),
+            "Cancelled" => Ok(Self::Cancelled),
+            s => Err(format!("unknown delivery run status {}", s)),
+        }
+    }
+}
+
+impl DeliveryRunStatus {
+    pub fn allowed_priors(&self) -> Vec<Self> {
+        match self {
+            Self::Dispatched => vec![Self::Planned],
+            Self::Completed => vec![Self::Dispatched],
+            Self::Cancelled => vec![Self::Planned],
+            _ => vec![],
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum DeliveryRunStatus {
+    /// Stops can't be on any other planned or dispatched run
+    Planned,
+    /// Vehicle has left the depot
+    Dispatched,
+    /// Every stop has been delivered
+    Completed,
+    /// Called off before dispatch, its stops are free to plan again
+    Cancelled,
+}
@@ -1,6 +1,9 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
-use super::ToRecord;
+use super::{Address, ToRecord};
 
 impl ToRecord for FulfillmentDetails {}
 
@@ -8,6 +11,70 @@ impl ToRecord for FulfillmentDetails {}
 pub struct FulfillmentDetails {
     pub fulfillment_type: FulfillmentType,
     pub status: FulfillmentStatus,
+    pub order_id: Option<i64>,
+    /// Snapshot of the delivery address taken when the fulfillment was created
+    pub ship_to: Option<Address>,
+    /// Supplier's order or shipment reference for drop-ship fulfillments
+    pub supplier_reference: Option<String>,
+    /// Location stock is taken from, stock and transfer fulfillments only
+    pub from_location_id: Option<i64>,
+    /// Location a transfer moves stock into
+    pub to_location_id: Option<i64>,
+    /// Pickup slot booked for collection, pick-ups only
+    pub pickup_slot_id: Option<i64>,
+    /// Picker or driver working on the fulfillment
+    pub assignee: Option<String>,
+}
+
+/// Status transition taken by a fulfillment, with who it was assigned to at the time
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct FulfillmentStatusEvent {
+    pub from_status: FulfillmentStatus,
+    pub to_status: FulfillmentStatus,
+    pub assignee: Option<String>,
+    pub changed_at: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct FulfillmentStatusChange {
+    pub fulfillment_status: FulfillmentStatus,
+    /// Allows fulfilling a pick-up before its booked slot opens
+    #[serde(default)]
+    pub override_pickup_slot: bool,
+}
+
+impl From<FulfillmentStatus> for FulfillmentStatusChange {
+    fn from(fulfillment_status: FulfillmentStatus) -> Self {
+        Self {
+            fulfillment_status,
+            override_pickup_slot: false,
+        }
+    }
+}
+
+/// Everything needed to create a fulfillment, only some fields apply to each type
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct NewFulfillment {
+    pub fulfillment_type: FulfillmentType,
+    pub order_id: Option<i64>,
+    /// Delivery and drop-ship, falls back to the order customer's default address
+    pub ship_to_address_id: Option<i64>,
+    pub supplier_reference: Option<String>,
+    pub from_location_id: Option<i64>,
+    pub to_location_id: Option<i64>,
+}
+
+impl From<FulfillmentType> for NewFulfillment {
+    fn from(fulfillment_type: FulfillmentType) -> Self {
+        Self {
+            fulfillment_type,
+            order_id: None,
+            ship_to_address_id: None,
+            supplier_reference: None,
+            from_location_id: None,
+            to_location_id: None,
+        }
+    }
 }
 
 impl From<FulfillmentType> for String {
@@ -16,19 +83,109 @@ impl From<FulfillmentType> for String {
     }
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+impl FromStr for FulfillmentType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "StockPickUp" => Ok(Self::StockPickUp),
+            "StockDelivery" => Ok(Self::StockDelivery),
+            "DropShip" => Ok(Self::DropShip),
+            "Transfer" => Ok(Self::Transfer),
+            "Service" => Ok(Self::Service),
+            s => Err(format!("unknown fulfillment type {}", s)),
+        }
+    }
+}
+
+impl FulfillmentType {
+    pub fn all() -> Vec<Self> {
+        vec![
+            Self::StockPickUp,
+            Self::StockDelivery,
+            Self::DropShip,
+            Self::Transfer,
+            Self::Service,
+        ]
+    }
+
+    /// Statuses a fulfillment of this type may move to `status` from in its built in
+    /// flow, configured workflows replace these
+    pub fn allowed_priors(&self, status: &FulfillmentStatus) -> Vec<FulfillmentStatus> {
+        match self {
+            Self::StockPickUp | Self::StockDelivery | Self::Transfer => status.allowed_priors(),
+            // nothing to pick, the supplier confirms shipment
+            Self::DropShip => match status {
+                FulfillmentStatus::Initialized => vec![FulfillmentStatus::New],
+                FulfillmentStatus::Fulfilled => vec![FulfillmentStatus::Initialized],
+                _ => vec![],
+            },
+            // nothing to reserve, work starts straight away
+            Self::Service => match status {
+                FulfillmentStatus::InProgress => vec![FulfillmentStatus::New],
+                FulfillmentStatus::Fulfilled => vec![FulfillmentStatus::InProgress],
+                _ => vec![],
+            },
+        }
+    }
+
+    /// Reserves stock on initialization and issues it from our own location when fulfilled
+    pub fn uses_stock(&self) -> bool {
+        matches!(self, Self::StockPickUp | Self::StockDelivery)
+    }
+
+    /// Picked off our own shelves before it leaves
+    pub fn is_picked(&self) -> bool {
+        matches!(
+            self,
+            Self::StockPickUp | Self::StockDelivery | Self::Transfer
+        )
+    }
+
+    /// Goes to the customer's address
+    pub fn ships_to_customer(&self) -> bool {
+        matches!(self, Self::StockDelivery | Self::DropShip)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub enum FulfillmentType {
     StockPickUp,
     StockDelivery,
+    /// Supplier ships straight to the customer
+    DropShip,
+    /// Moves stock between two of our locations
+    Transfer,
+    /// Work rather than goods, no stock moves
+    Service,
 }
 
 impl From<FulfillmentStatus> for String {
     fn from(value: FulfillmentStatus) -> Self {
-        format!("{:?}", value)
+        match value {
+            FulfillmentStatus::Custom(status) => status,
+            status => format!("{:?}", status),
+        }
+    }
+}
+
+impl FromStr for FulfillmentStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "New" => Ok(Self::New),
+            "Initialized" => Ok(Self::Initialized),
+            "InProgress" => Ok(Self::InProgress),
+            "Fulfilled" => Ok(Self::Fulfilled),
+            "" => Err("empty fulfillment status".to_string()),
+            s => Ok(Self::Custom(s.to_string())),
+        }
     }
 }
 
 impl FulfillmentStatus {
+    /// Built in flow for fulfillments picked from stock, see `FulfillmentType::allowed_priors`
     pub fn allowed_priors(&self) -> Vec<Self> {
         match self {
             Self::Initialized => vec![Self::New],
@@ -39,10 +196,13 @@ impl FulfillmentStatus {
     }
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub enum FulfillmentStatus {
     New,
     Initialized,
     InProgress,
     Fulfilled,
+    /// Extra step added by a configured workflow, e.g. `ReadyForPickup`
+    #[serde(untagged)]
+    Custom(String),
 }
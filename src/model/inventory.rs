@@ -0,0 +1,82 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::ToRecord;
+
+/// Location stock is kept at unless another is given
+pub const DEFAULT_LOCATION_ID: i64 = 1;
+
+impl ToRecord for LocationDetails {}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct LocationDetails {
+    pub name: String,
+}
+
+impl ToRecord for StockMovementDetails {}
+
+/// Entry in the stock ledger, on hand stock is the sum of all movements for a product
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct StockMovementDetails {
+    pub product_id: i64,
+    pub location_id: i64,
+    /// Positive into stock, negative out of it
+    pub quantity: i64,
+    pub reason: StockMovementReason,
+    /// What caused the movement, e.g. `fulfillment:3` or `return:7`
+    pub reference: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct StockLevel {
+    pub product_id: i64,
+    pub on_hand: i64,
+    /// Held for line items on fulfillments that haven't been fulfilled yet
+    pub reserved: i64,
+    pub available: i64,
+    pub locations: Vec<LocationStock>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct LocationStock {
+    pub location_id: i64,
+    pub on_hand: i64,
+}
+
+impl From<StockMovementReason> for String {
+    fn from(value: StockMovementReason) -> Self {
+        format!("{:?}", value)
+    }
+}
+
+impl FromStr for StockMovementReason {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Receipt" => Ok(Self::Receipt),
+            "Issue" => Ok(Self::Issue),
+            "Return" => Ok(Self::Return),
+            "Adjustment" => Ok(Self::Adjustment),
+            "Transfer" => Ok(Self::Transfer),
+            s => Err(format!("unknown stock movement reason {}", s)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum StockMovementReason {
+    /// Goods arriving from a supplier
+    Receipt,
+    /// Goods leaving with a fulfillment
+    Issue,
+    /// Goods coming back from a customer
+    Return,
+    /// Stock count corrections
+    Adjustment,
+    /// Goods moving between locations, recorded once out of one and once into the other
+    Transfer,
+}
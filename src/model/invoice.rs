@@ -0,0 +1,54 @@
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use super::{Address, CustomerDetails, OrderTotals, ToRecord};
+
+impl ToRecord for InvoiceDetails {}
+
+/// Invoice document, captured when the order was sold and never changed afterwards
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct InvoiceDetails {
+    pub order_id: i64,
+    pub sequence: String,
+    /// Position in the sequence, numbers within a sequence have no gaps
+    pub number: i64,
+    /// Printed invoice number, the sequence prefix followed by `number`
+    pub invoice_number: String,
+    pub issued_at: DateTime<Utc>,
+    pub currency: String,
+    pub customer: Option<CustomerDetails>,
+    pub bill_to: Option<Address>,
+    pub lines: Vec<InvoiceLine>,
+    pub totals: OrderTotals,
+    /// Payments taken before the invoice was issued, in minor units
+    pub amount_paid: i64,
+}
+
+/// Amounts other than `unit_price` are in minor units of the invoice currency
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct InvoiceLine {
+    pub product_id: i64,
+    pub sku: String,
+    pub description: String,
+    pub quantity: i64,
+    pub unit_price: Decimal,
+    pub tax_rate: Decimal,
+    pub subtotal: i64,
+    pub discount: i64,
+    pub tax: i64,
+    pub total: i64,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct InvoiceSequenceDetails {
+    pub name: String,
+    pub prefix: String,
+    pub next_number: i64,
+}
+
+pub const DEFAULT_INVOICE_SEQUENCE: &str = "default";
+
+pub fn format_invoice_number(prefix: &str, number: i64) -> String {
+    format!("{}{:06}", prefix, number)
+}
@@ -0,0 +1,21 @@
+use serde::{Deserialize, Serialize};
+
+use super::Address;
+
+/// What goes on the thermal label stuck to a delivery
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ShippingLabel {
+    pub fulfillment_id: i64,
+    pub order_id: Option<i64>,
+    /// Name of the location the goods leave from
+    pub ship_from: Option<String>,
+    pub ship_to: Address,
+    pub lines: Vec<LabelLine>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct LabelLine {
+    pub sku: String,
+    pub description: String,
+    pub quantity: i64,
+}
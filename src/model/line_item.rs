@@ -8,6 +8,23 @@ impl ToRecord for LineItemDetails {}
 pub struct LineItemDetails {
     pub product_id: i64,
     pub fulfillment_id: i64,
+    /// In the product's base unit, as are the fulfilled and reserved quantities
     pub quantity: i64,
     pub quantity_fulfilled: i64,
+    /// Stock held for this line, taken when its fulfillment is initialized
+    #[serde(default)]
+    pub quantity_reserved: i64,
+    /// Split off a line that couldn't be fully reserved, waiting on stock
+    #[serde(default)]
+    pub is_backorder: bool,
+    /// Unit the line was ordered in
+    #[serde(default)]
+    pub unit: String,
+    /// Base units in one of `unit` when the line was added
+    #[serde(default = "default_unit_factor")]
+    pub unit_factor: i64,
+}
+
+fn default_unit_factor() -> i64 {
+    1
 }
@@ -1,11 +1,50 @@
+mod carrier;
+mod category;
+mod currency;
+mod customer;
+mod delivery_run;
 mod fulfillment;
+mod inventory;
+mod invoice;
+mod label;
 mod line_item;
 mod order;
+mod packing_slip;
+mod payment;
+mod pickup;
+mod price_list;
 mod product;
+mod rma;
+mod scan;
+mod shipment;
+mod tax;
+mod totals;
+mod wave;
+mod workflow;
 
+pub use carrier::*;
+pub use category::*;
+pub use currency::*;
+pub use customer::*;
+pub use delivery_run::*;
 pub use fulfillment::*;
+pub use inventory::*;
+pub use invoice::*;
+pub use label::*;
 pub use line_item::*;
+pub use order::*;
+pub use packing_slip::*;
+pub use payment::*;
+pub use pickup::*;
+pub use price_list::*;
 pub use product::*;
+pub use rma::*;
+pub use scan::*;
+pub use shipment::*;
+pub use tax::*;
+pub use totals::*;
+pub use wave::*;
+pub use workflow::*;
 
 use serde::{Deserialize, Serialize};
 
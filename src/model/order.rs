@@ -1,14 +1,100 @@
+use std::str::FromStr;
+
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 
+use super::{Discount, LockedExchangeRate, OrderTotals, PricedLine, ToRecord};
+
+impl ToRecord for OrderDetails {}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct OrderDetails {
-    status: OrderStatus,
+    pub status: OrderStatus,
+    pub customer_id: Option<i64>,
+    pub price_list_id: Option<i64>,
+    /// Currency the order is quoted and invoiced in
+    pub currency: String,
+    /// Rates used to convert prices into `currency`, fixed on first use
+    pub exchange_rates: Vec<LockedExchangeRate>,
+    /// Discount on the whole order, spread across lines before tax
+    pub discount: Option<Discount>,
+    /// Recomputed every time the order's lines or discounts change
+    pub totals: OrderTotals,
+    /// Sum of payments in minor units
+    pub amount_paid: i64,
+    /// What's still owed in minor units, negative when overpaid
+    pub balance_due: i64,
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+impl From<OrderStatus> for String {
+    fn from(value: OrderStatus) -> Self {
+        format!("{:?}", value)
+    }
+}
+
+impl FromStr for OrderStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Quote" => Ok(Self::Quote),
+            "Sold" => Ok(Self::Sold),
+            "Done" => Ok(Self::Done),
+            s => Err(format!("unknown order status {}", s)),
+        }
+    }
+}
+
+impl OrderStatus {
+    pub fn allowed_priors(&self) -> Vec<Self> {
+        match self {
+            Self::Sold => vec![Self::Quote],
+            Self::Done => vec![Self::Sold],
+            _ => vec![],
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub enum OrderStatus {
     // NOTE: this may be expounded on or converted to an emergent property
     Quote,
     Sold,
     Done,
 }
+
+/// Requested move to a new order status
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct OrderStatusChange {
+    pub order_status: OrderStatus,
+    /// Allows marking an order `Done` with a balance still due
+    #[serde(default)]
+    pub override_unpaid: bool,
+    /// Sequence numbering the invoice issued when the order is sold, `default` if unset
+    pub invoice_sequence: Option<String>,
+}
+
+impl ToRecord for OrderLineDetails {}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct OrderLineDetails {
+    pub order_id: i64,
+    pub product_id: i64,
+    pub quantity: i64,
+    /// Price resolved when the line was added to the quote, in the order currency
+    pub unit_price: Decimal,
+    /// Rate of the product's tax class when the line was added
+    pub tax_rate: Decimal,
+    pub discount: Option<Discount>,
+}
+
+impl From<&OrderLineDetails> for PricedLine {
+    fn from(value: &OrderLineDetails) -> Self {
+        PricedLine {
+            quantity: value.quantity,
+            unit_price: value.unit_price,
+            discount: value.discount.clone(),
+            tax_rate: value.tax_rate,
+        }
+    }
+}
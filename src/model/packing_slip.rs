@@ -0,0 +1,26 @@
+use serde::{Deserialize, Serialize};
+
+use super::{Address, CustomerDetails, FulfillmentStatus, FulfillmentType};
+
+/// Sheet packed with a fulfillment listing what should be in it
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PackingSlip {
+    pub fulfillment_id: i64,
+    pub fulfillment_type: FulfillmentType,
+    pub status: FulfillmentStatus,
+    pub order_id: Option<i64>,
+    /// Customer on the fulfillment's order
+    pub customer: Option<CustomerDetails>,
+    pub ship_to: Option<Address>,
+    pub lines: Vec<PackingSlipLine>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PackingSlipLine {
+    pub line_item_id: i64,
+    pub product_id: i64,
+    pub sku: String,
+    pub description: String,
+    pub quantity: i64,
+    pub quantity_fulfilled: i64,
+}
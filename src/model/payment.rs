@@ -0,0 +1,50 @@
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use super::ToRecord;
+
+impl ToRecord for PaymentDetails {}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PaymentDetails {
+    pub order_id: i64,
+    pub method: PaymentMethod,
+    /// Amount in the order currency
+    pub amount: Decimal,
+    /// Card authorization, transfer reference, cheque number and so on
+    pub reference: Option<String>,
+    pub received_at: DateTime<Utc>,
+    /// Payments taken while the order was still a quote
+    pub is_deposit: bool,
+}
+
+impl From<PaymentMethod> for String {
+    fn from(value: PaymentMethod) -> Self {
+        format!("{:?}", value)
+    }
+}
+
+impl std::str::FromStr for PaymentMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Cash" => Ok(Self::Cash),
+            "Card" => Ok(Self::Card),
+            "BankTransfer" => Ok(Self::BankTransfer),
+            "Cheque" => Ok(Self::Cheque),
+            "Other" => Ok(Self::Other),
+            s => Err(format!("unknown payment method {}", s)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum PaymentMethod {
+    Cash,
+    Card,
+    BankTransfer,
+    Cheque,
+    Other,
+}
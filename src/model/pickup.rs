@@ -0,0 +1,26 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::ToRecord;
+
+impl ToRecord for PickupSlotDetails {}
+
+/// Window customers can collect pick-ups from a location in
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PickupSlotDetails {
+    pub location_id: i64,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    /// Most pick-ups that can be booked into the slot
+    pub capacity: i64,
+    /// Pick-ups booked into the slot
+    pub fulfillment_ids: Vec<i64>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct NewPickupSlot {
+    pub location_id: i64,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    pub capacity: i64,
+}
@@ -0,0 +1,36 @@
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use super::ToRecord;
+
+impl ToRecord for PriceListDetails {}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PriceListDetails {
+    pub name: String,
+    /// Currency of every price on the list
+    #[serde(default = "super::base_currency")]
+    pub currency: String,
+}
+
+/// A resolved price and the currency it's in
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Price {
+    pub amount: Decimal,
+    pub currency: String,
+}
+
+impl ToRecord for PriceListEntryDetails {}
+
+/// Per product price override on a price list.
+///
+/// Both ends of the effective range are inclusive, `None` leaves that end open.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PriceListEntryDetails {
+    pub price_list_id: i64,
+    pub product_id: i64,
+    pub price: Decimal,
+    pub effective_from: Option<NaiveDate>,
+    pub effective_to: Option<NaiveDate>,
+}
@@ -1,11 +1,266 @@
+use std::{collections::BTreeMap, str::FromStr};
+
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 
-use super::ToRecord;
+use super::{AttributeValue, Record, ToRecord};
+
+/// Base unit for products that don't name one
+pub const DEFAULT_UNIT: &str = "each";
 
 impl ToRecord for ProductDetails {}
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct ProductDetails {
     pub sku: String,
     pub description: String,
+    /// Price used when no price list entry applies
+    #[serde(default)]
+    pub base_price: Decimal,
+    #[serde(default)]
+    pub tax_class_id: Option<i64>,
+    /// Shipping weight of one unit, needed to load it onto a delivery run
+    #[serde(default)]
+    pub weight_grams: Option<i64>,
+    /// Codes printed on the product, each one unique across products
+    #[serde(default)]
+    pub barcodes: Vec<Barcode>,
+    /// Dimensions the product's variants differ by, e.g. `size` and `colour`
+    #[serde(default)]
+    pub options: Vec<String>,
+    /// Product this is a variant of
+    #[serde(default)]
+    pub parent_id: Option<i64>,
+    /// Variant's value for each of its parent's options
+    #[serde(default)]
+    pub option_values: BTreeMap<String, String>,
+    #[serde(default)]
+    pub product_type: ProductType,
+    /// What one kit is made of, kits only
+    #[serde(default)]
+    pub components: Vec<KitComponent>,
+    /// Unit stock is counted in, every other quantity is converted to it
+    #[serde(default = "default_unit")]
+    pub base_unit: String,
+    /// Other units it's bought or sold in, e.g. a case of 12
+    #[serde(default)]
+    pub units: Vec<UnitOfMeasure>,
+    #[serde(default)]
+    pub category_id: Option<i64>,
+    #[serde(default)]
+    pub attributes: BTreeMap<String, AttributeValue>,
+}
+
+fn default_unit() -> String {
+    DEFAULT_UNIT.to_string()
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct UnitOfMeasure {
+    pub name: String,
+    /// Base units in one of these
+    pub factor: i64,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct KitComponent {
+    pub product_id: i64,
+    /// Units of the component in one kit
+    pub quantity: i64,
+}
+
+/// Product in the catalog listing with its variants grouped under it
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CatalogEntry {
+    pub product: Record<ProductDetails>,
+    pub variants: Vec<Record<ProductDetails>>,
+}
+
+/// Product matching a search, best matches first
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ProductSearchHit {
+    pub product: Record<ProductDetails>,
+    /// BM25 score, lower is a better match
+    pub rank: f64,
+    /// Best matching fragment with the matched terms wrapped in `<b>` tags
+    pub snippet: String,
+}
+
+/// Turns what was typed into an FTS5 query where every word has to match the start of a
+/// term. Quoting each word keeps FTS5 syntax in the input from being interpreted.
+pub fn product_search_query(typed: &str) -> Option<String> {
+    let words: Vec<String> = typed
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| format!("\"{}\"*", w))
+        .collect();
+    match words.is_empty() {
+        true => None,
+        false => Some(words.join(" ")),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Barcode {
+    pub symbology: BarcodeSymbology,
+    pub code: String,
+}
+
+impl Barcode {
+    /// Key barcodes are looked up and kept unique by. GTINs are zero padded to 14 digits
+    /// so a UPC-A matches the same code read as an EAN-13.
+    pub fn lookup_code(&self) -> String {
+        match self.symbology {
+            BarcodeSymbology::Ean13 | BarcodeSymbology::UpcA => format!("{:0>14}", self.code),
+            BarcodeSymbology::Code128 => self.code.clone(),
+        }
+    }
+
+    /// Keys a scanned code could have been stored under
+    pub fn lookup_codes(scanned: &str) -> Vec<String> {
+        let mut codes = vec![scanned.to_string()];
+        if matches!(scanned.len(), 12 | 13) && scanned.bytes().all(|b| b.is_ascii_digit()) {
+            codes.push(format!("{:0>14}", scanned));
+        }
+        codes
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let digits = match self.symbology {
+            BarcodeSymbology::Ean13 => 13,
+            BarcodeSymbology::UpcA => 12,
+            BarcodeSymbology::Code128 => {
+                return match self.code.len() {
+                    1..=48 if self.code.bytes().all(|b| (b' '..=b'~').contains(&b)) => Ok(()),
+                    _ => Err(format!("bad Code128 barcode {:?}", self.code)),
+                };
+            }
+        };
+
+        if self.code.len() != digits || !self.code.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(format!(
+                "{:?} barcodes are {} digits, got {:?}",
+                self.symbology, digits, self.code
+            ));
+        }
+        if gtin_check_digit(&self.code[..digits - 1]) != self.code.as_bytes()[digits - 1] - b'0' {
+            return Err(format!("bad check digit on barcode {}", self.code));
+        }
+        Ok(())
+    }
+}
+
+/// GS1 check digit, weights alternate 3 and 1 starting from the rightmost digit
+fn gtin_check_digit(digits: &str) -> u8 {
+    let sum: u32 = digits
+        .bytes()
+        .rev()
+        .enumerate()
+        .map(|(i, b)| u32::from(b - b'0') * if i % 2 == 0 { 3 } else { 1 })
+        .sum();
+    ((10 - sum % 10) % 10) as u8
+}
+
+impl From<ProductType> for String {
+    fn from(value: ProductType) -> Self {
+        format!("{:?}", value)
+    }
+}
+
+impl FromStr for ProductType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Standard" => Ok(Self::Standard),
+            "Kit" => Ok(Self::Kit),
+            s => Err(format!("unknown product type {}", s)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub enum ProductType {
+    #[default]
+    Standard,
+    /// Bundle of components, line items for it are exploded into its components and its
+    /// stock is whatever can be made up from theirs
+    Kit,
+}
+
+impl From<BarcodeSymbology> for String {
+    fn from(value: BarcodeSymbology) -> Self {
+        format!("{:?}", value)
+    }
+}
+
+impl FromStr for BarcodeSymbology {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Ean13" => Ok(Self::Ean13),
+            "UpcA" => Ok(Self::UpcA),
+            "Code128" => Ok(Self::Code128),
+            s => Err(format!("unknown barcode symbology {}", s)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum BarcodeSymbology {
+    Ean13,
+    UpcA,
+    /// Internal codes, any printable ASCII
+    Code128,
+}
+
+#[cfg(test)]
+mod test {
+    use super::{product_search_query, Barcode, BarcodeSymbology};
+
+    fn barcode(symbology: BarcodeSymbology, code: &str) -> Barcode {
+        Barcode {
+            symbology,
+            code: code.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_check_digits() {
+        assert!(barcode(BarcodeSymbology::Ean13, "4006381333931")
+            .validate()
+            .is_ok());
+        assert!(barcode(BarcodeSymbology::Ean13, "4006381333932")
+            .validate()
+            .is_err());
+        assert!(barcode(BarcodeSymbology::UpcA, "036000291452")
+            .validate()
+            .is_ok());
+        assert!(barcode(BarcodeSymbology::UpcA, "03600029145")
+            .validate()
+            .is_err());
+        assert!(barcode(BarcodeSymbology::Code128, "BIN-A1/07")
+            .validate()
+            .is_ok());
+        assert!(barcode(BarcodeSymbology::Code128, "").validate().is_err());
+
+        // a UPC-A read as an EAN-13 is the same product
+        let upc = barcode(BarcodeSymbology::UpcA, "036000291452");
+        assert!(Barcode::lookup_codes("0036000291452").contains(&upc.lookup_code()));
+    }
+
+    #[test]
+    fn test_search_query() {
+        assert_eq!(
+            product_search_query("blue  SKU-1").as_deref(),
+            Some(r#""blue"* "SKU"* "1"*"#)
+        );
+        // FTS5 operators are just punctuation to split on
+        assert_eq!(
+            product_search_query(r#"mug" OR *"#).as_deref(),
+            Some(r#""mug"* "OR"*"#)
+        );
+        assert_eq!(product_search_query(" -* "), None);
+    }
 }
@@ -0,0 +1,73 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use super::ToRecord;
+
+impl ToRecord for ReturnDetails {}
+
+/// Return authorization for goods sent out on a fulfilled fulfillment
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ReturnDetails {
+    pub fulfillment_id: i64,
+    /// Order the goods were sold on, refunds are tracked against it
+    pub order_id: Option<i64>,
+    pub status: ReturnStatus,
+    pub reason: Option<String>,
+    pub requested_at: DateTime<Utc>,
+    /// Amount refunded in the order currency, set once the return is refunded
+    pub refund_amount: Option<Decimal>,
+    pub lines: Vec<ReturnLine>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ReturnLine {
+    pub line_item_id: i64,
+    pub product_id: i64,
+    pub quantity: i64,
+}
+
+impl From<ReturnStatus> for String {
+    fn from(value: ReturnStatus) -> Self {
+        format!("{:?}", value)
+    }
+}
+
+impl FromStr for ReturnStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Requested" => Ok(Self::Requested),
+            "Approved" => Ok(Self::Approved),
+            "Received" => Ok(Self::Received),
+            "Refunded" => Ok(Self::Refunded),
+            "Rejected" => Ok(Self::Rejected),
+            s => Err(format!("unknown return status {}", s)),
+        }
+    }
+}
+
+impl ReturnStatus {
+    pub fn allowed_priors(&self) -> Vec<Self> {
+        match self {
+            Self::Approved => vec![Self::Requested],
+            Self::Received => vec![Self::Approved],
+            Self::Refunded => vec![Self::Received],
+            Self::Rejected => vec![Self::Requested, Self::Approved],
+            _ => vec![],
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum ReturnStatus {
+    Requested,
+    Approved,
+    /// Goods are back and restocked
+    Received,
+    Refunded,
+    Rejected,
+}
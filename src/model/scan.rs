@@ -0,0 +1,36 @@
+use serde::{Deserialize, Serialize};
+
+/// Item scanned while picking a fulfillment
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Scan {
+    /// SKU or barcode read by the scanner
+    pub code: String,
+    #[serde(default = "Scan::default_quantity")]
+    pub quantity: i64,
+}
+
+impl Scan {
+    fn default_quantity() -> i64 {
+        1
+    }
+}
+
+/// What a scan picked and what's still left to pick on the fulfillment
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ScanResult {
+    pub fulfillment_id: i64,
+    pub line_item_id: i64,
+    pub product_id: i64,
+    /// Picked on the line so far, including this scan
+    pub quantity_fulfilled: i64,
+    pub remaining: Vec<RemainingPick>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RemainingPick {
+    pub line_item_id: i64,
+    pub product_id: i64,
+    pub sku: String,
+    pub description: String,
+    pub quantity: i64,
+}
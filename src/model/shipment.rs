@@ -0,0 +1,102 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use super::ToRecord;
+
+impl ToRecord for ShipmentDetails {}
+
+/// Package handed to a carrier for a delivery or drop-ship fulfillment
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ShipmentDetails {
+    pub fulfillment_id: i64,
+    /// Carrier name, shipments for a carrier with an integration can buy labels
+    pub carrier: String,
+    /// Carrier's service, e.g. `Express` or `Economy`
+    pub service_level: Option<String>,
+    pub tracking_number: Option<String>,
+    /// What the label bought through the carrier integration cost
+    pub label_cost: Option<Decimal>,
+    pub label_currency: Option<String>,
+    pub weight_grams: Option<i64>,
+    pub length_mm: Option<i64>,
+    pub width_mm: Option<i64>,
+    pub height_mm: Option<i64>,
+    pub status: ShipmentStatus,
+    pub shipped_at: Option<DateTime<Utc>>,
+    pub delivered_at: Option<DateTime<Utc>>,
+    pub lines: Vec<ShipmentLine>,
+    pub events: Vec<ShipmentEvent>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ShipmentLine {
+    pub line_item_id: i64,
+    pub product_id: i64,
+    pub quantity: i64,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ShipmentEvent {
+    pub status: ShipmentStatus,
+    pub occurred_at: DateTime<Utc>,
+}
+
+/// Everything needed to create a shipment
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct NewShipment {
+    pub fulfillment_id: i64,
+    pub carrier: String,
+    pub service_level: Option<String>,
+    pub tracking_number: Option<String>,
+    pub weight_grams: Option<i64>,
+    pub length_mm: Option<i64>,
+    pub width_mm: Option<i64>,
+    pub height_mm: Option<i64>,
+    pub lines: Vec<ShipmentItem>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ShipmentItem {
+    pub line_item_id: i64,
+    pub quantity: i64,
+}
+
+impl From<ShipmentStatus> for String {
+    fn from(value: ShipmentStatus) -> Self {
+        format!("{:?}", value)
+    }
+}
+
+impl FromStr for ShipmentStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Pending" => Ok(Self::Pending),
+            "Shipped" => Ok(Self::Shipped),
+            "Delivered" => Ok(Self::Delivered),
+            s => Err(format!("unknown shipment status {}", s)),
+        }
+    }
+}
+
+impl ShipmentStatus {
+    pub fn allowed_priors(&self) -> Vec<Self> {
+        match self {
+            Self::Shipped => vec![Self::Pending],
+            Self::Delivered => vec![Self::Shipped],
+            _ => vec![],
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum ShipmentStatus {
+    /// Packed, waiting on the carrier
+    Pending,
+    Shipped,
+    Delivered,
+}
@@ -0,0 +1,13 @@
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use super::ToRecord;
+
+impl ToRecord for TaxClassDetails {}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TaxClassDetails {
+    pub name: String,
+    /// Fraction of the taxable amount, `0.2` is twenty percent
+    pub rate: Decimal,
+}
@@ -0,0 +1,237 @@
+use rust_decimal::{prelude::ToPrimitive, Decimal, RoundingStrategy};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum Discount {
+    /// Percentage off, `10` is ten percent
+    Percent(Decimal),
+    /// Fixed amount off the whole line or order
+    Fixed(Decimal),
+}
+
+impl Discount {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Percent(_) => "Percent",
+            Self::Fixed(_) => "Fixed",
+        }
+    }
+
+    pub fn value(&self) -> Decimal {
+        match self {
+            Self::Percent(v) | Self::Fixed(v) => *v,
+        }
+    }
+
+    pub fn from_parts(kind: &str, value: Decimal) -> Result<Self, String> {
+        match kind {
+            "Percent" => Ok(Self::Percent(value)),
+            "Fixed" => Ok(Self::Fixed(value)),
+            k => Err(format!("unknown discount kind {}", k)),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            Self::Percent(p) if *p < Decimal::ZERO || *p > Decimal::ONE_HUNDRED => {
+                Err("percent discount must be between 0 and 100".to_string())
+            }
+            Self::Fixed(v) if *v < Decimal::ZERO => {
+                Err("fixed discount can't be negative".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Discount in minor units against `amount`, never more than `amount`
+    fn apply(&self, amount: i64, minor_units: u32) -> i64 {
+        let discount = match self {
+            Self::Percent(p) => round_minor(Decimal::from(amount) * p / Decimal::ONE_HUNDRED, 0),
+            Self::Fixed(v) => round_minor(*v, minor_units),
+        };
+        discount.clamp(0, amount.max(0))
+    }
+}
+
+/// Inputs the totals engine needs from an order line
+#[derive(Clone, Debug)]
+pub struct PricedLine {
+    pub quantity: i64,
+    pub unit_price: Decimal,
+    pub discount: Option<Discount>,
+    pub tax_rate: Decimal,
+}
+
+/// Totals in the currency's minor units (e.g. cents)
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct OrderTotals {
+    pub subtotal: i64,
+    pub discount: i64,
+    pub tax: i64,
+    pub total: i64,
+}
+
+impl OrderTotals {
+    fn add(&mut self, other: &OrderTotals) {
+        self.subtotal += other.subtotal;
+        self.discount += other.discount;
+        self.tax += other.tax;
+        self.total += other.total;
+    }
+}
+
+/// Rounds `amount` half away from zero into minor units
+pub fn round_minor(amount: Decimal, minor_units: u32) -> i64 {
+    let scaled = amount * Decimal::from(10_i64.pow(minor_units));
+    scaled
+        .round_dp_with_strategy(0, RoundingStrategy::MidpointAwayFromZero)
+        .to_i64()
+        .unwrap_or(i64::MAX)
+}
+
+/// Computes per line and order totals.
+///
+/// Each line is rounded to minor units before anything is summed. The order discount
+/// is spread over lines in proportion to their discounted subtotal, leftover minor
+/// units going to the earliest lines, so tax is always charged on what the customer
+/// pays for that line. The same inputs always produce the same totals.
+pub fn compute_totals(
+    lines: &[PricedLine],
+    order_discount: Option<&Discount>,
+    minor_units: u32,
+) -> (Vec<OrderTotals>, OrderTotals) {
+    let mut line_totals: Vec<OrderTotals> = lines
+        .iter()
+        .map(|line| {
+            let subtotal = round_minor(Decimal::from(line.quantity) * line.unit_price, minor_units);
+            let discount = line
+                .discount
+                .as_ref()
+                .map_or(0, |d| d.apply(subtotal, minor_units));
+            OrderTotals {
+                subtotal,
+                discount,
+                tax: 0,
+                total: 0,
+            }
+        })
+        .collect();
+
+    let nets: Vec<i64> = line_totals
+        .iter()
+        .map(|t| t.subtotal - t.discount)
+        .collect();
+    let net_sum: i64 = nets.iter().sum();
+
+    if let Some(discount) = order_discount {
+        let amount = discount.apply(net_sum, minor_units);
+        if net_sum > 0 && amount > 0 {
+            let mut allocated = 0;
+            for (totals, net) in line_totals.iter_mut().zip(&nets) {
+                let share = (i128::from(amount) * i128::from(*net) / i128::from(net_sum)) as i64;
+                totals.discount += share;
+                allocated += share;
+            }
+            for (totals, net) in line_totals.iter_mut().zip(&nets) {
+                if allocated == amount {
+                    break;
+                }
+                if *net > 0 && totals.discount < totals.subtotal {
+                    totals.discount += 1;
+                    allocated += 1;
+                }
+            }
+        }
+    }
+
+    let mut order_totals = OrderTotals::default();
+    for (totals, line) in line_totals.iter_mut().zip(lines) {
+        let taxable = totals.subtotal - totals.discount;
+        totals.tax = round_minor(Decimal::from(taxable) * line.tax_rate, 0);
+        totals.total = taxable + totals.tax;
+        order_totals.add(totals);
+    }
+
+    (line_totals, order_totals)
+}
+
+#[cfg(test)]
+mod test {
+    use rust_decimal::Decimal;
+
+    use super::{compute_totals, Discount, OrderTotals, PricedLine};
+
+    fn line(quantity: i64, unit_price: &str, tax_rate: &str) -> PricedLine {
+        PricedLine {
+            quantity,
+            unit_price: unit_price.parse().unwrap(),
+            discount: None,
+            tax_rate: tax_rate.parse().unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_no_discounts() {
+        let lines = [line(3, "1.005", "0.2"), line(1, "10", "0")];
+        let (per_line, totals) = compute_totals(&lines, None, 2);
+
+        // 3 * 1.005 = 3.015 rounds half away from zero to 3.02
+        assert_eq!(per_line[0].subtotal, 302);
+        assert_eq!(per_line[0].tax, 60);
+        assert_eq!(
+            totals,
+            OrderTotals {
+                subtotal: 1302,
+                discount: 0,
+                tax: 60,
+                total: 1362,
+            }
+        );
+    }
+
+    #[test]
+    fn test_line_discounts() {
+        let mut percent = line(2, "50", "0.1");
+        percent.discount = Some(Discount::Percent(Decimal::from(10)));
+        let mut fixed = line(1, "5", "0");
+        fixed.discount = Some(Discount::Fixed(Decimal::from(8)));
+
+        let (per_line, totals) = compute_totals(&[percent, fixed], None, 2);
+
+        assert_eq!(per_line[0].discount, 1000);
+        assert_eq!(per_line[0].tax, 900);
+        // fixed discounts can't take a line below zero
+        assert_eq!(per_line[1].discount, 500);
+        assert_eq!(per_line[1].total, 0);
+        assert_eq!(totals.total, 9900);
+    }
+
+    #[test]
+    fn test_order_discount_allocation() {
+        let lines = [line(1, "1", "0.2"), line(1, "1", "0"), line(1, "1", "0")];
+        let discount = Discount::Fixed(Decimal::from(1));
+        let (per_line, totals) = compute_totals(&lines, Some(&discount), 2);
+
+        let discounts: Vec<i64> = per_line.iter().map(|t| t.discount).collect();
+        assert_eq!(discounts, vec![34, 33, 33]);
+        assert_eq!(per_line[0].tax, 13);
+        assert_eq!(totals.discount, 100);
+        assert_eq!(totals.total, 213);
+
+        // recomputing gives the same answer
+        assert_eq!(compute_totals(&lines, Some(&discount), 2).1, totals);
+    }
+
+    #[test]
+    fn test_order_discount_bounds() {
+        let lines = [line(1, "10", "0")];
+        let (_, totals) = compute_totals(&lines, Some(&Discount::Fixed(Decimal::from(25))), 2);
+        assert_eq!(totals.total, 0);
+
+        let (_, totals) = compute_totals(&[], Some(&Discount::Percent(Decimal::from(50))), 2);
+        assert_eq!(totals, OrderTotals::default());
+
+        assert!(Discount::Percent(Decimal::from(101)).validate().is_err());
+        assert!(Discount::Fixed(Decimal::from(-1)).validate().is_err());
+    }
+}
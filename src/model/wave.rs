@@ -0,0 +1,68 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::ToRecord;
+
+impl ToRecord for WaveDetails {}
+
+/// Batch of fulfillments picked together
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct WaveDetails {
+    pub status: WaveStatus,
+    pub fulfillment_ids: Vec<i64>,
+    pub created_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+}
+
+/// Everything left to pick for a wave, one line per product at each location
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PickList {
+    pub wave_id: i64,
+    pub lines: Vec<PickListLine>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PickListLine {
+    pub location_id: i64,
+    pub product_id: i64,
+    pub sku: String,
+    pub description: String,
+    pub quantity: i64,
+    /// Where the picked quantity goes
+    pub fulfillments: Vec<PickAllocation>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PickAllocation {
+    pub fulfillment_id: i64,
+    pub line_item_id: i64,
+    pub quantity: i64,
+}
+
+impl From<WaveStatus> for String {
+    fn from(value: WaveStatus) -> Self {
+        format!("{:?}", value)
+    }
+}
+
+impl FromStr for WaveStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Open" => Ok(Self::Open),
+            "Started" => Ok(Self::Started),
+            s => Err(format!("unknown wave status {}", s)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum WaveStatus {
+    /// Planned, fulfillments can still be worked on one at a time
+    Open,
+    /// Picking has started, every fulfillment in the wave is in progress
+    Started,
+}
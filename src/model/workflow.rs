@@ -0,0 +1,373 @@
+use std::collections::{BTreeSet, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+use super::{FulfillmentStatus, FulfillmentType, LineItemDetails};
+
+/// Status flow for one fulfillment type.
+///
+/// Fulfillments start out `New` and finish `Fulfilled`. Stock is reserved on entering
+/// `Initialized` and issued on entering `Fulfilled`, any other state is free to add.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Workflow {
+    pub fulfillment_type: FulfillmentType,
+    pub states: Vec<FulfillmentStatus>,
+    pub transitions: Vec<Transition>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Transition {
+    pub from: FulfillmentStatus,
+    pub to: FulfillmentStatus,
+    #[serde(default)]
+    pub guards: Vec<Guard>,
+}
+
+/// Condition on a fulfillment's lines that must hold before a transition is taken
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum Guard {
+    /// At least one line on the fulfillment
+    HasLines,
+    /// Every line fully reserved
+    AllLinesReserved,
+    /// Every line picked in full
+    AllLinesPicked,
+}
+
+impl Guard {
+    pub fn check(&self, lines: &[LineItemDetails]) -> Result<(), String> {
+        let holds = match self {
+            Self::HasLines => !lines.is_empty(),
+            Self::AllLinesReserved => lines.iter().all(|l| l.quantity_reserved >= l.quantity),
+            Self::AllLinesPicked => lines.iter().all(|l| l.quantity_fulfilled >= l.quantity),
+        };
+
+        match holds {
+            true => Ok(()),
+            false => Err(format!("guard {:?} not met", self)),
+        }
+    }
+}
+
+impl Workflow {
+    /// Built in flow for a type, used when the config doesn't define one
+    pub fn builtin(fulfillment_type: FulfillmentType) -> Self {
+        let all = [
+            FulfillmentStatus::New,
+            FulfillmentStatus::Initialized,
+            FulfillmentStatus::InProgress,
+            FulfillmentStatus::Fulfilled,
+        ];
+
+        let mut transitions = Vec::new();
+        for to in &all {
+            for from in fulfillment_type.allowed_priors(to) {
+                transitions.push(Transition {
+                    from,
+                    to: to.clone(),
+                    guards: Vec::new(),
+                });
+            }
+        }
+
+        let states = all
+            .into_iter()
+            .filter(|s| *s == FulfillmentStatus::New || transitions.iter().any(|t| t.to == *s))
+            .collect();
+
+        Self {
+            fulfillment_type,
+            states,
+            transitions,
+        }
+    }
+
+    pub fn transition(
+        &self,
+        from: &FulfillmentStatus,
+        to: &FulfillmentStatus,
+    ) -> Option<&Transition> {
+        self.transitions
+            .iter()
+            .find(|t| t.from == *from && t.to == *to)
+    }
+
+    /// Checks the flow is well formed.
+    ///
+    /// Every state must be reachable from `New`, `Fulfilled` must be reachable from
+    /// every state and nothing may leave `Fulfilled`. Types that pick stock can only get
+    /// to `Fulfilled` through `Initialized`, so what's issued was reserved first.
+    pub fn validate(&self) -> Result<(), String> {
+        let name = format!("{:?} workflow", self.fulfillment_type);
+
+        let states: BTreeSet<String> = self.states.iter().cloned().map(String::from).collect();
+        if states.len() != self.states.len() {
+            return Err(format!("{} lists a state twice", name));
+        }
+        for required in [FulfillmentStatus::New, FulfillmentStatus::Fulfilled] {
+            if !self.states.contains(&required) {
+                return Err(format!("{} has no {:?} state", name, required));
+            }
+        }
+
+        for t in &self.transitions {
+            for end in [&t.from, &t.to] {
+                if !self.states.contains(end) {
+                    return Err(format!(
+                        "{} has a transition with unknown state {}",
+                        name,
+                        String::from(end.clone())
+                    ));
+                }
+            }
+            if t.from == FulfillmentStatus::Fulfilled {
+                return Err(format!("{} has a transition out of Fulfilled", name));
+            }
+            if self
+                .transitions
+                .iter()
+                .filter(|o| o.from == t.from && o.to == t.to)
+                .count()
+                > 1
+            {
+                return Err(format!("{} repeats a transition", name));
+            }
+        }
+
+        let forward = self.reachable(&FulfillmentStatus::New, |t| Some((&t.from, &t.to)));
+        if let Some(state) = self.states.iter().find(|s| !forward.contains(s)) {
+            return Err(format!(
+                "{} can't reach {} from New",
+                name,
+                String::from(state.clone())
+            ));
+        }
+
+        let backward = self.reachable(&FulfillmentStatus::Fulfilled, |t| Some((&t.to, &t.from)));
+        if let Some(state) = self.states.iter().find(|s| !backward.contains(s)) {
+            return Err(format!(
+                "{} can't reach Fulfilled from {}",
+                name,
+                String::from(state.clone())
+            ));
+        }
+
+        if self.fulfillment_type.is_picked() {
+            let bypass = self.reachable(&FulfillmentStatus::New, |t| {
+                (t.to != FulfillmentStatus::Initialized).then_some((&t.from, &t.to))
+            });
+            if bypass.contains(&&FulfillmentStatus::Fulfilled) {
+                return Err(format!(
+                    "{} can reach Fulfilled without going through Initialized",
+                    name
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn reachable<'a>(
+        &'a self,
+        start: &'a FulfillmentStatus,
+        edge: impl Fn(&'a Transition) -> Option<(&'a FulfillmentStatus, &'a FulfillmentStatus)>,
+    ) -> Vec<&'a FulfillmentStatus> {
+        let mut seen = vec![start];
+        let mut queue = VecDeque::from([start]);
+        while let Some(state) = queue.pop_front() {
+            for (from, to) in self.transitions.iter().filter_map(&edge) {
+                if from == state && !seen.contains(&to) {
+                    seen.push(to);
+                    queue.push_back(to);
+                }
+            }
+        }
+        seen
+    }
+}
+
+/// Workflows for every fulfillment type, as loaded at startup
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Workflows {
+    pub workflows: Vec<Workflow>,
+}
+
+impl Default for Workflows {
+    fn default() -> Self {
+        Self {
+            workflows: FulfillmentType::all()
+                .into_iter()
+                .map(Workflow::builtin)
+                .collect(),
+        }
+    }
+}
+
+impl Workflows {
+    /// Parses and validates a config, types it leaves out keep their built in flow
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let configured: Workflows =
+            serde_json::from_str(json).map_err(|e| format!("bad workflow config {}", e))?;
+
+        let mut workflows = Self::default();
+        let mut seen = Vec::new();
+        for workflow in configured.workflows {
+            workflow.validate()?;
+            if seen.contains(&workflow.fulfillment_type) {
+                return Err(format!(
+                    "{:?} workflow is defined twice",
+                    workflow.fulfillment_type
+                ));
+            }
+            seen.push(workflow.fulfillment_type.clone());
+            workflows
+                .workflows
+                .retain(|w| w.fulfillment_type != workflow.fulfillment_type);
+            workflows.workflows.push(workflow);
+        }
+
+        Ok(workflows)
+    }
+
+    pub fn get(&self, fulfillment_type: &FulfillmentType) -> Option<&Workflow> {
+        self.workflows
+            .iter()
+            .find(|w| w.fulfillment_type == *fulfillment_type)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Guard, Workflows};
+    use crate::model::{FulfillmentStatus, FulfillmentType, LineItemDetails, DEFAULT_UNIT};
+
+    #[test]
+    fn test_builtin_workflows_are_valid() {
+        for workflow in Workflows::default().workflows {
+            workflow.validate().unwrap();
+        }
+
+        let workflows = Workflows::default();
+        let service = workflows.get(&FulfillmentType::Service).unwrap();
+        assert!(!service.states.contains(&FulfillmentStatus::Initialized));
+    }
+
+    #[test]
+    fn test_ready_for_pickup() {
+        let workflows = Workflows::from_json(
+            r#"{"workflows": [{
+                "fulfillment_type": "StockPickUp",
+                "states": ["New", "Initialized", "InProgress", "ReadyForPickup", "Fulfilled"],
+                "transitions": [
+                    {"from": "New", "to": "Initialized"},
+                    {"from": "Initialized", "to": "InProgress"},
+                    {"from": "InProgress", "to": "ReadyForPickup", "guards": ["AllLinesPicked"]},
+                    {"from": "ReadyForPickup", "to": "Fulfilled"}
+                ]
+            }]}"#,
+        )
+        .unwrap();
+
+        let pickup = workflows.get(&FulfillmentType::StockPickUp).unwrap();
+        let ready = FulfillmentStatus::Custom("ReadyForPickup".to_string());
+        assert!(pickup
+            .transition(
+                &FulfillmentStatus::InProgress,
+                &FulfillmentStatus::Fulfilled
+            )
+            .is_none());
+        let transition = pickup
+            .transition(&FulfillmentStatus::InProgress, &ready)
+            .unwrap();
+        assert_eq!(transition.guards, vec![Guard::AllLinesPicked]);
+
+        // types left out keep the built in flow
+        assert!(workflows
+            .get(&FulfillmentType::StockDelivery)
+            .unwrap()
+            .transition(
+                &FulfillmentStatus::InProgress,
+                &FulfillmentStatus::Fulfilled
+            )
+            .is_some());
+    }
+
+    #[test]
+    fn test_unreachable_states() {
+        // Stuck is never entered
+        assert!(Workflows::from_json(
+            r#"{"workflows": [{
+                "fulfillment_type": "Service",
+                "states": ["New", "Stuck", "Fulfilled"],
+                "transitions": [{"from": "New", "to": "Fulfilled"}]
+            }]}"#,
+        )
+        .is_err());
+
+        // Limbo can't get to Fulfilled
+        assert!(Workflows::from_json(
+            r#"{"workflows": [{
+                "fulfillment_type": "Service",
+                "states": ["New", "Limbo", "Fulfilled"],
+                "transitions": [
+                    {"from": "New", "to": "Limbo"},
+                    {"from": "New", "to": "Fulfilled"}
+                ]
+            }]}"#,
+        )
+        .is_err());
+
+        assert!(Workflows::from_json(
+            r#"{"workflows": [{
+                "fulfillment_type": "Service",
+                "states": ["New", "Fulfilled"],
+                "transitions": [{"from": "New", "to": "Elsewhere"}]
+            }]}"#,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_stock_must_be_reserved() {
+        // InProgress can be reached without reserving anything
+        let config = |fulfillment_type: &str| {
+            format!(
+                r#"{{"workflows": [{{
+                    "fulfillment_type": "{}",
+                    "states": ["New", "Initialized", "InProgress", "Fulfilled"],
+                    "transitions": [
+                        {{"from": "New", "to": "Initialized"}},
+                        {{"from": "New", "to": "InProgress"}},
+                        {{"from": "Initialized", "to": "InProgress"}},
+                        {{"from": "InProgress", "to": "Fulfilled"}}
+                    ]
+                }}]}}"#,
+                fulfillment_type
+            )
+        };
+
+        assert!(Workflows::from_json(&config("StockDelivery")).is_err());
+        assert!(Workflows::from_json(&config("Transfer")).is_err());
+        assert!(Workflows::from_json(&config("Service")).is_ok());
+    }
+
+    #[test]
+    fn test_guards() {
+        let line = LineItemDetails {
+            product_id: 1,
+            fulfillment_id: 1,
+            quantity: 2,
+            quantity_fulfilled: 1,
+            quantity_reserved: 2,
+            is_backorder: false,
+            unit: DEFAULT_UNIT.to_string(),
+            unit_factor: 1,
+        };
+
+        assert!(Guard::HasLines.check(&[]).is_err());
+        assert!(Guard::AllLinesReserved
+            .check(std::slice::from_ref(&line))
+            .is_ok());
+        assert!(Guard::AllLinesPicked.check(&[line]).is_err());
+    }
+}
@@ -1,14 +1,29 @@
+use std::sync::Arc;
+
 use sqlx::{Pool, Sqlite, SqlitePool};
 
+use crate::{carrier::Carriers, model::Workflows};
+
 #[derive(Clone, Debug)]
 pub struct SqliteProvider {
     pub connection: Pool<Sqlite>,
+    pub workflows: Arc<Workflows>,
+    pub carriers: Carriers,
 }
 
 impl SqliteProvider {
     pub async fn new_memory() -> Result<Self, sqlx::Error> {
         let conn = SqlitePool::connect("sqlite::memory:").await?;
-        Ok(Self { connection: conn })
+        Ok(Self {
+            connection: conn,
+            workflows: Arc::new(Workflows::default()),
+            carriers: Carriers::default(),
+        })
+    }
+
+    pub fn with_workflows(mut self, workflows: Workflows) -> Self {
+        self.workflows = Arc::new(workflows);
+        self
     }
 
     // pub async fn new(url: &str) -> Result<Self, sqlx::Error> {
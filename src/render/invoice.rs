@@ -0,0 +1,163 @@
+use std::fmt::Write;
+
+use rust_decimal::Decimal;
+
+use super::{address_lines, escape_html, format_money};
+use crate::model;
+
+fn tax_percent(rate: Decimal) -> String {
+    format!("{}%", (rate * Decimal::ONE_HUNDRED).normalize())
+}
+
+pub fn invoice_html(invoice: &model::InvoiceDetails) -> String {
+    let money = |amount: i64| format_money(amount, &invoice.currency);
+    let number = escape_html(&invoice.invoice_number);
+
+    let mut html = String::new();
+    let _ = writeln!(html, "<!DOCTYPE html>");
+    let _ = writeln!(html, "<html>");
+    let _ = writeln!(
+        html,
+        "<head><meta charset=\"utf-8\"><title>Invoice {}</title></head>",
+        number
+    );
+    let _ = writeln!(html, "<body>");
+    let _ = writeln!(html, "<h1>Invoice {}</h1>", number);
+    let _ = writeln!(
+        html,
+        "<p>Issued {} for order {}</p>",
+        invoice.issued_at.format("%Y-%m-%d"),
+        invoice.order_id
+    );
+
+    if invoice.customer.is_some() || invoice.bill_to.is_some() {
+        let _ = writeln!(html, "<address>");
+        if let Some(customer) = &invoice.customer {
+            let _ = writeln!(html, "<strong>{}</strong><br>", escape_html(&customer.name));
+        }
+        if let Some(bill_to) = &invoice.bill_to {
+            for line in address_lines(bill_to) {
+                let _ = writeln!(html, "{}<br>", escape_html(&line));
+            }
+        }
+        let _ = writeln!(html, "</address>");
+    }
+
+    let _ = writeln!(html, "<table>");
+    let _ = writeln!(
+        html,
+        "<tr><th>SKU</th><th>Description</th><th>Qty</th><th>Unit price</th>\
+         <th>Discount</th><th>Tax</th><th>Total</th></tr>"
+    );
+    for line in &invoice.lines {
+        let _ = writeln!(
+            html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{} ({})</td><td>{}</td></tr>",
+            escape_html(&line.sku),
+            escape_html(&line.description),
+            line.quantity,
+            line.unit_price,
+            money(line.discount),
+            money(line.tax),
+            tax_percent(line.tax_rate),
+            money(line.total),
+        );
+    }
+    let _ = writeln!(html, "</table>");
+
+    let totals = &invoice.totals;
+    let _ = writeln!(html, "<table>");
+    for (label, amount) in [
+        ("Subtotal", totals.subtotal),
+        ("Discount", totals.discount),
+        ("Tax", totals.tax),
+        ("Total", totals.total),
+        ("Paid", invoice.amount_paid),
+        ("Balance due", totals.total - invoice.amount_paid),
+    ] {
+        let _ = writeln!(
+            html,
+            "<tr><th>{}</th><td>{} {}</td></tr>",
+            label,
+            money(amount),
+            escape_html(&invoice.currency)
+        );
+    }
+    let _ = writeln!(html, "</table>");
+    let _ = writeln!(html, "</body>");
+    let _ = writeln!(html, "</html>");
+    html
+}
+
+pub fn invoice_text(invoice: &model::InvoiceDetails) -> String {
+    let money = |amount: i64| format_money(amount, &invoice.currency);
+
+    let mut text = String::new();
+    let _ = writeln!(text, "INVOICE {}", invoice.invoice_number);
+    let _ = writeln!(
+        text,
+        "Issued {} for order {}",
+        invoice.issued_at.format("%Y-%m-%d"),
+        invoice.order_id
+    );
+
+    if let Some(customer) = &invoice.customer {
+        let _ = writeln!(text);
+        let _ = writeln!(text, "{}", customer.name);
+    }
+    if let Some(bill_to) = &invoice.bill_to {
+        if invoice.customer.is_none() {
+            let _ = writeln!(text);
+        }
+        for line in address_lines(bill_to) {
+            let _ = writeln!(text, "{}", line);
+        }
+    }
+
+    let _ = writeln!(text);
+    for line in &invoice.lines {
+        let _ = writeln!(
+            text,
+            "{:<16} {:<30} {:>5} x {:>10} {:>12}",
+            line.sku,
+            line.description,
+            line.quantity,
+            line.unit_price,
+            money(line.subtotal)
+        );
+        if line.discount > 0 {
+            let _ = writeln!(
+                text,
+                "{:>78}",
+                format!("discount -{}", money(line.discount))
+            );
+        }
+        if line.tax > 0 {
+            let _ = writeln!(
+                text,
+                "{:>78}",
+                format!("tax {} {}", tax_percent(line.tax_rate), money(line.tax))
+            );
+        }
+    }
+
+    let totals = &invoice.totals;
+    let _ = writeln!(text);
+    for (label, amount) in [
+        ("Subtotal", totals.subtotal),
+        ("Discount", totals.discount),
+        ("Tax", totals.tax),
+        ("Total", totals.total),
+        ("Paid", invoice.amount_paid),
+        ("Balance due", totals.total - invoice.amount_paid),
+    ] {
+        let _ = writeln!(
+            text,
+            "{:>60} {:>13} {}",
+            label,
+            money(amount),
+            invoice.currency
+        );
+    }
+    text
+}
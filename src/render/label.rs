@@ -0,0 +1,118 @@
+use std::fmt::Write;
+
+use super::{address_lines, escape_zpl};
+use crate::model;
+
+/// Lines listed on a label before the rest are summed up, a 4x6 label fits no more
+const MAX_LABEL_LINES: usize = 8;
+
+fn truncate(text: &str, width: usize) -> String {
+    text.chars().take(width).collect()
+}
+
+/// First lines on the label and how many were left off
+fn visible_lines(label: &model::ShippingLabel) -> (Vec<String>, usize) {
+    let lines = label
+        .lines
+        .iter()
+        .take(MAX_LABEL_LINES)
+        .map(|line| {
+            format!(
+                "{} x {} {}",
+                line.quantity,
+                line.sku,
+                truncate(&line.description, 30)
+            )
+        })
+        .collect();
+    (lines, label.lines.len().saturating_sub(MAX_LABEL_LINES))
+}
+
+fn zpl_text(zpl: &mut String, y: i64, size: i64, text: &str) {
+    let _ = writeln!(
+        zpl,
+        "^FO40,{}^A0N,{},{}^FH^FD{}^FS",
+        y,
+        size,
+        size,
+        escape_zpl(text)
+    );
+}
+
+/// 4x6 inch label at 203 dpi for thermal printers
+pub fn label_zpl(label: &model::ShippingLabel) -> String {
+    let mut zpl = String::new();
+    let _ = writeln!(zpl, "^XA");
+    let _ = writeln!(zpl, "^CI28");
+    let _ = writeln!(zpl, "^PW812");
+    let _ = writeln!(zpl, "^LL1218");
+
+    let mut y = 40;
+    if let Some(ship_from) = &label.ship_from {
+        zpl_text(&mut zpl, y, 28, &format!("FROM: {}", ship_from));
+        y += 50;
+    }
+
+    zpl_text(&mut zpl, y, 28, "SHIP TO:");
+    y += 40;
+    for line in address_lines(&label.ship_to) {
+        zpl_text(&mut zpl, y, 45, &line);
+        y += 55;
+    }
+
+    y += 20;
+    let _ = writeln!(zpl, "^FO40,{}^GB732,3,3^FS", y);
+    y += 30;
+    let _ = writeln!(
+        zpl,
+        "^FO40,{}^BY3^BCN,120,Y,N,N^FD{}^FS",
+        y, label.fulfillment_id
+    );
+    y += 180;
+
+    let (lines, hidden) = visible_lines(label);
+    for line in &lines {
+        zpl_text(&mut zpl, y, 28, line);
+        y += 36;
+    }
+    if hidden > 0 {
+        zpl_text(&mut zpl, y, 28, &format!("+ {} more lines", hidden));
+        y += 36;
+    }
+    if let Some(order_id) = label.order_id {
+        zpl_text(&mut zpl, y + 10, 28, &format!("ORDER {}", order_id));
+    }
+
+    let _ = writeln!(zpl, "^XZ");
+    zpl
+}
+
+/// Plain text fallback for printers without ZPL
+pub fn label_text(label: &model::ShippingLabel) -> String {
+    let mut text = String::new();
+    if let Some(ship_from) = &label.ship_from {
+        let _ = writeln!(text, "FROM: {}", ship_from);
+        let _ = writeln!(text);
+    }
+
+    let _ = writeln!(text, "SHIP TO:");
+    for line in address_lines(&label.ship_to) {
+        let _ = writeln!(text, "  {}", line);
+    }
+
+    let _ = writeln!(text);
+    let _ = writeln!(text, "FULFILLMENT {}", label.fulfillment_id);
+    if let Some(order_id) = label.order_id {
+        let _ = writeln!(text, "ORDER {}", order_id);
+    }
+
+    let (lines, hidden) = visible_lines(label);
+    let _ = writeln!(text);
+    for line in lines {
+        let _ = writeln!(text, "{}", line);
+    }
+    if hidden > 0 {
+        let _ = writeln!(text, "+ {} more lines", hidden);
+    }
+    text
+}
@@ -0,0 +1,101 @@
+use std::fmt::Write;
+
+use crate::model;
+
+pub mod invoice;
+pub mod label;
+pub mod packing_slip;
+
+/// Escapes text for use in HTML element content and attribute values
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Escapes text for a ZPL `^FD` field, which must be preceded by `^FH` so the hex
+/// escapes are decoded
+pub fn escape_zpl(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '_' => escaped.push_str("_5F"),
+            '^' => escaped.push_str("_5E"),
+            '~' => escaped.push_str("_7E"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Formats an amount in minor units of `currency`, `1234` in USD is `12.34`
+pub fn format_money(amount: i64, currency: &str) -> String {
+    let minor_units = model::minor_units(currency);
+    if minor_units == 0 {
+        return amount.to_string();
+    }
+
+    let scale = 10_i64.pow(minor_units);
+    let sign = if amount < 0 { "-" } else { "" };
+    let amount = amount.unsigned_abs();
+    format!(
+        "{}{}.{:0width$}",
+        sign,
+        amount / scale as u64,
+        amount % scale as u64,
+        width = minor_units as usize
+    )
+}
+
+/// Address as lines, blank optional parts left out
+pub fn address_lines(address: &model::Address) -> Vec<String> {
+    let mut lines = vec![address.recipient.clone(), address.line1.clone()];
+    if let Some(line2) = &address.line2 {
+        lines.push(line2.clone());
+    }
+
+    let mut locality = address.city.clone();
+    if let Some(region) = &address.region {
+        let _ = write!(locality, ", {}", region);
+    }
+    let _ = write!(locality, " {}", address.postal_code);
+    lines.push(locality);
+    lines.push(address.country.clone());
+    lines
+}
+
+#[cfg(test)]
+mod test {
+    use super::{escape_html, escape_zpl, format_money};
+
+    #[test]
+    fn test_format_money() {
+        assert_eq!(format_money(1234, "USD"), "12.34");
+        assert_eq!(format_money(5, "USD"), "0.05");
+        assert_eq!(format_money(-150, "EUR"), "-1.50");
+        assert_eq!(format_money(1500, "JPY"), "1500");
+    }
+
+    #[test]
+    fn test_escape_html() {
+        assert_eq!(
+            escape_html("<b>\"Tom\" & Jerry's</b>"),
+            "&lt;b&gt;&quot;Tom&quot; &amp; Jerry&#39;s&lt;/b&gt;"
+        );
+    }
+
+    #[test]
+    fn test_escape_zpl() {
+        assert_eq!(escape_zpl("A^B~C_D"), "A_5EB_7EC_5FD");
+        assert_eq!(escape_zpl("Zoë"), "Zoë");
+    }
+}
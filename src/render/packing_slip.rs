@@ -0,0 +1,60 @@
+use std::fmt::Write;
+
+use super::{address_lines, escape_html};
+use crate::model;
+
+pub fn packing_slip_html(slip: &model::PackingSlip) -> String {
+    let mut html = String::new();
+    let _ = writeln!(html, "<!DOCTYPE html>");
+    let _ = writeln!(html, "<html>");
+    let _ = writeln!(
+        html,
+        "<head><meta charset=\"utf-8\"><title>Packing slip {}</title></head>",
+        slip.fulfillment_id
+    );
+    let _ = writeln!(html, "<body>");
+    let _ = writeln!(html, "<h1>Packing slip {}</h1>", slip.fulfillment_id);
+
+    let mut details = format!(
+        "{:?} fulfillment, {}",
+        slip.fulfillment_type,
+        String::from(slip.status.clone())
+    );
+    if let Some(order_id) = slip.order_id {
+        let _ = write!(details, ", order {}", order_id);
+    }
+    let _ = writeln!(html, "<p>{}</p>", escape_html(&details));
+
+    if slip.customer.is_some() || slip.ship_to.is_some() {
+        let _ = writeln!(html, "<address>");
+        if let Some(customer) = &slip.customer {
+            let _ = writeln!(html, "<strong>{}</strong><br>", escape_html(&customer.name));
+        }
+        if let Some(ship_to) = &slip.ship_to {
+            for line in address_lines(ship_to) {
+                let _ = writeln!(html, "{}<br>", escape_html(&line));
+            }
+        }
+        let _ = writeln!(html, "</address>");
+    }
+
+    let _ = writeln!(html, "<table>");
+    let _ = writeln!(
+        html,
+        "<tr><th>SKU</th><th>Description</th><th>Ordered</th><th>Fulfilled</th></tr>"
+    );
+    for line in &slip.lines {
+        let _ = writeln!(
+            html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            escape_html(&line.sku),
+            escape_html(&line.description),
+            line.quantity,
+            line.quantity_fulfilled
+        );
+    }
+    let _ = writeln!(html, "</table>");
+    let _ = writeln!(html, "</body>");
+    let _ = writeln!(html, "</html>");
+    html
+}
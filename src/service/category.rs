@@ -0,0 +1,283 @@
+use std::fmt::Display;
+
+use axum::http::StatusCode;
+use sqlx::{sqlite::SqliteRow, Row, SqliteConnection};
+
+use crate::{
+    model::{self, ToRecord},
+    provider::SqliteProvider,
+};
+
+pub trait CategoryService {
+    type Error: Display + Into<StatusCode>;
+
+    async fn init_provider(&mut self) -> Result<(), Self::Error>;
+
+    /// Adds a category under an existing one, or at the top of the tree. Its name can't
+    /// repeat among its siblings and it can't redefine an attribute from above it.
+    async fn create_category(
+        &mut self,
+        category: &model::CategoryDetails,
+    ) -> Result<i64, Self::Error>;
+
+    async fn get_category(
+        &mut self,
+        category_id: i64,
+    ) -> Result<Option<model::Record<model::CategoryDetails>>, Self::Error>;
+
+    async fn get_categories(
+        &mut self,
+    ) -> Result<Vec<model::Record<model::CategoryDetails>>, Self::Error>;
+}
+
+fn category_from_row(
+    row: &SqliteRow,
+) -> Result<model::Record<model::CategoryDetails>, super::Error> {
+    let attributes: String = row.try_get("attributes")?;
+    Ok(model::CategoryDetails {
+        name: row.try_get("name")?,
+        parent_id: row.try_get("parentId")?,
+        attributes: serde_json::from_str(&attributes)
+            .map_err(|e| super::Error::ProviderFailure(e.to_string()))?,
+    }
+    .to_record(row.try_get("id")?))
+}
+
+/// Attributes defined on a category and every category above it, `None` if there's no such
+/// category
+pub async fn category_attributes(
+    conn: &mut SqliteConnection,
+    category_id: i64,
+) -> Result<Option<Vec<model::AttributeDefinition>>, super::Error> {
+    let rows = sqlx::query(sql_stmt::SELECT_ANCESTRY)
+        .bind(category_id)
+        .fetch_all(&mut *conn)
+        .await?;
+    if rows.is_empty() {
+        return Ok(None);
+    }
+
+    let mut attributes = Vec::new();
+    for row in rows {
+        attributes.extend(category_from_row(&row)?.data.attributes);
+    }
+    Ok(Some(attributes))
+}
+
+impl CategoryService for SqliteProvider {
+    type Error = super::Error;
+
+    async fn init_provider(&mut self) -> Result<(), Self::Error> {
+        let mut conn = self.connection.acquire().await?;
+        sqlx::query(sql_stmt::CREATE_TABLE)
+            .execute(&mut *conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn create_category(
+        &mut self,
+        category: &model::CategoryDetails,
+    ) -> Result<i64, Self::Error> {
+        if category.name.trim().is_empty() {
+            return Err(super::Error::BadInput("categories need a name".to_string()));
+        }
+        for attribute in &category.attributes {
+            if attribute.name.is_empty() {
+                return Err(super::Error::BadInput("attributes need a name".to_string()));
+            }
+            match attribute.kind {
+                model::AttributeKind::Enum if attribute.values.is_empty() => {
+                    return Err(super::Error::BadInput(format!(
+                        "enum attribute {} needs values",
+                        attribute.name
+                    )));
+                }
+                model::AttributeKind::Enum => {}
+                _ if !attribute.values.is_empty() => {
+                    return Err(super::Error::BadInput(format!(
+                        "only enum attributes have values, {} is a {:?}",
+                        attribute.name, attribute.kind
+                    )));
+                }
+                _ => {}
+            }
+        }
+        let attributes = serde_json::to_string(&category.attributes)
+            .map_err(|e| super::Error::ProviderFailure(e.to_string()))?;
+
+        let mut tx = self.connection.begin().await?;
+
+        let mut defined = match category.parent_id {
+            Some(parent_id) => category_attributes(&mut tx, parent_id)
+                .await?
+                .ok_or_else(|| {
+                    super::Error::BadInput(format!("no parent category {}", parent_id))
+                })?,
+            None => Vec::new(),
+        };
+        defined.extend(category.attributes.iter().cloned());
+        let mut names: Vec<&String> = defined.iter().map(|a| &a.name).collect();
+        names.sort();
+        names.dedup();
+        if names.len() != defined.len() {
+            return Err(super::Error::BadInput(
+                "attributes can't be defined twice along a category's path".to_string(),
+            ));
+        }
+
+        let row = sqlx::query(sql_stmt::SELECT_SIBLING_COUNT)
+            .bind(category.parent_id)
+            .bind(category.name.trim())
+            .fetch_one(&mut *tx)
+            .await?;
+        let siblings: i64 = row.try_get("siblings")?;
+        if siblings > 0 {
+            return Err(super::Error::BadInput(format!(
+                "there's already a category named {} there",
+                category.name.trim()
+            )));
+        }
+
+        let result = sqlx::query(sql_stmt::INSERT_CATEGORY)
+            .bind(category.name.trim())
+            .bind(category.parent_id)
+            .bind(attributes)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(result.last_insert_rowid())
+    }
+
+    async fn get_category(
+        &mut self,
+        category_id: i64,
+    ) -> Result<Option<model::Record<model::CategoryDetails>>, Self::Error> {
+        let mut conn = self.connection.acquire().await?;
+        let result = sqlx::query(sql_stmt::SELECT_CATEGORY)
+            .bind(category_id)
+            .fetch_optional(&mut *conn)
+            .await?;
+
+        match result {
+            Some(row) => Ok(Some(category_from_row(&row)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn get_categories(
+        &mut self,
+    ) -> Result<Vec<model::Record<model::CategoryDetails>>, Self::Error> {
+        let mut conn = self.connection.acquire().await?;
+        let rows = sqlx::query(sql_stmt::SELECT_CATEGORIES)
+            .fetch_all(&mut *conn)
+            .await?;
+
+        let mut records = Vec::new();
+        for row in rows {
+            records.push(category_from_row(&row)?);
+        }
+        Ok(records)
+    }
+}
+
+mod sql_stmt {
+    pub const CREATE_TABLE: &str = r#"
+        CREATE TABLE IF NOT EXISTS categories (
+            id INTEGER NOT NULL UNIQUE PRIMARY KEY,
+            name TEXT NOT NULL,
+            parentId INTEGER,
+            attributes TEXT NOT NULL DEFAULT '[]'
+        );
+    "#;
+
+    pub const INSERT_CATEGORY: &str = r#"
+        INSERT INTO categories (name, parentId, attributes) VALUES ( $1, $2, $3 );
+    "#;
+
+    pub const SELECT_CATEGORY: &str = r#"
+        SELECT * FROM categories WHERE id=$1;
+    "#;
+
+    pub const SELECT_CATEGORIES: &str = r#"
+        SELECT * FROM categories ORDER BY id;
+    "#;
+
+    pub const SELECT_SIBLING_COUNT: &str = r#"
+        SELECT COUNT(*) AS siblings FROM categories WHERE parentId IS $1 AND name = $2;
+    "#;
+
+    pub const SELECT_ANCESTRY: &str = r#"
+        WITH RECURSIVE ancestry(id) AS (
+            SELECT $1
+            UNION ALL
+            SELECT categories.parentId FROM categories
+            JOIN ancestry ON categories.id = ancestry.id
+            WHERE categories.parentId IS NOT NULL
+        )
+        SELECT categories.* FROM categories JOIN ancestry ON categories.id = ancestry.id;
+    "#;
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{model, provider::SqliteProvider, service::category::CategoryService};
+
+    #[tokio::test]
+    async fn test_category_tree() {
+        let mut provider = SqliteProvider::new_memory().await.unwrap();
+        CategoryService::init_provider(&mut provider).await.unwrap();
+
+        let apparel = provider
+            .create_category(&model::CategoryDetails {
+                name: "Apparel".to_string(),
+                parent_id: None,
+                attributes: vec![model::AttributeDefinition {
+                    name: "size".to_string(),
+                    kind: model::AttributeKind::Enum,
+                    values: vec!["S".to_string(), "M".to_string(), "L".to_string()],
+                }],
+            })
+            .await
+            .unwrap();
+        let shirts = model::CategoryDetails {
+            name: "Shirts".to_string(),
+            parent_id: Some(apparel),
+            attributes: vec![model::AttributeDefinition {
+                name: "organic".to_string(),
+                kind: model::AttributeKind::Bool,
+                values: vec![],
+            }],
+        };
+        provider.create_category(&shirts).await.unwrap();
+
+        // siblings can't share a name
+        assert!(provider.create_category(&shirts).await.is_err());
+        // nor can a category redefine an attribute from above it
+        assert!(provider
+            .create_category(&model::CategoryDetails {
+                name: "Hats".to_string(),
+                parent_id: Some(apparel),
+                attributes: vec![model::AttributeDefinition {
+                    name: "size".to_string(),
+                    kind: model::AttributeKind::Number,
+                    values: vec![],
+                }],
+            })
+            .await
+            .is_err());
+        assert!(provider
+            .create_category(&model::CategoryDetails {
+                name: "Orphan".to_string(),
+                parent_id: Some(99),
+                attributes: vec![],
+            })
+            .await
+            .is_err());
+
+        assert_eq!(provider.get_categories().await.unwrap().len(), 2);
+        let category = provider.get_category(apparel).await.unwrap().unwrap();
+        assert_eq!(category.data.attributes.len(), 1);
+    }
+}
@@ -0,0 +1,208 @@
+use std::fmt::Display;
+
+use axum::http::StatusCode;
+use chrono::Utc;
+use futures::TryStreamExt;
+use rust_decimal::Decimal;
+use sqlx::Row;
+
+use crate::{
+    model::{self, ToRecord},
+    provider::SqliteProvider,
+};
+
+pub trait ExchangeRateService {
+    type Error: Display + Into<StatusCode>;
+
+    async fn init_provider(&mut self) -> Result<(), Self::Error>;
+
+    /// Records a new rate for a currency pair, effective immediately
+    async fn set_exchange_rate(
+        &mut self,
+        base_currency: &str,
+        quote_currency: &str,
+        rate: &Decimal,
+    ) -> Result<model::Record<model::ExchangeRateDetails>, Self::Error>;
+
+    /// Latest rate for each currency pair
+    async fn get_exchange_rates(
+        &mut self,
+    ) -> Result<Vec<model::Record<model::ExchangeRateDetails>>, Self::Error>;
+
+    /// Current rate converting `from` into `to`, using the inverse pair if needed
+    async fn get_conversion_rate(&mut self, from: &str, to: &str) -> Result<Decimal, Self::Error>;
+}
+
+impl ExchangeRateService for SqliteProvider {
+    type Error = super::Error;
+
+    async fn init_provider(&mut self) -> Result<(), Self::Error> {
+        let mut conn = self.connection.acquire().await?;
+        sqlx::query(sql_stmt::CREATE_TABLE)
+            .execute(&mut *conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn set_exchange_rate(
+        &mut self,
+        base_currency: &str,
+        quote_currency: &str,
+        rate: &Decimal,
+    ) -> Result<model::Record<model::ExchangeRateDetails>, Self::Error> {
+        let base_currency =
+            model::parse_currency_code(base_currency).map_err(super::Error::BadInput)?;
+        let quote_currency =
+            model::parse_currency_code(quote_currency).map_err(super::Error::BadInput)?;
+
+        if base_currency == quote_currency {
+            return Err(super::Error::BadInput(
+                "can't set a rate between a currency and itself".to_string(),
+            ));
+        }
+        if *rate <= Decimal::ZERO {
+            return Err(super::Error::BadInput(
+                "exchange rate must be positive".to_string(),
+            ));
+        }
+
+        let details = model::ExchangeRateDetails {
+            base_currency,
+            quote_currency,
+            rate: *rate,
+            effective_at: Utc::now(),
+        };
+
+        let mut conn = self.connection.acquire().await?;
+        let result = sqlx::query(sql_stmt::INSERT_RATE)
+            .bind(&details.base_currency)
+            .bind(&details.quote_currency)
+            .bind(details.rate.to_string())
+            .bind(details.effective_at)
+            .execute(&mut *conn)
+            .await?;
+
+        Ok(details.to_record(result.last_insert_rowid()))
+    }
+
+    async fn get_exchange_rates(
+        &mut self,
+    ) -> Result<Vec<model::Record<model::ExchangeRateDetails>>, Self::Error> {
+        let mut conn = self.connection.acquire().await?;
+        let mut rows = sqlx::query(sql_stmt::SELECT_LATEST_RATES).fetch(&mut *conn);
+
+        let mut records = Vec::new();
+        while let Some(row) = rows.try_next().await? {
+            records.push(
+                model::ExchangeRateDetails {
+                    base_currency: row.try_get("baseCurrency")?,
+                    quote_currency: row.try_get("quoteCurrency")?,
+                    rate: super::get_decimal(&row, "rate")?,
+                    effective_at: row.try_get("effectiveAt")?,
+                }
+                .to_record(row.try_get("id")?),
+            );
+        }
+
+        Ok(records)
+    }
+
+    async fn get_conversion_rate(&mut self, from: &str, to: &str) -> Result<Decimal, Self::Error> {
+        if from == to {
+            return Ok(Decimal::ONE);
+        }
+
+        let mut conn = self.connection.acquire().await?;
+        let result = sqlx::query(sql_stmt::SELECT_CONVERSION_RATE)
+            .bind(from)
+            .bind(to)
+            .fetch_optional(&mut *conn)
+            .await?;
+
+        let Some(row) = result else {
+            return Err(super::Error::BadInput(format!(
+                "no exchange rate from {} to {}",
+                from, to
+            )));
+        };
+
+        let rate = super::get_decimal(&row, "rate")?;
+        let base_currency: String = row.try_get("baseCurrency")?;
+
+        if base_currency == from {
+            Ok(rate)
+        } else {
+            Ok(Decimal::ONE / rate)
+        }
+    }
+}
+
+mod sql_stmt {
+    pub const CREATE_TABLE: &str = r#"
+        CREATE TABLE IF NOT EXISTS exchangeRates (
+            id INTEGER NOT NULL UNIQUE PRIMARY KEY,
+            baseCurrency TEXT NOT NULL,
+            quoteCurrency TEXT NOT NULL,
+            rate TEXT NOT NULL,
+            effectiveAt TEXT NOT NULL
+        );
+    "#;
+
+    pub const INSERT_RATE: &str = r#"
+        INSERT INTO exchangeRates (baseCurrency, quoteCurrency, rate, effectiveAt)
+        VALUES ( $1, $2, $3, $4 );
+    "#;
+
+    pub const SELECT_LATEST_RATES: &str = r#"
+        SELECT * FROM exchangeRates
+        WHERE id IN (
+            SELECT MAX(id) FROM exchangeRates GROUP BY baseCurrency, quoteCurrency
+        )
+        ORDER BY baseCurrency, quoteCurrency;
+    "#;
+
+    pub const SELECT_CONVERSION_RATE: &str = r#"
+        SELECT baseCurrency, rate FROM exchangeRates
+        WHERE (baseCurrency = $1 AND quoteCurrency = $2)
+        OR (baseCurrency = $2 AND quoteCurrency = $1)
+        ORDER BY id DESC
+        LIMIT 1;
+    "#;
+}
+
+#[cfg(test)]
+mod test {
+    use rust_decimal::Decimal;
+
+    use crate::{provider::SqliteProvider, service::currency::ExchangeRateService};
+
+    #[tokio::test]
+    async fn test_conversion_rate() {
+        let mut provider = SqliteProvider::new_memory().await.unwrap();
+        ExchangeRateService::init_provider(&mut provider)
+            .await
+            .unwrap();
+
+        provider
+            .set_exchange_rate("usd", "eur", &Decimal::new(9, 1))
+            .await
+            .unwrap();
+        provider
+            .set_exchange_rate("EUR", "USD", &Decimal::new(125, 2))
+            .await
+            .unwrap();
+
+        // the most recent rate for the pair wins in either direction
+        let rate = provider.get_conversion_rate("EUR", "USD").await.unwrap();
+        assert_eq!(rate, Decimal::new(125, 2));
+        let rate = provider.get_conversion_rate("USD", "EUR").await.unwrap();
+        assert_eq!(rate, Decimal::new(8, 1));
+
+        assert_eq!(provider.get_exchange_rates().await.unwrap().len(), 2);
+        assert!(provider.get_conversion_rate("USD", "JPY").await.is_err());
+        assert!(provider
+            .set_exchange_rate("USD", "USD", &Decimal::ONE)
+            .await
+            .is_err());
+    }
+}
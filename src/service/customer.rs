@@ -0,0 +1,461 @@
+use std::fmt::Display;
+
+use axum::http::StatusCode;
+use futures::TryStreamExt;
+use log::warn;
+use sqlx::{sqlite::SqliteRow, Row, SqliteConnection};
+
+use crate::{
+    model::{self, ToRecord},
+    provider::SqliteProvider,
+};
+
+pub trait CustomerService {
+    type Error: Display + Into<StatusCode>;
+
+    async fn init_provider(&mut self) -> Result<(), Self::Error>;
+
+    async fn create_customer(
+        &mut self,
+        customer: &model::CustomerDetails,
+    ) -> Result<i64, Self::Error>;
+
+    async fn get_customer(
+        &mut self,
+        customer_id: i64,
+    ) -> Result<Option<model::Record<model::CustomerDetails>>, Self::Error>;
+
+    /// Adds an address to a customer, the first one added becomes the default
+    async fn add_address(
+        &mut self,
+        address: &model::AddressDetails,
+    ) -> Result<model::Record<model::AddressDetails>, Self::Error>;
+
+    async fn get_address(
+        &mut self,
+        address_id: i64,
+    ) -> Result<Option<model::Record<model::AddressDetails>>, Self::Error>;
+
+    async fn get_addresses(
+        &mut self,
+        customer_id: i64,
+    ) -> Result<Vec<model::Record<model::AddressDetails>>, Self::Error>;
+
+    async fn get_default_address(
+        &mut self,
+        customer_id: i64,
+    ) -> Result<Option<model::Record<model::AddressDetails>>, Self::Error>;
+}
+
+/// Reads the address columns shared by addresses and ship-to snapshots
+pub fn address_from_row(row: &SqliteRow) -> Result<model::Address, super::Error> {
+    Ok(model::Address {
+        recipient: row.try_get("recipient")?,
+        line1: row.try_get("line1")?,
+        line2: row.try_get("line2")?,
+        city: row.try_get("city")?,
+        region: row.try_get("region")?,
+        postal_code: row.try_get("postalCode")?,
+        country: row.try_get("country")?,
+        latitude: row.try_get("latitude")?,
+        longitude: row.try_get("longitude")?,
+    })
+}
+
+fn address_details_from_row(
+    row: &SqliteRow,
+) -> Result<model::Record<model::AddressDetails>, super::Error> {
+    Ok(model::AddressDetails {
+        customer_id: row.try_get("customerId")?,
+        label: row.try_get("label")?,
+        is_default: row.try_get("isDefault")?,
+        address: address_from_row(row)?,
+    }
+    .to_record(row.try_get("id")?))
+}
+
+pub async fn customer_details(
+    conn: &mut SqliteConnection,
+    customer_id: i64,
+) -> Result<Option<model::Record<model::CustomerDetails>>, super::Error> {
+    let result = sqlx::query(sql_stmt::SELECT_CUSTOMER)
+        .bind(customer_id)
+        .fetch_optional(&mut *conn)
+        .await?;
+
+    let Some(row) = result else {
+        warn!("Sql row not found");
+        return Ok(None);
+    };
+
+    Ok(Some(
+        model::CustomerDetails {
+            name: row.try_get("name")?,
+            email: row.try_get("email")?,
+            phone: row.try_get("phone")?,
+        }
+        .to_record(customer_id),
+    ))
+}
+
+pub async fn default_address(
+    conn: &mut SqliteConnection,
+    customer_id: i64,
+) -> Result<Option<model::Record<model::AddressDetails>>, super::Error> {
+    let result = sqlx::query(sql_stmt::SELECT_DEFAULT_ADDRESS)
+        .bind(customer_id)
+        .fetch_optional(&mut *conn)
+        .await?;
+
+    result.as_ref().map(address_details_from_row).transpose()
+}
+
+impl CustomerService for SqliteProvider {
+    type Error = super::Error;
+
+    async fn init_provider(&mut self) -> Result<(), Self::Error> {
+        let mut conn = self.connection.acquire().await?;
+        sqlx::query(sql_stmt::CREATE_CUSTOMER_TABLE)
+            .execute(&mut *conn)
+            .await?;
+        sqlx::query(sql_stmt::CREATE_ADDRESS_TABLE)
+            .execute(&mut *conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn create_customer(
+        &mut self,
+        customer: &model::CustomerDetails,
+    ) -> Result<i64, Self::Error> {
+        if customer.name.trim().is_empty() {
+            return Err(super::Error::BadInput(
+                "customer name can't be empty".to_string(),
+            ));
+        }
+
+        let mut conn = self.connection.acquire().await?;
+        let result = sqlx::query(sql_stmt::INSERT_CUSTOMER)
+            .bind(&customer.name)
+            .bind(&customer.email)
+            .bind(&customer.phone)
+            .execute(&mut *conn)
+            .await?;
+
+        Ok(result.last_insert_rowid())
+    }
+
+    async fn get_customer(
+        &mut self,
+        customer_id: i64,
+    ) -> Result<Option<model::Record<model::CustomerDetails>>, Self::Error> {
+        let mut conn = self.connection.acquire().await?;
+        customer_details(&mut conn, customer_id).await
+    }
+
+    async fn add_address(
+        &mut self,
+        address: &model::AddressDetails,
+    ) -> Result<model::Record<model::AddressDetails>, Self::Error> {
+        let mut tx = self.connection.begin().await?;
+
+        let existing: i64 = sqlx::query(sql_stmt::COUNT_ADDRESSES)
+            .bind(address.customer_id)
+            .fetch_one(&mut *tx)
+            .await?
+            .try_get("count")?;
+        let is_default = address.is_default || existing == 0;
+
+        if is_default {
+            sqlx::query(sql_stmt::CLEAR_DEFAULT_ADDRESS)
+                .bind(address.customer_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        let result = sqlx::query(sql_stmt::INSERT_ADDRESS)
+            .bind(address.customer_id)
+            .bind(&address.label)
+            .bind(is_default)
+            .bind(&address.address.recipient)
+            .bind(&address.address.line1)
+            .bind(&address.address.line2)
+            .bind(&address.address.city)
+            .bind(&address.address.region)
+            .bind(&address.address.postal_code)
+            .bind(&address.address.country)
+            .bind(address.address.latitude)
+            .bind(address.address.longitude)
+            .execute(&mut *tx)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(super::Error::NotFound(format!(
+                "customer {}",
+                address.customer_id
+            )));
+        }
+
+        tx.commit().await?;
+
+        Ok(model::AddressDetails {
+            is_default,
+            ..address.clone()
+        }
+        .to_record(result.last_insert_rowid()))
+    }
+
+    async fn get_address(
+        &mut self,
+        address_id: i64,
+    ) -> Result<Option<model::Record<model::AddressDetails>>, Self::Error> {
+        let mut conn = self.connection.acquire().await?;
+        let result = sqlx::query(sql_stmt::SELECT_ADDRESS)
+            .bind(address_id)
+            .fetch_optional(&mut *conn)
+            .await?;
+
+        result.as_ref().map(address_details_from_row).transpose()
+    }
+
+    async fn get_addresses(
+        &mut self,
+        customer_id: i64,
+    ) -> Result<Vec<model::Record<model::AddressDetails>>, Self::Error> {
+        let mut conn = self.connection.acquire().await?;
+        let mut rows = sqlx::query(sql_stmt::SELECT_ADDRESSES)
+            .bind(customer_id)
+            .fetch(&mut *conn);
+
+        let mut records = Vec::new();
+        while let Some(row) = rows.try_next().await? {
+            records.push(address_details_from_row(&row)?);
+        }
+
+        Ok(records)
+    }
+
+    async fn get_default_address(
+        &mut self,
+        customer_id: i64,
+    ) -> Result<Option<model::Record<model::AddressDetails>>, Self::Error> {
+        let mut conn = self.connection.acquire().await?;
+        default_address(&mut conn, customer_id).await
+    }
+}
+
+mod sql_stmt {
+    pub const CREATE_CUSTOMER_TABLE: &str = r#"
+        CREATE TABLE IF NOT EXISTS customers (
+            id INTEGER NOT NULL UNIQUE PRIMARY KEY,
+            name TEXT NOT NULL,
+            email TEXT,
+            phone TEXT
+        );
+    "#;
+
+    pub const CREATE_ADDRESS_TABLE: &str = r#"
+        CREATE TABLE IF NOT EXISTS addresses (
+            id INTEGER NOT NULL UNIQUE PRIMARY KEY,
+            customerId INTEGER NOT NULL,
+            label TEXT,
+            isDefault BOOLEAN NOT NULL,
+            recipient TEXT NOT NULL,
+            line1 TEXT NOT NULL,
+            line2 TEXT,
+            city TEXT NOT NULL,
+            region TEXT,
+            postalCode TEXT NOT NULL,
+            country TEXT NOT NULL,
+            latitude REAL,
+            longitude REAL
+        );
+    "#;
+
+    pub const INSERT_CUSTOMER: &str = r#"
+        INSERT INTO customers (name, email, phone) VALUES ( $1, $2, $3 );
+    "#;
+
+    pub const SELECT_CUSTOMER: &str = r#"
+        SELECT id, name, email, phone FROM customers WHERE id=$1;
+    "#;
+
+    pub const COUNT_ADDRESSES: &str = r#"
+        SELECT COUNT(*) AS count FROM addresses WHERE customerId=$1;
+    "#;
+
+    pub const CLEAR_DEFAULT_ADDRESS: &str = r#"
+        UPDATE addresses SET isDefault = FALSE WHERE customerId=$1;
+    "#;
+
+    pub const INSERT_ADDRESS: &str = r#"
+        INSERT INTO addresses (
+            customerId, label, isDefault, recipient, line1, line2, city, region, postalCode, country,
+            latitude, longitude
+        )
+        SELECT $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12
+        WHERE EXISTS ( SELECT 1 FROM customers WHERE id = $1 );
+    "#;
+
+    pub const SELECT_ADDRESS: &str = r#"
+        SELECT * FROM addresses WHERE id=$1;
+    "#;
+
+    pub const SELECT_ADDRESSES: &str = r#"
+        SELECT * FROM addresses WHERE customerId=$1 ORDER BY id;
+    "#;
+
+    pub const SELECT_DEFAULT_ADDRESS: &str = r#"
+        SELECT * FROM addresses WHERE customerId=$1 AND isDefault LIMIT 1;
+    "#;
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        model,
+        provider::SqliteProvider,
+        service::{
+            customer::CustomerService, fulfillment::FulfillmentService, order::OrderService,
+        },
+    };
+
+    fn address(recipient: &str) -> model::Address {
+        model::Address {
+            recipient: recipient.to_string(),
+            line1: "1 Main St".to_string(),
+            line2: None,
+            city: "Springfield".to_string(),
+            region: None,
+            postal_code: "12345".to_string(),
+            country: "US".to_string(),
+            latitude: None,
+            longitude: None,
+        }
+    }
+
+    async fn setup() -> SqliteProvider {
+        let mut provider = SqliteProvider::new_memory().await.unwrap();
+        CustomerService::init_provider(&mut provider).await.unwrap();
+        OrderService::init_provider(&mut provider).await.unwrap();
+        FulfillmentService::init_provider(&mut provider)
+            .await
+            .unwrap();
+        provider
+    }
+
+    #[tokio::test]
+    async fn test_default_address() {
+        let mut provider = setup().await;
+        let customer_id = provider
+            .create_customer(&model::CustomerDetails {
+                name: "Ada".to_string(),
+                email: None,
+                phone: None,
+            })
+            .await
+            .unwrap();
+
+        let first = provider
+            .add_address(&model::AddressDetails {
+                customer_id,
+                label: Some("home".to_string()),
+                is_default: false,
+                address: address("Ada"),
+            })
+            .await
+            .unwrap();
+        assert!(first.data.is_default);
+
+        let second = provider
+            .add_address(&model::AddressDetails {
+                customer_id,
+                label: Some("work".to_string()),
+                is_default: true,
+                address: address("Ada at work"),
+            })
+            .await
+            .unwrap();
+
+        let default = provider.get_default_address(customer_id).await.unwrap();
+        assert_eq!(default.unwrap().id, second.id);
+        assert_eq!(provider.get_addresses(customer_id).await.unwrap().len(), 2);
+
+        assert!(provider
+            .add_address(&model::AddressDetails {
+                customer_id: 42,
+                label: None,
+                is_default: false,
+                address: address("Nobody"),
+            })
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_delivery_ship_to_snapshot() {
+        let mut provider = setup().await;
+        let customer_id = provider
+            .create_customer(&model::CustomerDetails {
+                name: "Ada".to_string(),
+                email: Some("ada@example.com".to_string()),
+                phone: None,
+            })
+            .await
+            .unwrap();
+        provider
+            .add_address(&model::AddressDetails {
+                customer_id,
+                label: None,
+                is_default: true,
+                address: address("Ada"),
+            })
+            .await
+            .unwrap();
+        let order_id = provider
+            .create_order(Some(customer_id), None, "USD")
+            .await
+            .unwrap();
+
+        let fulfillment_id = provider
+            .create_fulfillment(&model::NewFulfillment {
+                order_id: Some(order_id),
+                ..model::NewFulfillment::from(model::FulfillmentType::StockDelivery)
+            })
+            .await
+            .unwrap();
+
+        // later address changes don't reach fulfillments already created
+        provider
+            .add_address(&model::AddressDetails {
+                customer_id,
+                label: None,
+                is_default: true,
+                address: address("Ada moved"),
+            })
+            .await
+            .unwrap();
+
+        let fulfillment = provider
+            .get_fulfillment(&fulfillment_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(fulfillment.data.order_id, Some(order_id));
+        assert_eq!(fulfillment.data.ship_to.unwrap().recipient, "Ada");
+
+        assert!(provider
+            .create_fulfillment(&model::NewFulfillment::from(
+                model::FulfillmentType::StockDelivery
+            ))
+            .await
+            .is_err());
+        assert!(provider
+            .create_fulfillment(&model::NewFulfillment {
+                ship_to_address_id: Some(1),
+                ..model::NewFulfillment::from(model::FulfillmentType::StockPickUp)
+            })
+            .await
+            .is_err());
+    }
+}
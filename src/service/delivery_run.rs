@@ -0,0 +1,649 @@
+use std::fmt::Display;
+
+use axum::http::StatusCode;
+use chrono::{NaiveDate, Utc};
+use sqlx::{sqlite::SqliteRow, Row, SqliteConnection};
+
+use crate::{
+    model::{self, ToRecord},
+    provider::SqliteProvider,
+    service::{
+        fulfillment::FulfillmentService, line_item::LineItemService, product::ProductService,
+    },
+};
+
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+pub trait DeliveryRunService {
+    type Error: Display + Into<StatusCode>;
+
+    async fn init_provider(&mut self) -> Result<(), Self::Error>;
+
+    /// Plans a run over stock deliveries that have been reserved but not delivered yet.
+    ///
+    /// Every delivery needs coordinates on its ship-to address and a weight for each of
+    /// its products. Stops are visited nearest first from the start and the run must fit
+    /// in the vehicle.
+    async fn create_delivery_run(
+        &mut self,
+        run: &model::NewDeliveryRun,
+    ) -> Result<i64, Self::Error>;
+
+    async fn get_delivery_run(
+        &mut self,
+        delivery_run_id: i64,
+    ) -> Result<Option<model::Record<model::DeliveryRunDetails>>, Self::Error>;
+
+    async fn get_delivery_runs_by_date(
+        &mut self,
+        date: NaiveDate,
+    ) -> Result<Vec<model::Record<model::DeliveryRunDetails>>, Self::Error>;
+
+    /// Runs can only be completed once every stop has been fulfilled
+    async fn set_delivery_run_status(
+        &mut self,
+        delivery_run_id: i64,
+        status: model::DeliveryRunStatus,
+    ) -> Result<(), Self::Error>;
+}
+
+impl DeliveryRunService for SqliteProvider {
+    type Error = super::Error;
+
+    async fn init_provider(&mut self) -> Result<(), Self::Error> {
+        let mut conn = self.connection.acquire().await?;
+        sqlx::query(sql_stmt::CREATE_DELIVERY_RUN_TABLE)
+            .execute(&mut *conn)
+            .await?;
+        sqlx::query(sql_stmt::CREATE_DELIVERY_STOP_TABLE)
+            .execute(&mut *conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn create_delivery_run(
+        &mut self,
+        run: &model::NewDeliveryRun,
+    ) -> Result<i64, Self::Error> {
+        let mut fulfillment_ids = run.fulfillment_ids.clone();
+        fulfillment_ids.sort();
+        fulfillment_ids.dedup();
+
+        if fulfillment_ids.is_empty() {
+            return Err(super::Error::BadInput(
+                "a delivery run needs at least one stop".to_string(),
+            ));
+        }
+        if run.capacity_grams <= 0 {
+            return Err(super::Error::BadInput(
+                "vehicle capacity must be positive".to_string(),
+            ));
+        }
+
+        let mut stops = Vec::new();
+        for fulfillment_id in &fulfillment_ids {
+            stops.push(self.delivery_stop(*fulfillment_id).await?);
+        }
+
+        let load_grams: i64 = stops.iter().map(|s| s.weight_grams).sum();
+        if load_grams > run.capacity_grams {
+            return Err(super::Error::BadInput(format!(
+                "run weighs {}g, the vehicle takes {}g",
+                load_grams, run.capacity_grams
+            )));
+        }
+
+        let stops = order_stops((run.start_latitude, run.start_longitude), stops);
+
+        let mut tx = self.connection.begin().await?;
+        for fulfillment_id in &fulfillment_ids {
+            let row = sqlx::query(sql_stmt::SELECT_ACTIVE_RUN_COUNT)
+                .bind(fulfillment_id)
+                .fetch_one(&mut *tx)
+                .await?;
+            let active_runs: i64 = row.try_get("activeRuns")?;
+            if active_runs > 0 {
+                return Err(super::Error::BadInput(format!(
+                    "fulfillment {} is already on a delivery run",
+                    fulfillment_id
+                )));
+            }
+        }
+
+        let result = sqlx::query(sql_stmt::INSERT_DELIVERY_RUN)
+            .bind(run.date)
+            .bind(String::from(model::DeliveryRunStatus::Planned))
+            .bind(run.start_latitude)
+            .bind(run.start_longitude)
+            .bind(run.capacity_grams)
+            .execute(&mut *tx)
+            .await?;
+        let delivery_run_id = result.last_insert_rowid();
+
+        for (sequence, stop) in stops.iter().enumerate() {
+            sqlx::query(sql_stmt::INSERT_DELIVERY_STOP)
+                .bind(delivery_run_id)
+                .bind(sequence as i64)
+                .bind(stop.fulfillment_id)
+                .bind(stop.latitude)
+                .bind(stop.longitude)
+                .bind(stop.weight_grams)
+                .bind(stop.distance_km)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+        Ok(delivery_run_id)
+    }
+
+    async fn get_delivery_run(
+        &mut self,
+        delivery_run_id: i64,
+    ) -> Result<Option<model::Record<model::DeliveryRunDetails>>, Self::Error> {
+        let mut conn = self.connection.acquire().await?;
+        let result = sqlx::query(sql_stmt::SELECT_DELIVERY_RUN)
+            .bind(delivery_run_id)
+            .fetch_optional(&mut *conn)
+            .await?;
+
+        match result {
+            Some(row) => Ok(Some(run_from_row(&mut conn, &row).await?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn get_delivery_runs_by_date(
+        &mut self,
+        date: NaiveDate,
+    ) -> Result<Vec<model::Record<model::DeliveryRunDetails>>, Self::Error> {
+        let mut conn = self.connection.acquire().await?;
+        let rows = sqlx::query(sql_stmt::SELECT_DELIVERY_RUNS_BY_DATE)
+            .bind(date)
+            .fetch_all(&mut *conn)
+            .await?;
+
+        let mut runs = Vec::new();
+        for row in rows {
+            runs.push(run_from_row(&mut conn, &row).await?);
+        }
+        Ok(runs)
+    }
+
+    async fn set_delivery_run_status(
+        &mut self,
+        delivery_run_id: i64,
+        status: model::DeliveryRunStatus,
+    ) -> Result<(), Self::Error> {
+        let Some(run) = self.get_delivery_run(delivery_run_id).await? else {
+            return Err(super::Error::NotFound(format!(
+                "delivery run {}",
+                delivery_run_id
+            )));
+        };
+
+        if !status.allowed_priors().contains(&run.data.status) {
+            return Err(super::Error::BadInput(
+                "bad delivery run status transition".to_string(),
+            ));
+        }
+
+        if status == model::DeliveryRunStatus::Completed {
+            for stop in &run.data.stops {
+                let fulfilled = self
+                    .get_fulfillment(&stop.fulfillment_id)
+                    .await?
+                    .is_some_and(|f| f.data.status == model::FulfillmentStatus::Fulfilled);
+                if !fulfilled {
+                    return Err(super::Error::BadInput(format!(
+                        "fulfillment {} hasn't been delivered",
+                        stop.fulfillment_id
+                    )));
+                }
+            }
+        }
+
+        let mut conn = self.connection.acquire().await?;
+        let query = match status {
+            model::DeliveryRunStatus::Dispatched => {
+                sqlx::query(sql_stmt::UPDATE_DELIVERY_RUN_DISPATCHED).bind(Utc::now())
+            }
+            model::DeliveryRunStatus::Completed => {
+                sqlx::query(sql_stmt::UPDATE_DELIVERY_RUN_COMPLETED).bind(Utc::now())
+            }
+            _ => sqlx::query(sql_stmt::UPDATE_DELIVERY_RUN_STATUS),
+        };
+        let result = query
+            .bind(String::from(status))
+            .bind(delivery_run_id)
+            .bind(String::from(run.data.status))
+            .execute(&mut *conn)
+            .await?;
+        if result.rows_affected() != 1 {
+            return Err(super::Error::BadInput(
+                "delivery run status changed concurrently".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl SqliteProvider {
+    /// Where a stock delivery goes and what it weighs, distance is filled in once the
+    /// stops are ordered
+    async fn delivery_stop(
+        &mut self,
+        fulfillment_id: i64,
+    ) -> Result<model::DeliveryStop, super::Error> {
+        let Some(fulfillment) = self.get_fulfillment(&fulfillment_id).await? else {
+            return Err(super::Error::NotFound(format!(
+                "fulfillment {}",
+                fulfillment_id
+            )));
+        };
+        if fulfillment.data.fulfillment_type != model::FulfillmentType::StockDelivery {
+            return Err(super::Error::BadInput(format!(
+                "fulfillment {} isn't a stock delivery",
+                fulfillment_id
+            )));
+        }
+        if matches!(
+            fulfillment.data.status,
+            model::FulfillmentStatus::New | model::FulfillmentStatus::Fulfilled
+        ) {
+            return Err(super::Error::BadInput(format!(
+                "fulfillment {} isn't ready for delivery",
+                fulfillment_id
+            )));
+        }
+        let (Some(latitude), Some(longitude)) = fulfillment
+            .data
+            .ship_to
+            .as_ref()
+            .map_or((None, None), |a| (a.latitude, a.longitude))
+        else {
+            return Err(super::Error::BadInput(format!(
+                "fulfillment {} has no delivery coordinates",
+                fulfillment_id
+            )));
+        };
+
+        let mut weight_grams = 0;
+        for line_item in self
+            .get_line_items_by_fulfillment_id(fulfillment_id)
+            .await?
+        {
+            let product = self.get_product(&line_item.data.product_id).await?;
+            let Some(weight) = product.data.weight_grams else {
+                return Err(super::Error::BadInput(format!(
+                    "product {} has no weight",
+                    product.data.sku
+                )));
+            };
+            weight_grams += weight * line_item.data.quantity;
+        }
+
+        Ok(model::DeliveryStop {
+            fulfillment_id,
+            latitude,
+            longitude,
+            weight_grams,
+            distance_km: 0.0,
+        })
+    }
+}
+
+/// Great circle distance between two points in degrees
+fn distance_km(from: (f64, f64), to: (f64, f64)) -> f64 {
+    let (lat1, lon1) = (from.0.to_radians(), from.1.to_radians());
+    let (lat2, lon2) = (to.0.to_radians(), to.1.to_radians());
+    let a = ((lat2 - lat1) / 2.0).sin().powi(2)
+        + lat1.cos() * lat2.cos() * ((lon2 - lon1) / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+}
+
+/// Nearest neighbour ordering, each stop is the closest one not yet visited
+fn order_stops(
+    start: (f64, f64),
+    mut remaining: Vec<model::DeliveryStop>,
+) -> Vec<model::DeliveryStop> {
+    let mut ordered = Vec::new();
+    let mut here = start;
+    while !remaining.is_empty() {
+        let (next, distance) = remaining
+            .iter()
+            .map(|s| distance_km(here, (s.latitude, s.longitude)))
+            .enumerate()
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .unwrap();
+        let mut stop = remaining.remove(next);
+        stop.distance_km = distance;
+        here = (stop.latitude, stop.longitude);
+        ordered.push(stop);
+    }
+    ordered
+}
+
+async fn run_from_row(
+    conn: &mut SqliteConnection,
+    row: &SqliteRow,
+) -> Result<model::Record<model::DeliveryRunDetails>, super::Error> {
+    let delivery_run_id: i64 = row.try_get("id")?;
+    let rows = sqlx::query(sql_stmt::SELECT_DELIVERY_STOPS)
+        .bind(delivery_run_id)
+        .fetch_all(&mut *conn)
+        .await?;
+
+    let mut stops = Vec::new();
+    for stop in rows {
+        stops.push(model::DeliveryStop {
+            fulfillment_id: stop.try_get("fulfillmentId")?,
+            latitude: stop.try_get("latitude")?,
+            longitude: stop.try_get("longitude")?,
+            weight_grams: stop.try_get("weightGrams")?,
+            distance_km: stop.try_get("distanceKm")?,
+        });
+    }
+
+    let status: String = row.try_get("runStatus")?;
+    Ok(model::DeliveryRunDetails {
+        date: row.try_get("date")?,
+        status: status.parse().map_err(super::Error::ProviderFailure)?,
+        start_latitude: row.try_get("startLatitude")?,
+        start_longitude: row.try_get("startLongitude")?,
+        capacity_grams: row.try_get("capacityGrams")?,
+        load_grams: stops.iter().map(|s| s.weight_grams).sum(),
+        distance_km: stops.iter().map(|s| s.distance_km).sum(),
+        stops,
+        dispatched_at: row.try_get("dispatchedAt")?,
+        completed_at: row.try_get("completedAt")?,
+    }
+    .to_record(delivery_run_id))
+}
+
+mod sql_stmt {
+    pub const CREATE_DELIVERY_RUN_TABLE: &str = r#"
+        CREATE TABLE IF NOT EXISTS deliveryRuns (
+            id INTEGER NOT NULL UNIQUE PRIMARY KEY,
+            date TEXT NOT NULL,
+            runStatus TEXT NOT NULL,
+            startLatitude REAL NOT NULL,
+            startLongitude REAL NOT NULL,
+            capacityGrams INTEGER NOT NULL,
+            dispatchedAt TEXT,
+            completedAt TEXT
+        );
+    "#;
+
+    pub const CREATE_DELIVERY_STOP_TABLE: &str = r#"
+        CREATE TABLE IF NOT EXISTS deliveryStops (
+            deliveryRunId INTEGER NOT NULL,
+            sequence INTEGER NOT NULL,
+            fulfillmentId INTEGER NOT NULL,
+            latitude REAL NOT NULL,
+            longitude REAL NOT NULL,
+            weightGrams INTEGER NOT NULL,
+            distanceKm REAL NOT NULL,
+            PRIMARY KEY (deliveryRunId, sequence)
+        );
+    "#;
+
+    pub const INSERT_DELIVERY_RUN: &str = r#"
+        INSERT INTO deliveryRuns (date, runStatus, startLatitude, startLongitude, capacityGrams)
+        VALUES ( $1, $2, $3, $4, $5 );
+    "#;
+
+    pub const INSERT_DELIVERY_STOP: &str = r#"
+        INSERT INTO deliveryStops (
+            deliveryRunId, sequence, fulfillmentId, latitude, longitude, weightGrams, distanceKm
+        )
+        VALUES ( $1, $2, $3, $4, $5, $6, $7 );
+    "#;
+
+    pub const SELECT_ACTIVE_RUN_COUNT: &str = r#"
+        SELECT COUNT(*) AS activeRuns
+        FROM deliveryStops
+        JOIN deliveryRuns ON deliveryRuns.id = deliveryStops.deliveryRunId
+        WHERE deliveryStops.fulfillmentId = $1
+        AND deliveryRuns.runStatus IN ('Planned', 'Dispatched');
+    "#;
+
+    pub const SELECT_DELIVERY_RUN: &str = r#"
+        SELECT * FROM deliveryRuns WHERE id=$1;
+    "#;
+
+    pub const SELECT_DELIVERY_RUNS_BY_DATE: &str = r#"
+        SELECT * FROM deliveryRuns WHERE date=$1 ORDER BY id;
+    "#;
+
+    pub const SELECT_DELIVERY_STOPS: &str = r#"
+        SELECT * FROM deliveryStops WHERE deliveryRunId=$1 ORDER BY sequence;
+    "#;
+
+    pub const UPDATE_DELIVERY_RUN_STATUS: &str = r#"
+        UPDATE deliveryRuns SET runStatus = $1
+        WHERE id = $2 AND runStatus = $3;
+    "#;
+
+    pub const UPDATE_DELIVERY_RUN_DISPATCHED: &str = r#"
+        UPDATE deliveryRuns SET dispatchedAt = $1, runStatus = $2
+        WHERE id = $3 AND runStatus = $4;
+    "#;
+
+    pub const UPDATE_DELIVERY_RUN_COMPLETED: &str = r#"
+        UPDATE deliveryRuns SET completedAt = $1, runStatus = $2
+        WHERE id = $3 AND runStatus = $4;
+    "#;
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::NaiveDate;
+
+    use crate::{
+        model,
+        provider::SqliteProvider,
+        service::{
+            customer::CustomerService, delivery_run::DeliveryRunService,
+            fulfillment::FulfillmentService, inventory::InventoryService,
+            line_item::LineItemService, product::ProductService,
+        },
+    };
+
+    /// A 1kg product in stock and a customer to deliver it to
+    async fn setup() -> (SqliteProvider, i64) {
+        let mut provider = SqliteProvider::new_memory().await.unwrap();
+        ProductService::init_provider(&mut provider).await.unwrap();
+        FulfillmentService::init_provider(&mut provider)
+            .await
+            .unwrap();
+        LineItemService::init_provider(&mut provider).await.unwrap();
+        InventoryService::init_provider(&mut provider)
+            .await
+            .unwrap();
+        CustomerService::init_provider(&mut provider).await.unwrap();
+        DeliveryRunService::init_provider(&mut provider)
+            .await
+            .unwrap();
+
+        provider
+            .create_product(&model::ProductDetails {
+                sku: "SKU-1".to_string(),
+                description: "widget".to_string(),
+                weight_grams: Some(1000),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        provider
+            .record_stock_movement(1, None, 20, model::StockMovementReason::Receipt, None)
+            .await
+            .unwrap();
+        let customer_id = provider
+            .create_customer(&model::CustomerDetails {
+                name: "Ada".to_string(),
+                email: None,
+                phone: None,
+            })
+            .await
+            .unwrap();
+
+        (provider, customer_id)
+    }
+
+    /// Initialized delivery of `quantity` widgets to a point on the equator
+    async fn delivery(
+        provider: &mut SqliteProvider,
+        customer_id: i64,
+        longitude: f64,
+        quantity: i64,
+    ) -> i64 {
+        let address = provider
+            .add_address(&model::AddressDetails {
+                customer_id,
+                label: None,
+                is_default: false,
+                address: model::Address {
+                    recipient: "Ada".to_string(),
+                    line1: "1 Analytical Row".to_string(),
+                    line2: None,
+                    city: "London".to_string(),
+                    region: None,
+                    postal_code: "N1 1AA".to_string(),
+                    country: "GB".to_string(),
+                    latitude: Some(0.0),
+                    longitude: Some(longitude),
+                },
+            })
+            .await
+            .unwrap();
+        let fulfillment_id = provider
+            .create_fulfillment(&model::NewFulfillment {
+                ship_to_address_id: Some(address.id),
+                ..model::NewFulfillment::from(model::FulfillmentType::StockDelivery)
+            })
+            .await
+            .unwrap();
+        provider
+            .create_line_item(fulfillment_id, 1, quantity, None)
+            .await
+            .unwrap();
+        provider
+            .set_fulfillment_status(
+                &fulfillment_id,
+                &model::FulfillmentStatus::Initialized.into(),
+            )
+            .await
+            .unwrap();
+        fulfillment_id
+    }
+
+    fn new_run(fulfillment_ids: Vec<i64>, capacity_grams: i64) -> model::NewDeliveryRun {
+        model::NewDeliveryRun {
+            date: NaiveDate::from_ymd_opt(2024, 3, 1).unwrap(),
+            start_latitude: 0.0,
+            start_longitude: 0.0,
+            capacity_grams,
+            fulfillment_ids,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stop_order_and_capacity() {
+        let (mut provider, customer_id) = setup().await;
+        let far = delivery(&mut provider, customer_id, 3.0, 1).await;
+        let near = delivery(&mut provider, customer_id, 1.0, 2).await;
+        let middle = delivery(&mut provider, customer_id, 2.0, 3).await;
+
+        assert!(provider
+            .create_delivery_run(&new_run(vec![far, near, middle], 5999))
+            .await
+            .is_err());
+        let run_id = provider
+            .create_delivery_run(&new_run(vec![far, near, middle], 6000))
+            .await
+            .unwrap();
+        // each stop can only be on one run at a time
+        assert!(provider
+            .create_delivery_run(&new_run(vec![near], 6000))
+            .await
+            .is_err());
+
+        let run = provider.get_delivery_run(run_id).await.unwrap().unwrap();
+        let order: Vec<i64> = run.data.stops.iter().map(|s| s.fulfillment_id).collect();
+        assert_eq!(order, vec![near, middle, far]);
+        assert_eq!(run.data.load_grams, 6000);
+        // a degree of longitude on the equator is about 111km
+        assert!((run.data.distance_km - 333.6).abs() < 1.0);
+
+        let runs = provider
+            .get_delivery_runs_by_date(run.data.date)
+            .await
+            .unwrap();
+        assert_eq!(runs.len(), 1);
+
+        // only stock deliveries go out on runs
+        let pickup = provider
+            .create_fulfillment(&model::NewFulfillment::from(
+                model::FulfillmentType::StockPickUp,
+            ))
+            .await
+            .unwrap();
+        assert!(provider
+            .create_delivery_run(&new_run(vec![pickup], 6000))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_delivery_run_status() {
+        let (mut provider, customer_id) = setup().await;
+        let fulfillment_id = delivery(&mut provider, customer_id, 1.0, 1).await;
+
+        let cancelled = provider
+            .create_delivery_run(&new_run(vec![fulfillment_id], 1000))
+            .await
+            .unwrap();
+        provider
+            .set_delivery_run_status(cancelled, model::DeliveryRunStatus::Cancelled)
+            .await
+            .unwrap();
+
+        // cancelling frees the stop up for another run
+        let run_id = provider
+            .create_delivery_run(&new_run(vec![fulfillment_id], 1000))
+            .await
+            .unwrap();
+        assert!(provider
+            .set_delivery_run_status(run_id, model::DeliveryRunStatus::Completed)
+            .await
+            .is_err());
+        provider
+            .set_delivery_run_status(run_id, model::DeliveryRunStatus::Dispatched)
+            .await
+            .unwrap();
+        assert!(provider
+            .set_delivery_run_status(run_id, model::DeliveryRunStatus::Completed)
+            .await
+            .is_err());
+
+        for status in [
+            model::FulfillmentStatus::InProgress,
+            model::FulfillmentStatus::Fulfilled,
+        ] {
+            provider
+                .set_fulfillment_status(&fulfillment_id, &status.into())
+                .await
+                .unwrap();
+        }
+        provider
+            .set_delivery_run_status(run_id, model::DeliveryRunStatus::Completed)
+            .await
+            .unwrap();
+
+        let run = provider.get_delivery_run(run_id).await.unwrap().unwrap();
+        assert_eq!(run.data.status, model::DeliveryRunStatus::Completed);
+        assert!(run.data.dispatched_at.is_some() && run.data.completed_at.is_some());
+    }
+}
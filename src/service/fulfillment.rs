@@ -1,34 +1,310 @@
 use std::fmt::Display;
 
-use sqlx::query;
+use axum::http::StatusCode;
+use chrono::Utc;
+use log::warn;
+use sqlx::{query, sqlite::SqliteRow, Row, SqliteConnection};
 
-use crate::{model, provider::SqliteProvider};
+use crate::{
+    model::{self, ToRecord},
+    provider::SqliteProvider,
+    service::{
+        customer::CustomerService,
+        inventory::{self, InventoryService},
+        line_item::LineItemService,
+        order::OrderService,
+        pickup::PickupService,
+        product::ProductService,
+    },
+};
 
 pub trait FulfillmentService {
-    type Error: Display;
+    type Error: Display + Into<StatusCode>;
 
     async fn init_provider(&mut self) -> Result<(), Self::Error>;
 
+    /// Creates a fulfillment, optionally for an order.
+    ///
+    /// Delivery and drop-ship fulfillments snapshot their ship-to address, falling back
+    /// to the order customer's default address when `ship_to_address_id` isn't given.
+    /// Transfers need both locations, stock fulfillments ship from the default location
+    /// unless told otherwise.
     async fn create_fulfillment(
         &mut self,
-        fulfillment_type: model::FulfillmentType,
+        fulfillment: &model::NewFulfillment,
     ) -> Result<i64, Self::Error>;
 
+    async fn get_fulfillment(
+        &mut self,
+        fulfillment_id: &i64,
+    ) -> Result<Option<model::Record<model::FulfillmentDetails>>, Self::Error>;
+
+    /// Moves a fulfillment along the workflow for its type, checking the transition's
+    /// guards.
+    ///
+    /// Initializing a stock fulfillment or a transfer reserves stock for its lines at
+    /// the location it takes stock from, anything that can't be reserved is moved to a
+    /// backorder fulfillment. Fulfilling it issues the picked stock, fulfilling a
+    /// transfer moves it, either failing if the location doesn't hold what was picked.
+    /// Fulfilling a drop-ship marks every line as fulfilled. A pick-up can't be fulfilled before its booked slot opens
+    /// unless the change overrides it.
     async fn set_fulfillment_status(
         &mut self,
         fulfillment_id: &i64,
-        fulfillment_status: model::FulfillmentStatus,
+        change: &model::FulfillmentStatusChange,
+    ) -> Result<(), Self::Error>;
+
+    async fn set_supplier_reference(
+        &mut self,
+        fulfillment_id: &i64,
+        supplier_reference: &str,
+    ) -> Result<(), Self::Error>;
+
+    /// Workflows in use for each fulfillment type
+    async fn get_workflows(&mut self) -> Result<model::Workflows, Self::Error>;
+
+    /// Label contents for a stock delivery, rendered by the caller
+    async fn get_shipping_label(
+        &mut self,
+        fulfillment_id: &i64,
+    ) -> Result<model::ShippingLabel, Self::Error>;
+
+    /// Assigns a fulfillment to a picker or driver, `None` unassigns it. Fulfilled
+    /// fulfillments can't be reassigned.
+    async fn assign_fulfillment(
+        &mut self,
+        fulfillment_id: &i64,
+        assignee: Option<&str>,
     ) -> Result<(), Self::Error>;
+
+    /// Fulfillments that haven't been fulfilled yet, only those assigned to `assignee`
+    /// when given
+    async fn get_work_queue(
+        &mut self,
+        assignee: Option<&str>,
+    ) -> Result<Vec<model::Record<model::FulfillmentDetails>>, Self::Error>;
+
+    /// Picks a scanned item on an in progress fulfillment and returns what's left to pick.
+    ///
+    /// The code must resolve to a product on the fulfillment, and the scan can't pick
+    /// more of it than the fulfillment asks for.
+    async fn scan_item(
+        &mut self,
+        fulfillment_id: &i64,
+        scan: &model::Scan,
+    ) -> Result<model::ScanResult, Self::Error>;
+
+    /// Every status transition the fulfillment has taken, oldest first
+    async fn get_fulfillment_status_history(
+        &mut self,
+        fulfillment_id: &i64,
+    ) -> Result<Vec<model::FulfillmentStatusEvent>, Self::Error>;
+
+    /// Packing slip contents for any fulfillment, rendered by the caller
+    async fn get_packing_slip(
+        &mut self,
+        fulfillment_id: &i64,
+    ) -> Result<model::PackingSlip, Self::Error>;
 }
 
 pub const CREATE_FULFILLMENT_TABLE_SQL: &str = r#"
     CREATE TABLE IF NOT EXISTS fulfillments (
         id INTEGER NOT NULL UNIQUE PRIMARY KEY,
         fulfillmentStatus TEXT NOT NULL,
-        fulfillmentType TEXT NOT NULL
+        fulfillmentType TEXT NOT NULL,
+        orderId INTEGER,
+        shipToRecipient TEXT,
+        shipToLine1 TEXT,
+        shipToLine2 TEXT,
+        shipToCity TEXT,
+        shipToRegion TEXT,
+        shipToPostalCode TEXT,
+        shipToCountry TEXT,
+        shipToLatitude REAL,
+        shipToLongitude REAL,
+        supplierReference TEXT,
+        fromLocationId INTEGER,
+        toLocationId INTEGER,
+        pickupSlotId INTEGER,
+        assignee TEXT
+    );
+"#;
+
+pub const CREATE_FULFILLMENT_STATUS_CHANGE_TABLE_SQL: &str = r#"
+    CREATE TABLE IF NOT EXISTS fulfillmentStatusChanges (
+        id INTEGER NOT NULL UNIQUE PRIMARY KEY,
+        fulfillmentId INTEGER NOT NULL,
+        fromStatus TEXT NOT NULL,
+        toStatus TEXT NOT NULL,
+        assignee TEXT,
+        changedAt TEXT NOT NULL
     );
 "#;
 
+/// Moves a fulfillment between statuses, failing if it has moved on since it was read.
+/// The transition is recorded along with who the fulfillment is assigned to.
+pub async fn update_fulfillment_status(
+    conn: &mut SqliteConnection,
+    fulfillment_id: i64,
+    from: &model::FulfillmentStatus,
+    to: &model::FulfillmentStatus,
+) -> Result<(), super::Error> {
+    let result = query(
+        r#"
+        UPDATE fulfillments
+        SET fulfillmentStatus = ?1
+        WHERE id = ?2
+        AND fulfillmentStatus = ?3;
+    "#,
+    )
+    .bind(String::from(to.clone()))
+    .bind(fulfillment_id)
+    .bind(String::from(from.clone()))
+    .execute(&mut *conn)
+    .await?;
+
+    match result.rows_affected() {
+        1 => {
+            query(
+                r#"
+                INSERT INTO fulfillmentStatusChanges (
+                    fulfillmentId, fromStatus, toStatus, assignee, changedAt
+                )
+                SELECT id, ?1, fulfillmentStatus, assignee, ?2 FROM fulfillments WHERE id = ?3;
+            "#,
+            )
+            .bind(String::from(from.clone()))
+            .bind(Utc::now())
+            .bind(fulfillment_id)
+            .execute(&mut *conn)
+            .await?;
+            Ok(())
+        }
+        0 => Err(super::Error::BadInput(format!(
+            "fulfillment {} status changed concurrently",
+            fulfillment_id
+        ))),
+        n => Err(super::Error::ProviderFailure(format!(
+            "{} rows affected, expected 1 or 0",
+            n
+        ))),
+    }
+}
+
+/// Moves a fulfillment to a checked status along with the stock it reserves or moves,
+/// inside the caller's transaction
+pub async fn apply_fulfillment_status(
+    conn: &mut SqliteConnection,
+    fulfillment: &model::Record<model::FulfillmentDetails>,
+    fulfillment_status: &model::FulfillmentStatus,
+) -> Result<(), super::Error> {
+    let fulfillment_type = &fulfillment.data.fulfillment_type;
+    update_fulfillment_status(
+        conn,
+        fulfillment.id,
+        &fulfillment.data.status,
+        fulfillment_status,
+    )
+    .await?;
+
+    let from_location_id = fulfillment
+        .data
+        .from_location_id
+        .unwrap_or(model::DEFAULT_LOCATION_ID);
+    match (fulfillment_status, fulfillment_type) {
+        // transfers hold their stock too, so two can't promise the same units
+        (model::FulfillmentStatus::Initialized, t) if t.is_picked() => {
+            inventory::reserve_fulfillment_stock(conn, fulfillment.id, from_location_id).await?;
+        }
+        (model::FulfillmentStatus::Fulfilled, t) if t.uses_stock() => {
+            inventory::issue_fulfillment_stock(conn, fulfillment.id, from_location_id).await?;
+        }
+        (model::FulfillmentStatus::Fulfilled, model::FulfillmentType::Transfer) => {
+            let Some(to_location_id) = fulfillment.data.to_location_id else {
+                return Err(super::Error::ProviderFailure(format!(
+                    "transfer {} has no destination",
+                    fulfillment.id
+                )));
+            };
+            inventory::transfer_fulfillment_stock(
+                conn,
+                fulfillment.id,
+                from_location_id,
+                to_location_id,
+            )
+            .await?;
+        }
+        (model::FulfillmentStatus::Fulfilled, model::FulfillmentType::DropShip) => {
+            query(
+                r#"
+                UPDATE lineItems SET quantityFulfilled = quantity
+                WHERE fulfillmentId = ?1;
+            "#,
+            )
+            .bind(fulfillment.id)
+            .execute(&mut *conn)
+            .await?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Copies a fulfillment into a new one in `New` status to hold its backordered lines
+pub async fn create_backorder_fulfillment(
+    conn: &mut SqliteConnection,
+    fulfillment_id: i64,
+) -> Result<i64, super::Error> {
+    let result = query(
+        r#"
+        INSERT INTO fulfillments (
+            fulfillmentStatus, fulfillmentType, orderId, shipToRecipient, shipToLine1,
+            shipToLine2, shipToCity, shipToRegion, shipToPostalCode, shipToCountry,
+            shipToLatitude, shipToLongitude, fromLocationId, toLocationId
+        )
+        SELECT
+            ?1, fulfillmentType, orderId, shipToRecipient, shipToLine1,
+            shipToLine2, shipToCity, shipToRegion, shipToPostalCode, shipToCountry,
+            shipToLatitude, shipToLongitude, fromLocationId, toLocationId
+        FROM fulfillments WHERE id = ?2;
+    "#,
+    )
+    .bind(String::from(model::FulfillmentStatus::New))
+    .bind(fulfillment_id)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(result.last_insert_rowid())
+}
+
+/// Reads a fulfillment selected with its ship-to columns named as on addresses
+fn fulfillment_from_row(
+    row: &SqliteRow,
+) -> Result<model::Record<model::FulfillmentDetails>, super::Error> {
+    let status: String = row.try_get("fulfillmentStatus")?;
+    let fulfillment_type: String = row.try_get("fulfillmentType")?;
+    let recipient: Option<String> = row.try_get("recipient")?;
+    let ship_to = match recipient {
+        Some(_) => Some(super::customer::address_from_row(row)?),
+        None => None,
+    };
+
+    Ok(model::FulfillmentDetails {
+        fulfillment_type: fulfillment_type
+            .parse()
+            .map_err(super::Error::ProviderFailure)?,
+        status: status.parse().map_err(super::Error::ProviderFailure)?,
+        order_id: row.try_get("orderId")?,
+        ship_to,
+        supplier_reference: row.try_get("supplierReference")?,
+        from_location_id: row.try_get("fromLocationId")?,
+        to_location_id: row.try_get("toLocationId")?,
+        pickup_slot_id: row.try_get("pickupSlotId")?,
+        assignee: row.try_get("assignee")?,
+    }
+    .to_record(row.try_get("id")?))
+}
+
 impl FulfillmentService for SqliteProvider {
     type Error = super::Error;
 
@@ -37,68 +313,1324 @@ impl FulfillmentService for SqliteProvider {
         let _result = sqlx::query(CREATE_FULFILLMENT_TABLE_SQL)
             .execute(&mut *conn)
             .await?;
+        sqlx::query(CREATE_FULFILLMENT_STATUS_CHANGE_TABLE_SQL)
+            .execute(&mut *conn)
+            .await?;
         Ok(())
     }
 
     async fn create_fulfillment(
         &mut self,
-        fulfillment_type: model::FulfillmentType,
+        fulfillment: &model::NewFulfillment,
     ) -> Result<i64, Self::Error> {
+        let fulfillment_type = &fulfillment.fulfillment_type;
+        let order_id = fulfillment.order_id;
+
+        let customer_id = match order_id {
+            Some(order_id) => match self.get_order(order_id).await? {
+                Some(order) => order.data.customer_id,
+                None => return Err(super::Error::NotFound(format!("order {}", order_id))),
+            },
+            None => None,
+        };
+
+        let ships_to_customer = fulfillment_type.ships_to_customer();
+        let ship_to = match (ships_to_customer, fulfillment.ship_to_address_id) {
+            (true, Some(address_id)) => {
+                let Some(address) = self.get_address(address_id).await? else {
+                    return Err(super::Error::NotFound(format!("address {}", address_id)));
+                };
+                if customer_id.is_some_and(|id| id != address.data.customer_id) {
+                    return Err(super::Error::BadInput(format!(
+                        "address {} doesn't belong to the order's customer",
+                        address_id
+                    )));
+                }
+                Some(address.data.address)
+            }
+            (true, None) => {
+                let default_address = match customer_id {
+                    Some(customer_id) => self.get_default_address(customer_id).await?,
+                    None => None,
+                };
+                let Some(address) = default_address else {
+                    return Err(super::Error::BadInput(
+                        "delivery and drop-ship fulfillments need a ship-to address".to_string(),
+                    ));
+                };
+                Some(address.data.address)
+            }
+            (false, Some(_)) => {
+                return Err(super::Error::BadInput(
+                    "only delivery and drop-ship fulfillments have a ship-to address".to_string(),
+                ))
+            }
+            (false, None) => None,
+        };
+
+        if fulfillment.supplier_reference.is_some()
+            && *fulfillment_type != model::FulfillmentType::DropShip
+        {
+            return Err(super::Error::BadInput(
+                "only drop-ship fulfillments have a supplier reference".to_string(),
+            ));
+        }
+
+        let (from_location_id, to_location_id) = match fulfillment_type {
+            model::FulfillmentType::Transfer => {
+                let (Some(from), Some(to)) =
+                    (fulfillment.from_location_id, fulfillment.to_location_id)
+                else {
+                    return Err(super::Error::BadInput(
+                        "transfers need a from and to location".to_string(),
+                    ));
+                };
+                if from == to {
+                    return Err(super::Error::BadInput(
+                        "can't transfer to the same location".to_string(),
+                    ));
+                }
+                (Some(from), Some(to))
+            }
+            t if t.uses_stock() && fulfillment.to_location_id.is_none() => (
+                Some(
+                    fulfillment
+                        .from_location_id
+                        .unwrap_or(model::DEFAULT_LOCATION_ID),
+                ),
+                None,
+            ),
+            _ if fulfillment.from_location_id.is_none() && fulfillment.to_location_id.is_none() => {
+                (None, None)
+            }
+            _ => {
+                return Err(super::Error::BadInput(
+                    "locations don't apply to this fulfillment type".to_string(),
+                ))
+            }
+        };
+
+        let given = [fulfillment.from_location_id, fulfillment.to_location_id];
+        for location_id in given.into_iter().flatten() {
+            if self.get_location(location_id).await?.is_none() {
+                return Err(super::Error::NotFound(format!("location {}", location_id)));
+            }
+        }
+
         let mut conn = self.connection.acquire().await?;
 
         let result = query(
             r#"
-            INSERT INTO fulfillments VALUES( NULL, ?1, ?2 );
+            INSERT INTO fulfillments (
+                fulfillmentStatus, fulfillmentType, orderId, shipToRecipient, shipToLine1,
+                shipToLine2, shipToCity, shipToRegion, shipToPostalCode, shipToCountry,
+                shipToLatitude, shipToLongitude, supplierReference, fromLocationId, toLocationId
+            )
+            VALUES( ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15 );
         "#,
         )
         .bind(String::from(model::FulfillmentStatus::New))
-        .bind(String::from(fulfillment_type))
+        .bind(String::from(fulfillment_type.clone()))
+        .bind(order_id)
+        .bind(ship_to.as_ref().map(|a| a.recipient.clone()))
+        .bind(ship_to.as_ref().map(|a| a.line1.clone()))
+        .bind(ship_to.as_ref().and_then(|a| a.line2.clone()))
+        .bind(ship_to.as_ref().map(|a| a.city.clone()))
+        .bind(ship_to.as_ref().and_then(|a| a.region.clone()))
+        .bind(ship_to.as_ref().map(|a| a.postal_code.clone()))
+        .bind(ship_to.as_ref().map(|a| a.country.clone()))
+        .bind(ship_to.as_ref().and_then(|a| a.latitude))
+        .bind(ship_to.as_ref().and_then(|a| a.longitude))
+        .bind(&fulfillment.supplier_reference)
+        .bind(from_location_id)
+        .bind(to_location_id)
         .execute(&mut *conn)
         .await?;
 
         Ok(result.last_insert_rowid())
     }
 
+    async fn get_fulfillment(
+        &mut self,
+        fulfillment_id: &i64,
+    ) -> Result<Option<model::Record<model::FulfillmentDetails>>, Self::Error> {
+        let mut conn = self.connection.acquire().await?;
+
+        let result = query(
+            r#"
+            SELECT
+                id, fulfillmentStatus, fulfillmentType, orderId,
+                shipToRecipient AS recipient, shipToLine1 AS line1, shipToLine2 AS line2,
+                shipToCity AS city, shipToRegion AS region, shipToPostalCode AS postalCode,
+                shipToCountry AS country, shipToLatitude AS latitude,
+                shipToLongitude AS longitude, supplierReference, fromLocationId, toLocationId,
+                pickupSlotId, assignee
+            FROM fulfillments WHERE id = ?1;
+        "#,
+        )
+        .bind(fulfillment_id.to_owned())
+        .fetch_optional(&mut *conn)
+        .await?;
+
+        let Some(row) = result else {
+            warn!("Sql row not found");
+            return Ok(None);
+        };
+
+        Ok(Some(fulfillment_from_row(&row)?))
+    }
+
     async fn set_fulfillment_status(
         &mut self,
         fulfillment_id: &i64,
-        fulfillment_status: model::FulfillmentStatus,
+        change: &model::FulfillmentStatusChange,
+    ) -> Result<(), Self::Error> {
+        let fulfillment_status = change.fulfillment_status.clone();
+        let Some(fulfillment) = self.get_fulfillment(fulfillment_id).await? else {
+            return Err(super::Error::NotFound(format!(
+                "fulfillment {}",
+                fulfillment_id
+            )));
+        };
+        self.check_status_change(&fulfillment, change).await?;
+
+        let mut tx = self.connection.begin().await?;
+        apply_fulfillment_status(&mut tx, &fulfillment, &fulfillment_status).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn set_supplier_reference(
+        &mut self,
+        fulfillment_id: &i64,
+        supplier_reference: &str,
+    ) -> Result<(), Self::Error> {
+        let mut conn = self.connection.acquire().await?;
+        let result = query(
+            r#"
+            UPDATE fulfillments SET supplierReference = ?1
+            WHERE id = ?2 AND fulfillmentType = 'DropShip';
+        "#,
+        )
+        .bind(supplier_reference)
+        .bind(fulfillment_id.to_owned())
+        .execute(&mut *conn)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(super::Error::BadInput(format!(
+                "fulfillment {} isn't a drop-ship",
+                fulfillment_id
+            )));
+        }
+
+        Ok(())
+    }
+
+    async fn get_workflows(&mut self) -> Result<model::Workflows, Self::Error> {
+        Ok(self.workflows.as_ref().clone())
+    }
+
+    async fn assign_fulfillment(
+        &mut self,
+        fulfillment_id: &i64,
+        assignee: Option<&str>,
     ) -> Result<(), Self::Error> {
+        let Some(fulfillment) = self.get_fulfillment(fulfillment_id).await? else {
+            return Err(super::Error::NotFound(format!(
+                "fulfillment {}",
+                fulfillment_id
+            )));
+        };
+        if fulfillment.data.status == model::FulfillmentStatus::Fulfilled {
+            return Err(super::Error::BadInput(format!(
+                "fulfillment {} has already been fulfilled",
+                fulfillment_id
+            )));
+        }
+
+        let mut conn = self.connection.acquire().await?;
+        let result = query(
+            r#"
+            UPDATE fulfillments SET assignee = ?1
+            WHERE id = ?2 AND fulfillmentStatus != 'Fulfilled';
+        "#,
+        )
+        .bind(assignee)
+        .bind(fulfillment_id.to_owned())
+        .execute(&mut *conn)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(super::Error::BadInput(format!(
+                "fulfillment {} was fulfilled while assigning",
+                fulfillment_id
+            )));
+        }
+
+        Ok(())
+    }
+
+    async fn get_work_queue(
+        &mut self,
+        assignee: Option<&str>,
+    ) -> Result<Vec<model::Record<model::FulfillmentDetails>>, Self::Error> {
         let mut conn = self.connection.acquire().await?;
-        let allowed_priors_strings = fulfillment_status
-            .allowed_priors()
+        let rows = query(
+            r#"
+            SELECT
+                id, fulfillmentStatus, fulfillmentType, orderId,
+                shipToRecipient AS recipient, shipToLine1 AS line1, shipToLine2 AS line2,
+                shipToCity AS city, shipToRegion AS region, shipToPostalCode AS postalCode,
+                shipToCountry AS country, shipToLatitude AS latitude,
+                shipToLongitude AS longitude, supplierReference, fromLocationId, toLocationId,
+                pickupSlotId, assignee
+            FROM fulfillments
+            WHERE fulfillmentStatus != 'Fulfilled' AND (?1 IS NULL OR assignee = ?1)
+            ORDER BY id;
+        "#,
+        )
+        .bind(assignee)
+        .fetch_all(&mut *conn)
+        .await?;
+
+        let mut fulfillments = Vec::new();
+        for row in rows {
+            fulfillments.push(fulfillment_from_row(&row)?);
+        }
+        Ok(fulfillments)
+    }
+
+    async fn scan_item(
+        &mut self,
+        fulfillment_id: &i64,
+        scan: &model::Scan,
+    ) -> Result<model::ScanResult, Self::Error> {
+        if scan.quantity <= 0 {
+            return Err(super::Error::BadInput(
+                "scanned quantity must be positive".to_string(),
+            ));
+        }
+        let Some(fulfillment) = self.get_fulfillment(fulfillment_id).await? else {
+            return Err(super::Error::NotFound(format!(
+                "fulfillment {}",
+                fulfillment_id
+            )));
+        };
+        if fulfillment.data.status != model::FulfillmentStatus::InProgress {
+            return Err(super::Error::BadInput(format!(
+                "fulfillment {} isn't being picked",
+                fulfillment_id
+            )));
+        }
+        let Some(product) = self.find_product_by_code(&scan.code).await? else {
+            return Err(super::Error::ProductNotFound(scan.code.clone()));
+        };
+
+        let lines: Vec<model::Record<model::LineItemDetails>> = self
+            .get_line_items_by_fulfillment_id(*fulfillment_id)
+            .await?
+            .into_iter()
+            .filter(|l| l.data.product_id == product.id)
+            .collect();
+        if lines.is_empty() {
+            return Err(super::Error::BadInput(format!(
+                "{} isn't on fulfillment {}",
+                product.data.sku, fulfillment_id
+            )));
+        }
+        let Some(line) = lines
             .iter()
-            .map(|s| format!("'{}'", String::from(s.clone())))
-            .collect::<Vec<String>>()
-            .join(", ");
+            .find(|l| l.data.quantity - l.data.quantity_fulfilled >= scan.quantity)
+        else {
+            return Err(super::Error::BadInput(format!(
+                "scanning {} more {} would over-pick fulfillment {}",
+                scan.quantity, product.data.sku, fulfillment_id
+            )));
+        };
+
+        let mut conn = self.connection.acquire().await?;
+        let result = query(
+            r#"
+            UPDATE lineItems SET quantityFulfilled = quantityFulfilled + ?1
+            WHERE id = ?2 AND quantityFulfilled + ?1 <= quantity
+            AND EXISTS (
+                SELECT 1
+                FROM fulfillments
+                WHERE id = lineItems.fulfillmentId AND fulfillmentStatus = 'InProgress'
+            );
+        "#,
+        )
+        .bind(scan.quantity)
+        .bind(line.id)
+        .execute(&mut *conn)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(super::Error::BadInput(format!(
+                "fulfillment {} changed while scanning",
+                fulfillment_id
+            )));
+        }
+        drop(conn);
 
-        println!("{}", allowed_priors_strings);
+        let mut remaining = Vec::new();
+        for line_item in self
+            .get_line_items_by_fulfillment_id(*fulfillment_id)
+            .await?
+        {
+            let quantity = line_item.data.quantity - line_item.data.quantity_fulfilled;
+            if quantity <= 0 {
+                continue;
+            }
+            let line_product = self.get_product(&line_item.data.product_id).await?;
+            remaining.push(model::RemainingPick {
+                line_item_id: line_item.id,
+                product_id: line_item.data.product_id,
+                sku: line_product.data.sku,
+                description: line_product.data.description,
+                quantity,
+            });
+        }
 
-        let query_str = format!(
+        Ok(model::ScanResult {
+            fulfillment_id: *fulfillment_id,
+            line_item_id: line.id,
+            product_id: product.id,
+            quantity_fulfilled: line.data.quantity_fulfilled + scan.quantity,
+            remaining,
+        })
+    }
+
+    async fn get_fulfillment_status_history(
+        &mut self,
+        fulfillment_id: &i64,
+    ) -> Result<Vec<model::FulfillmentStatusEvent>, Self::Error> {
+        let mut conn = self.connection.acquire().await?;
+        let rows = query(
             r#"
-                UPDATE fulfillments
-                SET fulfillmentStatus = $1
-                WHERE id = $2
-                AND fulfillmentStatus IN ( {} );
-            "#,
-            allowed_priors_strings,
-        );
+            SELECT * FROM fulfillmentStatusChanges WHERE fulfillmentId = ?1 ORDER BY id;
+        "#,
+        )
+        .bind(fulfillment_id.to_owned())
+        .fetch_all(&mut *conn)
+        .await?;
 
-        let result = query(&query_str)
-            .bind(String::from(fulfillment_status))
-            .bind(fulfillment_id.to_owned())
-            .execute(&mut *conn)
+        let mut events = Vec::new();
+        for row in rows {
+            let from_status: String = row.try_get("fromStatus")?;
+            let to_status: String = row.try_get("toStatus")?;
+            events.push(model::FulfillmentStatusEvent {
+                from_status: from_status.parse().map_err(super::Error::ProviderFailure)?,
+                to_status: to_status.parse().map_err(super::Error::ProviderFailure)?,
+                assignee: row.try_get("assignee")?,
+                changed_at: row.try_get("changedAt")?,
+            });
+        }
+        Ok(events)
+    }
+
+    async fn get_shipping_label(
+        &mut self,
+        fulfillment_id: &i64,
+    ) -> Result<model::ShippingLabel, Self::Error> {
+        let Some(fulfillment) = self.get_fulfillment(fulfillment_id).await? else {
+            return Err(super::Error::NotFound(format!(
+                "fulfillment {}",
+                fulfillment_id
+            )));
+        };
+
+        if fulfillment.data.fulfillment_type != model::FulfillmentType::StockDelivery {
+            return Err(super::Error::BadInput(format!(
+                "only stock deliveries get a shipping label, fulfillment {} is {:?}",
+                fulfillment_id, fulfillment.data.fulfillment_type
+            )));
+        }
+        let Some(ship_to) = fulfillment.data.ship_to else {
+            return Err(super::Error::ProviderFailure(format!(
+                "delivery {} has no ship-to address",
+                fulfillment_id
+            )));
+        };
+
+        let ship_from = match fulfillment.data.from_location_id {
+            Some(location_id) => self.get_location(location_id).await?.map(|l| l.data.name),
+            None => None,
+        };
+
+        let mut lines = Vec::new();
+        for line_item in self
+            .get_line_items_by_fulfillment_id(*fulfillment_id)
+            .await?
+        {
+            let product = self.get_product(&line_item.data.product_id).await?;
+            lines.push(model::LabelLine {
+                sku: product.data.sku,
+                description: product.data.description,
+                quantity: line_item.data.quantity,
+            });
+        }
+
+        Ok(model::ShippingLabel {
+            fulfillment_id: *fulfillment_id,
+            order_id: fulfillment.data.order_id,
+            ship_from,
+            ship_to,
+            lines,
+        })
+    }
+
+    async fn get_packing_slip(
+        &mut self,
+        fulfillment_id: &i64,
+    ) -> Result<model::PackingSlip, Self::Error> {
+        let Some(fulfillment) = self.get_fulfillment(fulfillment_id).await? else {
+            return Err(super::Error::NotFound(format!(
+                "fulfillment {}",
+                fulfillment_id
+            )));
+        };
+
+        let customer_id = match fulfillment.data.order_id {
+            Some(order_id) => self
+                .get_order(order_id)
+                .await?
+                .and_then(|order| order.data.customer_id),
+            None => None,
+        };
+        let customer = match customer_id {
+            Some(customer_id) => self.get_customer(customer_id).await?.map(|c| c.data),
+            None => None,
+        };
+
+        let mut lines = Vec::new();
+        for line_item in self
+            .get_line_items_by_fulfillment_id(*fulfillment_id)
+            .await?
+        {
+            let product = self.get_product(&line_item.data.product_id).await?;
+            lines.push(model::PackingSlipLine {
+                line_item_id: line_item.id,
+                product_id: line_item.data.product_id,
+                sku: product.data.sku,
+                description: product.data.description,
+                quantity: line_item.data.quantity,
+                quantity_fulfilled: line_item.data.quantity_fulfilled,
+            });
+        }
+
+        Ok(model::PackingSlip {
+            fulfillment_id: *fulfillment_id,
+            fulfillment_type: fulfillment.data.fulfillment_type,
+            status: fulfillment.data.status,
+            order_id: fulfillment.data.order_id,
+            customer,
+            ship_to: fulfillment.data.ship_to,
+            lines,
+        })
+    }
+}
+
+impl SqliteProvider {
+    /// Checks the fulfillment's workflow has a step to `to` and that its guards hold
+    pub async fn check_transition(
+        &mut self,
+        fulfillment: &model::Record<model::FulfillmentDetails>,
+        to: &model::FulfillmentStatus,
+    ) -> Result<(), super::Error> {
+        let fulfillment_type = &fulfillment.data.fulfillment_type;
+        let Some(workflow) = self.workflows.get(fulfillment_type) else {
+            return Err(super::Error::ProviderFailure(format!(
+                "no workflow for {:?} fulfillments",
+                fulfillment_type
+            )));
+        };
+        let Some(transition) = workflow.transition(&fulfillment.data.status, to) else {
+            return Err(super::Error::BadInput(format!(
+                "bad fulfillment status transition for fulfillment {}",
+                fulfillment.id
+            )));
+        };
+        let guards = transition.guards.clone();
+
+        if !guards.is_empty() {
+            let lines: Vec<model::LineItemDetails> = self
+                .get_line_items_by_fulfillment_id(fulfillment.id)
+                .await?
+                .into_iter()
+                .map(|line| line.data)
+                .collect();
+            for guard in guards {
+                guard.check(&lines).map_err(super::Error::BadInput)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Everything `set_fulfillment_status` checks before it moves a fulfillment
+    pub async fn check_status_change(
+        &mut self,
+        fulfillment: &model::Record<model::FulfillmentDetails>,
+        change: &model::FulfillmentStatusChange,
+    ) -> Result<(), super::Error> {
+        self.check_transition(fulfillment, &change.fulfillment_status)
             .await?;
 
-        match result.rows_affected() {
-            1 => Ok(()),
-            0 => Err(super::Error::BadInput(
-                "bad fulfillment status transition".to_string(),
-            )),
-            n => Err(super::Error::ProviderFailure(format!(
-                "{} rows affected, expected 1 or 0",
-                n
-            ))),
+        if let (model::FulfillmentStatus::Fulfilled, Some(pickup_slot_id), false) = (
+            &change.fulfillment_status,
+            fulfillment.data.pickup_slot_id,
+            change.override_pickup_slot,
+        ) {
+            if let Some(slot) = self.get_pickup_slot(pickup_slot_id).await? {
+                if slot.data.starts_at > Utc::now() {
+                    return Err(super::Error::BadInput(format!(
+                        "pickup for fulfillment {} isn't until {}",
+                        fulfillment.id, slot.data.starts_at
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        model,
+        provider::SqliteProvider,
+        render,
+        service::{
+            self, customer::CustomerService, fulfillment::FulfillmentService,
+            inventory::InventoryService, line_item::LineItemService, product::ProductService,
+        },
+    };
+
+    async fn setup() -> SqliteProvider {
+        let mut provider = SqliteProvider::new_memory().await.unwrap();
+        FulfillmentService::init_provider(&mut provider)
+            .await
+            .unwrap();
+        LineItemService::init_provider(&mut provider).await.unwrap();
+        ProductService::init_provider(&mut provider).await.unwrap();
+        InventoryService::init_provider(&mut provider)
+            .await
+            .unwrap();
+        provider
+    }
+
+    async fn advance(
+        provider: &mut SqliteProvider,
+        fulfillment_id: i64,
+        statuses: &[model::FulfillmentStatus],
+    ) {
+        for status in statuses {
+            provider
+                .set_fulfillment_status(&fulfillment_id, &status.clone().into())
+                .await
+                .unwrap();
         }
     }
+
+    #[tokio::test]
+    async fn test_drop_ship_flow() {
+        let mut provider = setup().await;
+        // supplier references only make sense on drop-ships
+        assert!(provider
+            .create_fulfillment(&model::NewFulfillment {
+                supplier_reference: Some("PO-1".to_string()),
+                ..model::NewFulfillment::from(model::FulfillmentType::Service)
+            })
+            .await
+            .is_err());
+
+        let mut drop_ship = model::NewFulfillment::from(model::FulfillmentType::DropShip);
+        // ships to the customer, so it needs somewhere to go
+        assert!(provider.create_fulfillment(&drop_ship).await.is_err());
+
+        drop_ship.supplier_reference = Some("PO-1".to_string());
+        drop_ship.ship_to_address_id = Some(1);
+        assert!(provider.create_fulfillment(&drop_ship).await.is_err());
+
+        // skip the address lookup by inserting a drop-ship directly
+        let mut conn = provider.connection.acquire().await.unwrap();
+        sqlx::query(
+            r#"INSERT INTO fulfillments (fulfillmentStatus, fulfillmentType) VALUES ( ?1, ?2 );"#,
+        )
+        .bind(String::from(model::FulfillmentStatus::New))
+        .bind(String::from(model::FulfillmentType::DropShip))
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+        drop(conn);
+
+        provider.create_line_item(1, 1, 3, None).await.unwrap();
+        provider.set_supplier_reference(&1, "SUP-9").await.unwrap();
+
+        advance(&mut provider, 1, &[model::FulfillmentStatus::Initialized]).await;
+        // no picking for drop-ships
+        assert!(provider
+            .set_fulfillment_status(&1, &model::FulfillmentStatus::InProgress.into())
+            .await
+            .is_err());
+        advance(&mut provider, 1, &[model::FulfillmentStatus::Fulfilled]).await;
+
+        let fulfillment = provider.get_fulfillment(&1).await.unwrap().unwrap();
+        assert_eq!(
+            fulfillment.data.supplier_reference.as_deref(),
+            Some("SUP-9")
+        );
+        let lines = provider.get_line_items_by_fulfillment_id(1).await.unwrap();
+        assert_eq!(lines[0].data.quantity_fulfilled, 3);
+        assert_eq!(lines[0].data.quantity_reserved, 0);
+        assert_eq!(provider.get_stock_level(1).await.unwrap().on_hand, 0);
+    }
+
+    #[tokio::test]
+    async fn test_service_flow() {
+        let mut provider = setup().await;
+        let fulfillment_id = provider
+            .create_fulfillment(&model::NewFulfillment::from(
+                model::FulfillmentType::Service,
+            ))
+            .await
+            .unwrap();
+        provider
+            .create_line_item(fulfillment_id, 1, 1, None)
+            .await
+            .unwrap();
+
+        assert!(provider
+            .set_fulfillment_status(
+                &fulfillment_id,
+                &model::FulfillmentStatus::Initialized.into()
+            )
+            .await
+            .is_err());
+        advance(
+            &mut provider,
+            fulfillment_id,
+            &[
+                model::FulfillmentStatus::InProgress,
+                model::FulfillmentStatus::Fulfilled,
+            ],
+        )
+        .await;
+
+        assert!(provider.get_stock_movements(1).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_assignment() {
+        let mut provider = setup().await;
+        let mut fulfillment_ids = Vec::new();
+        for _ in 0..2 {
+            fulfillment_ids.push(
+                provider
+                    .create_fulfillment(&model::NewFulfillment::from(
+                        model::FulfillmentType::Service,
+                    ))
+                    .await
+                    .unwrap(),
+            );
+        }
+        let fulfillment_id = fulfillment_ids[0];
+
+        provider
+            .assign_fulfillment(&fulfillment_id, Some("ada"))
+            .await
+            .unwrap();
+        advance(
+            &mut provider,
+            fulfillment_id,
+            &[model::FulfillmentStatus::InProgress],
+        )
+        .await;
+        provider
+            .assign_fulfillment(&fulfillment_id, Some("grace"))
+            .await
+            .unwrap();
+
+        let queue = provider.get_work_queue(Some("grace")).await.unwrap();
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue[0].id, fulfillment_id);
+        assert_eq!(provider.get_work_queue(None).await.unwrap().len(), 2);
+
+        assert!(matches!(
+            provider.assign_fulfillment(&42, Some("ada")).await,
+            Err(service::Error::NotFound(_))
+        ));
+        // unassigned work only shows in the shared queue
+        provider
+            .assign_fulfillment(&fulfillment_ids[1], Some("ada"))
+            .await
+            .unwrap();
+        provider
+            .assign_fulfillment(&fulfillment_ids[1], None)
+            .await
+            .unwrap();
+        assert!(provider
+            .get_work_queue(Some("ada"))
+            .await
+            .unwrap()
+            .is_empty());
+
+        advance(
+            &mut provider,
+            fulfillment_id,
+            &[model::FulfillmentStatus::Fulfilled],
+        )
+        .await;
+        // fulfilled work leaves the queue and stays with whoever did it
+        assert!(provider
+            .get_work_queue(Some("grace"))
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(provider
+            .assign_fulfillment(&fulfillment_id, None)
+            .await
+            .is_err());
+
+        let history = provider
+            .get_fulfillment_status_history(&fulfillment_id)
+            .await
+            .unwrap();
+        let assignees: Vec<Option<&str>> = history.iter().map(|e| e.assignee.as_deref()).collect();
+        assert_eq!(assignees, vec![Some("ada"), Some("grace")]);
+        assert_eq!(history[1].to_status, model::FulfillmentStatus::Fulfilled);
+    }
+
+    #[tokio::test]
+    async fn test_transfer_moves_stock() {
+        let mut provider = setup().await;
+        let store = provider.create_location("store").await.unwrap();
+
+        let mut transfer = model::NewFulfillment::from(model::FulfillmentType::Transfer);
+        transfer.from_location_id = Some(model::DEFAULT_LOCATION_ID);
+        assert!(provider.create_fulfillment(&transfer).await.is_err());
+        transfer.to_location_id = Some(model::DEFAULT_LOCATION_ID);
+        assert!(provider.create_fulfillment(&transfer).await.is_err());
+        transfer.to_location_id = Some(store + 1);
+        assert!(provider.create_fulfillment(&transfer).await.is_err());
+        transfer.to_location_id = Some(store);
+        let fulfillment_id = provider.create_fulfillment(&transfer).await.unwrap();
+
+        provider
+            .record_stock_movement(1, None, 5, model::StockMovementReason::Receipt, None)
+            .await
+            .unwrap();
+        let line_item_id = provider
+            .create_line_item(fulfillment_id, 1, 2, None)
+            .await
+            .unwrap()[0];
+        advance(
+            &mut provider,
+            fulfillment_id,
+            &[
+                model::FulfillmentStatus::Initialized,
+                model::FulfillmentStatus::InProgress,
+            ],
+        )
+        .await;
+        provider
+            .set_quantity_fulfilled(line_item_id, 2)
+            .await
+            .unwrap();
+        advance(
+            &mut provider,
+            fulfillment_id,
+            &[model::FulfillmentStatus::Fulfilled],
+        )
+        .await;
+
+        let level = provider.get_stock_level(1).await.unwrap();
+        assert_eq!(level.on_hand, 5);
+        let by_location: Vec<(i64, i64)> = level
+            .locations
+            .iter()
+            .map(|l| (l.location_id, l.on_hand))
+            .collect();
+        assert_eq!(
+            by_location,
+            vec![(model::DEFAULT_LOCATION_ID, 3), (store, 2)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stock_moves_need_stock_on_hand() {
+        let mut provider = setup().await;
+        let store = provider.create_location("store").await.unwrap();
+        provider
+            .record_stock_movement(1, None, 3, model::StockMovementReason::Receipt, None)
+            .await
+            .unwrap();
+
+        // the first transfer holds two units, so the second only gets the one left
+        let mut transfer = model::NewFulfillment::from(model::FulfillmentType::Transfer);
+        transfer.from_location_id = Some(model::DEFAULT_LOCATION_ID);
+        transfer.to_location_id = Some(store);
+        let mut transfer_ids = Vec::new();
+        for _ in 0..2 {
+            let fulfillment_id = provider.create_fulfillment(&transfer).await.unwrap();
+            provider
+                .create_line_item(fulfillment_id, 1, 2, None)
+                .await
+                .unwrap();
+            advance(
+                &mut provider,
+                fulfillment_id,
+                &[model::FulfillmentStatus::Initialized],
+            )
+            .await;
+            transfer_ids.push(fulfillment_id);
+        }
+        let lines = provider
+            .get_line_items_by_fulfillment_id(transfer_ids[1])
+            .await
+            .unwrap();
+        assert_eq!(lines[0].data.quantity_reserved, 1);
+        let backorders = provider.get_backorders(1).await.unwrap();
+        assert_eq!(backorders[0].data.quantity, 1);
+        let backorder = provider
+            .get_fulfillment(&backorders[0].data.fulfillment_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(backorder.data.to_location_id, Some(store));
+
+        // a count correction takes the shelf below what was picked
+        advance(
+            &mut provider,
+            transfer_ids[0],
+            &[model::FulfillmentStatus::InProgress],
+        )
+        .await;
+        let line_item_id = provider
+            .get_line_items_by_fulfillment_id(transfer_ids[0])
+            .await
+            .unwrap()[0]
+            .id;
+        provider
+            .set_quantity_fulfilled(line_item_id, 2)
+            .await
+            .unwrap();
+        provider
+            .record_stock_movement(1, None, -2, model::StockMovementReason::Adjustment, None)
+            .await
+            .unwrap();
+        assert!(provider
+            .set_fulfillment_status(
+                &transfer_ids[0],
+                &model::FulfillmentStatus::Fulfilled.into()
+            )
+            .await
+            .is_err());
+        let level = provider.get_stock_level(1).await.unwrap();
+        assert_eq!((level.on_hand, level.available), (1, 0));
+        assert_eq!(level.locations[0].on_hand, 1);
+    }
+
+    #[tokio::test]
+    async fn test_stock_is_held_at_the_source_location() {
+        let mut provider = setup().await;
+        let store = provider.create_location("store").await.unwrap();
+        provider
+            .record_stock_movement(1, None, 3, model::StockMovementReason::Receipt, None)
+            .await
+            .unwrap();
+
+        // everything is in the warehouse, so a transfer out of the store can't hold any
+        let mut transfer = model::NewFulfillment::from(model::FulfillmentType::Transfer);
+        transfer.from_location_id = Some(store);
+        transfer.to_location_id = Some(model::DEFAULT_LOCATION_ID);
+        let fulfillment_id = provider.create_fulfillment(&transfer).await.unwrap();
+        provider
+            .create_line_item(fulfillment_id, 1, 2, None)
+            .await
+            .unwrap();
+        advance(
+            &mut provider,
+            fulfillment_id,
+            &[model::FulfillmentStatus::Initialized],
+        )
+        .await;
+        let backorders = provider.get_backorders(1).await.unwrap();
+        assert_eq!(backorders[0].data.quantity, 2);
+        assert_eq!(backorders[0].data.quantity_reserved, 0);
+        let level = provider.get_stock_level(1).await.unwrap();
+        assert_eq!((level.on_hand, level.reserved, level.available), (3, 0, 3));
+
+        // warehouse receipts leave the store backorder waiting
+        provider
+            .record_stock_movement(1, None, 2, model::StockMovementReason::Receipt, None)
+            .await
+            .unwrap();
+        let backorders = provider.get_backorders(1).await.unwrap();
+        assert_eq!(backorders[0].data.quantity_reserved, 0);
+
+        provider
+            .record_stock_movement(1, Some(store), 1, model::StockMovementReason::Receipt, None)
+            .await
+            .unwrap();
+        let backorders = provider.get_backorders(1).await.unwrap();
+        assert_eq!(backorders[0].data.quantity_reserved, 1);
+    }
+
+    #[tokio::test]
+    async fn test_configured_workflow() {
+        let workflows = model::Workflows::from_json(
+            r#"{"workflows": [{
+                "fulfillment_type": "StockPickUp",
+                "states": ["New", "Initialized", "InProgress", "ReadyForPickup", "Fulfilled"],
+                "transitions": [
+                    {"from": "New", "to": "Initialized", "guards": ["HasLines"]},
+                    {"from": "Initialized", "to": "InProgress"},
+                    {"from": "InProgress", "to": "ReadyForPickup", "guards": ["AllLinesPicked"]},
+                    {"from": "ReadyForPickup", "to": "Fulfilled"}
+                ]
+            }]}"#,
+        )
+        .unwrap();
+        let mut provider = setup().await.with_workflows(workflows);
+        let ready = model::FulfillmentStatus::Custom("ReadyForPickup".to_string());
+
+        provider
+            .record_stock_movement(1, None, 2, model::StockMovementReason::Receipt, None)
+            .await
+            .unwrap();
+        let fulfillment_id = provider
+            .create_fulfillment(&model::NewFulfillment::from(
+                model::FulfillmentType::StockPickUp,
+            ))
+            .await
+            .unwrap();
+
+        assert!(provider
+            .set_fulfillment_status(
+                &fulfillment_id,
+                &model::FulfillmentStatus::Initialized.into()
+            )
+            .await
+            .is_err());
+        let line_item_id = provider
+            .create_line_item(fulfillment_id, 1, 2, None)
+            .await
+            .unwrap()[0];
+        advance(
+            &mut provider,
+            fulfillment_id,
+            &[
+                model::FulfillmentStatus::Initialized,
+                model::FulfillmentStatus::InProgress,
+            ],
+        )
+        .await;
+
+        // the ready step can't be skipped, and needs everything picked
+        assert!(provider
+            .set_fulfillment_status(&fulfillment_id, &model::FulfillmentStatus::Fulfilled.into())
+            .await
+            .is_err());
+        provider
+            .set_quantity_fulfilled(line_item_id, 1)
+            .await
+            .unwrap();
+        assert!(provider
+            .set_fulfillment_status(&fulfillment_id, &ready.clone().into())
+            .await
+            .is_err());
+        provider
+            .set_quantity_fulfilled(line_item_id, 2)
+            .await
+            .unwrap();
+        advance(&mut provider, fulfillment_id, std::slice::from_ref(&ready)).await;
+
+        let fulfillment = provider
+            .get_fulfillment(&fulfillment_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(fulfillment.data.status, ready);
+
+        advance(
+            &mut provider,
+            fulfillment_id,
+            &[model::FulfillmentStatus::Fulfilled],
+        )
+        .await;
+        assert_eq!(provider.get_stock_level(1).await.unwrap().on_hand, 0);
+    }
+
+    #[tokio::test]
+    async fn test_shipping_label() {
+        let mut provider = setup().await;
+        CustomerService::init_provider(&mut provider).await.unwrap();
+
+        provider
+            .create_product(&model::ProductDetails {
+                sku: "SKU_1".to_string(),
+                description: "widget".to_string(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let customer_id = provider
+            .create_customer(&model::CustomerDetails {
+                name: "Ada".to_string(),
+                email: None,
+                phone: None,
+            })
+            .await
+            .unwrap();
+        let address = provider
+            .add_address(&model::AddressDetails {
+                customer_id,
+                label: None,
+                is_default: true,
+                address: model::Address {
+                    recipient: "Ada ^ Co".to_string(),
+                    line1: "1 Analytical Row".to_string(),
+                    line2: None,
+                    city: "London".to_string(),
+                    region: None,
+                    postal_code: "N1 1AA".to_string(),
+                    country: "GB".to_string(),
+                    latitude: None,
+                    longitude: None,
+                },
+            })
+            .await
+            .unwrap();
+
+        let delivery = provider
+            .create_fulfillment(&model::NewFulfillment {
+                ship_to_address_id: Some(address.id),
+                ..model::NewFulfillment::from(model::FulfillmentType::StockDelivery)
+            })
+            .await
+            .unwrap();
+        provider
+            .create_line_item(delivery, 1, 2, None)
+            .await
+            .unwrap();
+
+        let label = provider.get_shipping_label(&delivery).await.unwrap();
+        assert_eq!(label.ship_from.as_deref(), Some("Default"));
+        assert_eq!(label.lines[0].quantity, 2);
+
+        let zpl = render::label::label_zpl(&label);
+        assert!(zpl.starts_with("^XA") && zpl.ends_with("^XZ\n"));
+        assert!(zpl.contains("^FDAda _5E Co^FS"));
+        assert!(zpl.contains(&format!("^BCN,120,Y,N,N^FD{}^FS", delivery)));
+        assert!(zpl.contains("2 x SKU_5F1 widget"));
+        assert!(render::label::label_text(&label).contains("2 x SKU_1 widget"));
+
+        let pickup = provider
+            .create_fulfillment(&model::NewFulfillment::from(
+                model::FulfillmentType::StockPickUp,
+            ))
+            .await
+            .unwrap();
+        assert!(provider.get_shipping_label(&pickup).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_packing_slip() {
+        let mut provider = setup().await;
+        provider
+            .create_product(&model::ProductDetails {
+                sku: "SKU-1".to_string(),
+                description: "bolts <M6>".to_string(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        provider
+            .record_stock_movement(1, None, 4, model::StockMovementReason::Receipt, None)
+            .await
+            .unwrap();
+
+        let fulfillment_id = provider
+            .create_fulfillment(&model::NewFulfillment::from(
+                model::FulfillmentType::StockPickUp,
+            ))
+            .await
+            .unwrap();
+        let line_item_id = provider
+            .create_line_item(fulfillment_id, 1, 4, None)
+            .await
+            .unwrap()[0];
+        advance(
+            &mut provider,
+            fulfillment_id,
+            &[
+                model::FulfillmentStatus::Initialized,
+                model::FulfillmentStatus::InProgress,
+            ],
+        )
+        .await;
+        provider
+            .set_quantity_fulfilled(line_item_id, 3)
+            .await
+            .unwrap();
+
+        let slip = provider.get_packing_slip(&fulfillment_id).await.unwrap();
+        assert!(slip.customer.is_none());
+        assert_eq!(slip.lines[0].sku, "SKU-1");
+        assert_eq!(
+            (slip.lines[0].quantity, slip.lines[0].quantity_fulfilled),
+            (4, 3)
+        );
+
+        let html = render::packing_slip::packing_slip_html(&slip);
+        assert!(html.contains("<td>bolts &lt;M6&gt;</td><td>4</td><td>3</td>"));
+        assert!(matches!(
+            provider.get_packing_slip(&42).await,
+            Err(service::Error::NotFound(_))
+        ));
+
+        // nothing added yet still prints, just without lines
+        let empty = provider
+            .create_fulfillment(&model::NewFulfillment::from(
+                model::FulfillmentType::StockPickUp,
+            ))
+            .await
+            .unwrap();
+        let slip = provider.get_packing_slip(&empty).await.unwrap();
+        assert!(slip.lines.is_empty());
+        assert!(!render::packing_slip::packing_slip_html(&slip).contains("<td>"));
+    }
+
+    #[tokio::test]
+    async fn test_scan_item() {
+        let mut provider = setup().await;
+        for sku in ["SKU-1", "SKU-2", "SKU-3"] {
+            let product_id = provider
+                .create_product(&model::ProductDetails {
+                    sku: sku.to_string(),
+                    description: "widget".to_string(),
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+            provider
+                .record_stock_movement(
+                    product_id,
+                    None,
+                    4,
+                    model::StockMovementReason::Receipt,
+                    None,
+                )
+                .await
+                .unwrap();
+        }
+
+        let fulfillment_id = provider
+            .create_fulfillment(&model::NewFulfillment::from(
+                model::FulfillmentType::StockPickUp,
+            ))
+            .await
+            .unwrap();
+        provider
+            .create_line_item(fulfillment_id, 1, 2, None)
+            .await
+            .unwrap();
+        provider
+            .create_line_item(fulfillment_id, 2, 1, None)
+            .await
+            .unwrap();
+
+        let scan = |code: &str| model::Scan {
+            code: code.to_string(),
+            quantity: 1,
+        };
+        // nothing to pick until it's in progress
+        assert!(provider
+            .scan_item(&fulfillment_id, &scan("SKU-1"))
+            .await
+            .is_err());
+        advance(
+            &mut provider,
+            fulfillment_id,
+            &[
+                model::FulfillmentStatus::Initialized,
+                model::FulfillmentStatus::InProgress,
+            ],
+        )
+        .await;
+
+        let result = provider
+            .scan_item(&fulfillment_id, &scan("SKU-1"))
+            .await
+            .unwrap();
+        assert_eq!(result.quantity_fulfilled, 1);
+        let remaining: Vec<(&str, i64)> = result
+            .remaining
+            .iter()
+            .map(|r| (r.sku.as_str(), r.quantity))
+            .collect();
+        assert_eq!(remaining, vec![("SKU-1", 1), ("SKU-2", 1)]);
+
+        // unknown codes, empty scans, products not on the fulfillment and over-picks are
+        // rejected
+        assert!(matches!(
+            provider.scan_item(&fulfillment_id, &scan("NOPE")).await,
+            Err(service::Error::ProductNotFound(_))
+        ));
+        for quantity in [0, -1] {
+            assert!(provider
+                .scan_item(
+                    &fulfillment_id,
+                    &model::Scan {
+                        quantity,
+                        ..scan("SKU-1")
+                    }
+                )
+                .await
+                .is_err());
+        }
+        assert!(provider
+            .scan_item(&fulfillment_id, &scan("SKU-3"))
+            .await
+            .is_err());
+        assert!(provider
+            .scan_item(
+                &fulfillment_id,
+                &model::Scan {
+                    quantity: 2,
+                    ..scan("SKU-1")
+                }
+            )
+            .await
+            .is_err());
+
+        provider
+            .scan_item(&fulfillment_id, &scan("SKU-1"))
+            .await
+            .unwrap();
+        let result = provider
+            .scan_item(&fulfillment_id, &scan("SKU-2"))
+            .await
+            .unwrap();
+        assert!(result.remaining.is_empty());
+
+        assert!(matches!(
+            provider.scan_item(&42, &scan("SKU-1")).await,
+            Err(service::Error::NotFound(_))
+        ));
+
+        // nor can anything be picked once it's done
+        advance(
+            &mut provider,
+            fulfillment_id,
+            &[model::FulfillmentStatus::Fulfilled],
+        )
+        .await;
+        assert!(provider
+            .scan_item(&fulfillment_id, &scan("SKU-1"))
+            .await
+            .is_err());
+    }
 }
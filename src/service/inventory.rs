@@ -0,0 +1,1069 @@
+use std::{collections::BTreeMap, fmt::Display};
+
+use axum::http::StatusCode;
+use chrono::Utc;
+use futures::TryStreamExt;
+use sqlx::{Row, SqliteConnection};
+
+use crate::{
+    model::{self, ToRecord},
+    provider::SqliteProvider,
+    service::fulfillment,
+};
+
+pub trait InventoryService {
+    type Error: Display + Into<StatusCode>;
+
+    async fn init_provider(&mut self) -> Result<(), Self::Error>;
+
+    async fn create_location(&mut self, name: &str) -> Result<i64, Self::Error>;
+
+    async fn get_location(
+        &mut self,
+        location_id: i64,
+    ) -> Result<Option<model::Record<model::LocationDetails>>, Self::Error>;
+
+    async fn get_locations(
+        &mut self,
+    ) -> Result<Vec<model::Record<model::LocationDetails>>, Self::Error>;
+
+    /// Records goods received or a stock count correction.
+    ///
+    /// Received stock is allocated to waiting backorders, oldest first.
+    async fn record_stock_movement(
+        &mut self,
+        product_id: i64,
+        location_id: Option<i64>,
+        quantity: i64,
+        reason: model::StockMovementReason,
+        reference: Option<String>,
+    ) -> Result<model::Record<model::StockMovementDetails>, Self::Error>;
+
+    /// A kit's stock is how many could be made up from its components' stock
+    async fn get_stock_level(&mut self, product_id: i64) -> Result<model::StockLevel, Self::Error>;
+
+    async fn get_stock_movements(
+        &mut self,
+        product_id: i64,
+    ) -> Result<Vec<model::Record<model::StockMovementDetails>>, Self::Error>;
+
+    /// Backordered lines for a product still waiting on their fulfillment, oldest first
+    async fn get_backorders(
+        &mut self,
+        product_id: i64,
+    ) -> Result<Vec<model::Record<model::LineItemDetails>>, Self::Error>;
+}
+
+impl InventoryService for SqliteProvider {
+    type Error = super::Error;
+
+    async fn init_provider(&mut self) -> Result<(), Self::Error> {
+        let mut conn = self.connection.acquire().await?;
+        sqlx::query(sql_stmt::CREATE_LOCATION_TABLE)
+            .execute(&mut *conn)
+            .await?;
+        sqlx::query(sql_stmt::INSERT_DEFAULT_LOCATION)
+            .bind(model::DEFAULT_LOCATION_ID)
+            .execute(&mut *conn)
+            .await?;
+        sqlx::query(sql_stmt::CREATE_TABLE)
+            .execute(&mut *conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn create_location(&mut self, name: &str) -> Result<i64, Self::Error> {
+        let mut conn = self.connection.acquire().await?;
+        let result = sqlx::query(sql_stmt::INSERT_LOCATION)
+            .bind(name)
+            .execute(&mut *conn)
+            .await?;
+        Ok(result.last_insert_rowid())
+    }
+
+    async fn get_location(
+        &mut self,
+        location_id: i64,
+    ) -> Result<Option<model::Record<model::LocationDetails>>, Self::Error> {
+        let mut conn = self.connection.acquire().await?;
+        let result = sqlx::query(sql_stmt::SELECT_LOCATION)
+            .bind(location_id)
+            .fetch_optional(&mut *conn)
+            .await?;
+
+        let Some(row) = result else {
+            return Ok(None);
+        };
+
+        Ok(Some(
+            model::LocationDetails {
+                name: row.try_get("name")?,
+            }
+            .to_record(location_id),
+        ))
+    }
+
+    async fn get_locations(
+        &mut self,
+    ) -> Result<Vec<model::Record<model::LocationDetails>>, Self::Error> {
+        let mut conn = self.connection.acquire().await?;
+        let mut rows = sqlx::query(sql_stmt::SELECT_LOCATIONS).fetch(&mut *conn);
+
+        let mut records = Vec::new();
+        while let Some(row) = rows.try_next().await? {
+            records.push(
+                model::LocationDetails {
+                    name: row.try_get("name")?,
+                }
+                .to_record(row.try_get("id")?),
+            );
+        }
+
+        Ok(records)
+    }
+
+    async fn record_stock_movement(
+        &mut self,
+        product_id: i64,
+        location_id: Option<i64>,
+        quantity: i64,
+        reason: model::StockMovementReason,
+        reference: Option<String>,
+    ) -> Result<model::Record<model::StockMovementDetails>, Self::Error> {
+        match reason {
+            model::StockMovementReason::Receipt if quantity <= 0 => {
+                return Err(super::Error::BadInput(
+                    "received quantity must be positive".to_string(),
+                ))
+            }
+            model::StockMovementReason::Adjustment if quantity == 0 => {
+                return Err(super::Error::BadInput(
+                    "adjustment can't be zero".to_string(),
+                ))
+            }
+            model::StockMovementReason::Issue
+            | model::StockMovementReason::Return
+            | model::StockMovementReason::Transfer => {
+                return Err(super::Error::BadInput(
+                    "issues, transfers and returns are recorded by fulfillments and returns"
+                        .to_string(),
+                ))
+            }
+            _ => {}
+        }
+
+        let location_id = location_id.unwrap_or(model::DEFAULT_LOCATION_ID);
+        if self.get_location(location_id).await?.is_none() {
+            return Err(super::Error::NotFound(format!("location {}", location_id)));
+        }
+
+        let allocate = reason == model::StockMovementReason::Receipt;
+
+        let mut tx = self.connection.begin().await?;
+        let movement = insert_stock_movement(
+            &mut tx,
+            product_id,
+            location_id,
+            quantity,
+            reason,
+            reference,
+        )
+        .await?;
+        if allocate {
+            allocate_backorders(&mut tx, product_id).await?;
+        }
+        tx.commit().await?;
+
+        Ok(movement)
+    }
+
+    async fn get_stock_level(&mut self, product_id: i64) -> Result<model::StockLevel, Self::Error> {
+        let mut conn = self.connection.acquire().await?;
+        let components = sqlx::query(sql_stmt::SELECT_KIT_COMPONENTS)
+            .bind(product_id)
+            .fetch_all(&mut *conn)
+            .await?;
+        if components.is_empty() {
+            return stock_level(&mut conn, product_id).await;
+        }
+
+        let mut kit: Option<model::StockLevel> = None;
+        for row in components {
+            let per_kit: i64 = row.try_get("quantity")?;
+            let component = stock_level(&mut conn, row.try_get("productId")?).await?;
+            let locations: Vec<model::LocationStock> = component
+                .locations
+                .iter()
+                .map(|l| model::LocationStock {
+                    location_id: l.location_id,
+                    on_hand: l.on_hand / per_kit,
+                })
+                .collect();
+            let on_hand = component.on_hand / per_kit;
+            let available = component.available / per_kit;
+
+            kit = Some(match kit {
+                None => model::StockLevel {
+                    product_id,
+                    on_hand,
+                    reserved: 0,
+                    available,
+                    locations,
+                },
+                Some(kit) => model::StockLevel {
+                    on_hand: kit.on_hand.min(on_hand),
+                    available: kit.available.min(available),
+                    // only where every component is stocked
+                    locations: kit
+                        .locations
+                        .iter()
+                        .filter_map(|k| {
+                            locations
+                                .iter()
+                                .find(|l| l.location_id == k.location_id)
+                                .map(|l| model::LocationStock {
+                                    location_id: k.location_id,
+                                    on_hand: k.on_hand.min(l.on_hand),
+                                })
+                        })
+                        .collect(),
+                    ..kit
+                },
+            });
+        }
+
+        let mut kit = kit.unwrap();
+        // component stock already held for other lines
+        kit.reserved = kit.on_hand - kit.available;
+        Ok(kit)
+    }
+
+    async fn get_stock_movements(
+        &mut self,
+        product_id: i64,
+    ) -> Result<Vec<model::Record<model::StockMovementDetails>>, Self::Error> {
+        let mut conn = self.connection.acquire().await?;
+        let mut rows = sqlx::query(sql_stmt::SELECT_MOVEMENTS)
+            .bind(product_id)
+            .fetch(&mut *conn);
+
+        let mut records = Vec::new();
+        while let Some(row) = rows.try_next().await? {
+            let reason: String = row.try_get("reason")?;
+            records.push(
+                model::StockMovementDetails {
+                    product_id: row.try_get("productId")?,
+                    location_id: row.try_get("locationId")?,
+                    quantity: row.try_get("quantity")?,
+                    reason: reason.parse().map_err(super::Error::ProviderFailure)?,
+                    reference: row.try_get("reference")?,
+                    created_at: row.try_get("createdAt")?,
+                }
+                .to_record(row.try_get("id")?),
+            );
+        }
+
+        Ok(records)
+    }
+
+    async fn get_backorders(
+        &mut self,
+        product_id: i64,
+    ) -> Result<Vec<model::Record<model::LineItemDetails>>, Self::Error> {
+        let mut conn = self.connection.acquire().await?;
+        let rows = sqlx::query(sql_stmt::SELECT_OPEN_BACKORDERS)
+            .bind(product_id)
+            .fetch_all(&mut *conn)
+            .await?;
+
+        let mut records = Vec::new();
+        for row in rows {
+            records.push(
+                model::LineItemDetails {
+                    product_id: row.try_get("productId")?,
+                    fulfillment_id: row.try_get("fulfillmentId")?,
+                    quantity: row.try_get("quantity")?,
+                    quantity_fulfilled: row.try_get("quantityFulfilled")?,
+                    quantity_reserved: row.try_get("quantityReserved")?,
+                    is_backorder: row.try_get("isBackorder")?,
+                    unit: row.try_get("unit")?,
+                    unit_factor: row.try_get("unitFactor")?,
+                }
+                .to_record(row.try_get("id")?),
+            );
+        }
+
+        Ok(records)
+    }
+}
+
+async fn on_hand(conn: &mut SqliteConnection, product_id: i64) -> Result<i64, super::Error> {
+    let row = sqlx::query(sql_stmt::SELECT_ON_HAND)
+        .bind(product_id)
+        .fetch_one(&mut *conn)
+        .await?;
+    Ok(row.try_get("onHand")?)
+}
+
+async fn reserved(conn: &mut SqliteConnection, product_id: i64) -> Result<i64, super::Error> {
+    let row = sqlx::query(sql_stmt::SELECT_RESERVED)
+        .bind(product_id)
+        .fetch_one(&mut *conn)
+        .await?;
+    Ok(row.try_get("reserved")?)
+}
+
+/// What's left to promise, never negative even when a count correction has taken stock
+/// below what's already reserved
+fn unreserved(on_hand: i64, reserved: i64) -> i64 {
+    (on_hand - reserved).max(0)
+}
+
+/// What's left to promise at one location, fulfillments only hold stock where they'll
+/// take it from
+async fn available(
+    conn: &mut SqliteConnection,
+    product_id: i64,
+    location_id: i64,
+) -> Result<i64, super::Error> {
+    let row = sqlx::query(sql_stmt::SELECT_STOCK_AT_LOCATION)
+        .bind(product_id)
+        .bind(location_id)
+        .bind(model::DEFAULT_LOCATION_ID)
+        .fetch_one(&mut *conn)
+        .await?;
+    Ok(unreserved(row.try_get("onHand")?, row.try_get("reserved")?))
+}
+
+async fn stock_level(
+    conn: &mut SqliteConnection,
+    product_id: i64,
+) -> Result<model::StockLevel, super::Error> {
+    let on_hand = on_hand(conn, product_id).await?;
+    let reserved = reserved(conn, product_id).await?;
+
+    let mut locations = Vec::new();
+    let mut rows = sqlx::query(sql_stmt::SELECT_ON_HAND_BY_LOCATION)
+        .bind(product_id)
+        .fetch(&mut *conn);
+    while let Some(row) = rows.try_next().await? {
+        locations.push(model::LocationStock {
+            location_id: row.try_get("locationId")?,
+            on_hand: row.try_get("onHand")?,
+        });
+    }
+
+    Ok(model::StockLevel {
+        product_id,
+        on_hand,
+        reserved,
+        available: unreserved(on_hand, reserved),
+        locations,
+    })
+}
+
+async fn reserve_line(
+    conn: &mut SqliteConnection,
+    line_item_id: i64,
+    quantity: i64,
+) -> Result<(), super::Error> {
+    sqlx::query(sql_stmt::RESERVE_LINE)
+        .bind(quantity)
+        .bind(line_item_id)
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+/// Reserves stock at `location_id` for every line on a fulfillment that's being
+/// initialized.
+///
+/// Whatever can't be reserved moves to a single new backorder fulfillment: a partly
+/// reserved line is split, an unreserved line is moved across whole. Returns the
+/// backorder fulfillment, if one was needed.
+pub async fn reserve_fulfillment_stock(
+    conn: &mut SqliteConnection,
+    fulfillment_id: i64,
+    location_id: i64,
+) -> Result<Option<i64>, super::Error> {
+    let lines = sqlx::query(sql_stmt::SELECT_FULFILLMENT_LINES)
+        .bind(fulfillment_id)
+        .fetch_all(&mut *conn)
+        .await?;
+
+    let mut backorder_id = None;
+    for row in lines {
+        let line_item_id: i64 = row.try_get("id")?;
+        let product_id: i64 = row.try_get("productId")?;
+        let quantity: i64 = row.try_get("quantity")?;
+        let quantity_reserved: i64 = row.try_get("quantityReserved")?;
+
+        let needed = quantity - quantity_reserved;
+        if needed <= 0 {
+            continue;
+        }
+
+        let reserve = needed.min(available(conn, product_id, location_id).await?);
+        if reserve > 0 {
+            reserve_line(conn, line_item_id, reserve).await?;
+        }
+        if reserve == needed {
+            continue;
+        }
+
+        let backorder = match backorder_id {
+            Some(id) => id,
+            None => {
+                let id = fulfillment::create_backorder_fulfillment(conn, fulfillment_id).await?;
+                backorder_id = Some(id);
+                id
+            }
+        };
+
+        let reserved = quantity_reserved + reserve;
+        if reserved == 0 {
+            sqlx::query(sql_stmt::MOVE_TO_BACKORDER)
+                .bind(backorder)
+                .bind(line_item_id)
+                .execute(&mut *conn)
+                .await?;
+        } else {
+            sqlx::query(sql_stmt::SET_LINE_QUANTITY)
+                .bind(reserved)
+                .bind(line_item_id)
+                .execute(&mut *conn)
+                .await?;
+            sqlx::query(sql_stmt::INSERT_BACKORDER_LINE)
+                .bind(backorder)
+                .bind(quantity - reserved)
+                .bind(line_item_id)
+                .execute(&mut *conn)
+                .await?;
+        }
+    }
+
+    Ok(backorder_id)
+}
+
+/// Hands available stock to waiting backorder lines, oldest first, each from the location
+/// its fulfillment takes stock from
+pub async fn allocate_backorders(
+    conn: &mut SqliteConnection,
+    product_id: i64,
+) -> Result<(), super::Error> {
+    let backorders = sqlx::query(sql_stmt::SELECT_OPEN_BACKORDERS)
+        .bind(product_id)
+        .fetch_all(&mut *conn)
+        .await?;
+
+    let mut available_at = BTreeMap::new();
+    for row in backorders {
+        let location_id = row
+            .try_get::<Option<i64>, _>("fromLocationId")?
+            .unwrap_or(model::DEFAULT_LOCATION_ID);
+        let available = match available_at.get_mut(&location_id) {
+            Some(available) => available,
+            None => {
+                let available = available(conn, product_id, location_id).await?;
+                available_at.entry(location_id).or_insert(available)
+            }
+        };
+
+        let quantity: i64 = row.try_get("quantity")?;
+        let quantity_reserved: i64 = row.try_get("quantityReserved")?;
+        let reserve = (quantity - quantity_reserved).min(*available);
+        if reserve > 0 {
+            reserve_line(conn, row.try_get("id")?, reserve).await?;
+            *available -= reserve;
+        }
+    }
+
+    Ok(())
+}
+
+/// Adds a movement to the ledger, usable inside another service's transaction
+pub async fn insert_stock_movement(
+    conn: &mut SqliteConnection,
+    product_id: i64,
+    location_id: i64,
+    quantity: i64,
+    reason: model::StockMovementReason,
+    reference: Option<String>,
+) -> Result<model::Record<model::StockMovementDetails>, super::Error> {
+    let movement = model::StockMovementDetails {
+        product_id,
+        location_id,
+        quantity,
+        reason,
+        reference,
+        created_at: Utc::now(),
+    };
+
+    let result = sqlx::query(sql_stmt::INSERT_MOVEMENT)
+        .bind(movement.product_id)
+        .bind(movement.location_id)
+        .bind(movement.quantity)
+        .bind(String::from(movement.reason.clone()))
+        .bind(&movement.reference)
+        .bind(movement.created_at)
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(movement.to_record(result.last_insert_rowid()))
+}
+
+/// Fails if `location_id` doesn't hold everything picked for a fulfillment
+async fn check_picked_on_hand(
+    conn: &mut SqliteConnection,
+    fulfillment_id: i64,
+    location_id: i64,
+) -> Result<(), super::Error> {
+    let rows = sqlx::query(sql_stmt::SELECT_PICKED_ON_HAND)
+        .bind(fulfillment_id)
+        .bind(location_id)
+        .fetch_all(&mut *conn)
+        .await?;
+    for row in rows {
+        let picked: i64 = row.try_get("picked")?;
+        let on_hand: i64 = row.try_get("onHand")?;
+        if picked > on_hand {
+            let product_id: i64 = row.try_get("productId")?;
+            return Err(super::Error::BadInput(format!(
+                "location {} has {} of product {}, {} were picked",
+                location_id, on_hand, product_id, picked
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Takes what was picked for a fulfillment out of stock at `location_id`
+pub async fn issue_fulfillment_stock(
+    conn: &mut SqliteConnection,
+    fulfillment_id: i64,
+    location_id: i64,
+) -> Result<(), super::Error> {
+    check_picked_on_hand(conn, fulfillment_id, location_id).await?;
+    sqlx::query(sql_stmt::MOVE_FULFILLMENT_STOCK)
+        .bind(fulfillment_id)
+        .bind(location_id)
+        .bind(-1)
+        .bind(String::from(model::StockMovementReason::Issue))
+        .bind(format!("fulfillment:{}", fulfillment_id))
+        .bind(Utc::now())
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+/// Moves what was picked for a transfer fulfillment from one location to another
+pub async fn transfer_fulfillment_stock(
+    conn: &mut SqliteConnection,
+    fulfillment_id: i64,
+    from_location_id: i64,
+    to_location_id: i64,
+) -> Result<(), super::Error> {
+    check_picked_on_hand(conn, fulfillment_id, from_location_id).await?;
+    let created_at = Utc::now();
+    for (location_id, sign) in [(from_location_id, -1), (to_location_id, 1)] {
+        sqlx::query(sql_stmt::MOVE_FULFILLMENT_STOCK)
+            .bind(fulfillment_id)
+            .bind(location_id)
+            .bind(sign)
+            .bind(String::from(model::StockMovementReason::Transfer))
+            .bind(format!("fulfillment:{}", fulfillment_id))
+            .bind(created_at)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
+
+mod sql_stmt {
+    pub const CREATE_LOCATION_TABLE: &str = r#"
+        CREATE TABLE IF NOT EXISTS locations (
+            id INTEGER NOT NULL UNIQUE PRIMARY KEY,
+            name TEXT NOT NULL
+        );
+    "#;
+
+    pub const INSERT_DEFAULT_LOCATION: &str = r#"
+        INSERT OR IGNORE INTO locations (id, name) VALUES ( $1, 'Default' );
+    "#;
+
+    pub const INSERT_LOCATION: &str = r#"
+        INSERT INTO locations (name) VALUES ( $1 );
+    "#;
+
+    pub const SELECT_LOCATION: &str = r#"
+        SELECT name FROM locations WHERE id=$1;
+    "#;
+
+    pub const SELECT_LOCATIONS: &str = r#"
+        SELECT id, name FROM locations ORDER BY id;
+    "#;
+
+    pub const CREATE_TABLE: &str = r#"
+        CREATE TABLE IF NOT EXISTS stockMovements (
+            id INTEGER NOT NULL UNIQUE PRIMARY KEY,
+            productId INTEGER NOT NULL,
+            locationId INTEGER NOT NULL,
+            quantity INTEGER NOT NULL,
+            reason TEXT NOT NULL,
+            reference TEXT,
+            createdAt TEXT NOT NULL
+        );
+    "#;
+
+    pub const INSERT_MOVEMENT: &str = r#"
+        INSERT INTO stockMovements (productId, locationId, quantity, reason, reference, createdAt)
+        VALUES ( $1, $2, $3, $4, $5, $6 );
+    "#;
+
+    pub const MOVE_FULFILLMENT_STOCK: &str = r#"
+        INSERT INTO stockMovements (productId, locationId, quantity, reason, reference, createdAt)
+        SELECT productId, $2, $3 * quantityFulfilled, $4, $5, $6
+        FROM lineItems
+        WHERE fulfillmentId = $1 AND quantityFulfilled > 0;
+    "#;
+
+    pub const SELECT_PICKED_ON_HAND: &str = r#"
+        SELECT
+            productId,
+            SUM(quantityFulfilled) AS picked,
+            (
+                SELECT COALESCE(SUM(quantity), 0) FROM stockMovements
+                WHERE stockMovements.productId = lineItems.productId AND locationId = $2
+            ) AS onHand
+        FROM lineItems
+        WHERE fulfillmentId = $1 AND quantityFulfilled > 0
+        GROUP BY productId;
+    "#;
+
+    pub const SELECT_ON_HAND: &str = r#"
+        SELECT COALESCE(SUM(quantity), 0) AS onHand FROM stockMovements WHERE productId=$1;
+    "#;
+
+    pub const SELECT_ON_HAND_BY_LOCATION: &str = r#"
+        SELECT locationId, SUM(quantity) AS onHand
+        FROM stockMovements WHERE productId=$1
+        GROUP BY locationId ORDER BY locationId;
+    "#;
+
+    pub const SELECT_RESERVED: &str = r#"
+        SELECT COALESCE(SUM(lineItems.quantityReserved), 0) AS reserved
+        FROM lineItems
+        JOIN fulfillments ON fulfillments.id = lineItems.fulfillmentId
+        WHERE lineItems.productId = $1 AND fulfillments.fulfillmentStatus != 'Fulfilled';
+    "#;
+
+    pub const SELECT_STOCK_AT_LOCATION: &str = r#"
+        SELECT
+            (
+                SELECT COALESCE(SUM(quantity), 0) FROM stockMovements
+                WHERE productId = $1 AND locationId = $2
+            ) AS onHand,
+            (
+                SELECT COALESCE(SUM(lineItems.quantityReserved), 0)
+                FROM lineItems
+                JOIN fulfillments ON fulfillments.id = lineItems.fulfillmentId
+                WHERE lineItems.productId = $1
+                AND fulfillments.fulfillmentStatus != 'Fulfilled'
+                AND COALESCE(fulfillments.fromLocationId, $3) = $2
+            ) AS reserved;
+    "#;
+
+    pub const SELECT_FULFILLMENT_LINES: &str = r#"
+        SELECT id, productId, quantity, quantityReserved
+        FROM lineItems WHERE fulfillmentId = $1 ORDER BY id;
+    "#;
+
+    pub const SELECT_OPEN_BACKORDERS: &str = r#"
+        SELECT lineItems.*, fulfillments.fromLocationId
+        FROM lineItems
+        JOIN fulfillments ON fulfillments.id = lineItems.fulfillmentId
+        WHERE lineItems.productId = $1
+        AND lineItems.isBackorder
+        AND fulfillments.fulfillmentStatus = 'New'
+        ORDER BY lineItems.id;
+    "#;
+
+    pub const RESERVE_LINE: &str = r#"
+        UPDATE lineItems SET quantityReserved = quantityReserved + $1 WHERE id = $2;
+    "#;
+
+    pub const SET_LINE_QUANTITY: &str = r#"
+        UPDATE lineItems SET quantity = $1 WHERE id = $2;
+    "#;
+
+    pub const MOVE_TO_BACKORDER: &str = r#"
+        UPDATE lineItems SET fulfillmentId = $1, isBackorder = 1 WHERE id = $2;
+    "#;
+
+    pub const INSERT_BACKORDER_LINE: &str = r#"
+        INSERT INTO lineItems (fulfillmentId, productId, quantity, isBackorder, unit, unitFactor)
+        SELECT $1, productId, $2, 1, unit, unitFactor FROM lineItems WHERE id = $3;
+    "#;
+
+    pub const SELECT_KIT_COMPONENTS: &str = r#"
+        SELECT productId, quantity FROM kitComponents WHERE kitId=$1 ORDER BY productId;
+    "#;
+    pub const SELECT_MOVEMENTS: &str = r#"
+        SELECT * FROM stockMovements WHERE productId=$1 ORDER BY id;
+    "#;
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        model,
+        provider::SqliteProvider,
+        service::{
+            fulfillment::FulfillmentService, inventory::InventoryService,
+            line_item::LineItemService, product::ProductService,
+        },
+    };
+
+    async fn setup() -> SqliteProvider {
+        let mut provider = SqliteProvider::new_memory().await.unwrap();
+        FulfillmentService::init_provider(&mut provider)
+            .await
+            .unwrap();
+        LineItemService::init_provider(&mut provider).await.unwrap();
+        ProductService::init_provider(&mut provider).await.unwrap();
+        InventoryService::init_provider(&mut provider)
+            .await
+            .unwrap();
+        provider
+    }
+
+    async fn receive(provider: &mut SqliteProvider, product_id: i64, quantity: i64) {
+        provider
+            .record_stock_movement(
+                product_id,
+                None,
+                quantity,
+                model::StockMovementReason::Receipt,
+                None,
+            )
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_partial_reservation_backorders() {
+        let mut provider = setup().await;
+        receive(&mut provider, 1, 3).await;
+
+        let fulfillment_id = provider
+            .create_fulfillment(&model::NewFulfillment::from(
+                model::FulfillmentType::StockPickUp,
+            ))
+            .await
+            .unwrap();
+        let partial = provider
+            .create_line_item(fulfillment_id, 1, 5, None)
+            .await
+            .unwrap()[0];
+        provider
+            .create_line_item(fulfillment_id, 2, 2, None)
+            .await
+            .unwrap();
+
+        provider
+            .set_fulfillment_status(
+                &fulfillment_id,
+                &model::FulfillmentStatus::Initialized.into(),
+            )
+            .await
+            .unwrap();
+
+        let lines = provider
+            .get_line_items_by_fulfillment_id(fulfillment_id)
+            .await
+            .unwrap();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].id, partial);
+        assert_eq!(lines[0].data.quantity, 3);
+        assert_eq!(lines[0].data.quantity_reserved, 3);
+
+        // both shortfalls end up on the same new fulfillment
+        let backorders = provider.get_backorders(1).await.unwrap();
+        assert_eq!(backorders.len(), 1);
+        assert_eq!(backorders[0].data.quantity, 2);
+        let backorder_id = backorders[0].data.fulfillment_id;
+        assert_ne!(backorder_id, fulfillment_id);
+        let backorder = provider
+            .get_fulfillment(&backorder_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(backorder.data.status, model::FulfillmentStatus::New);
+        assert_eq!(
+            provider
+                .get_line_items_by_fulfillment_id(backorder_id)
+                .await
+                .unwrap()
+                .len(),
+            2
+        );
+
+        let level = provider.get_stock_level(1).await.unwrap();
+        assert_eq!((level.on_hand, level.reserved, level.available), (3, 3, 0));
+    }
+
+    #[tokio::test]
+    async fn test_receipts_allocate_backorders() {
+        let mut provider = setup().await;
+
+        let first = provider
+            .create_fulfillment(&model::NewFulfillment::from(
+                model::FulfillmentType::StockPickUp,
+            ))
+            .await
+            .unwrap();
+        provider.create_line_item(first, 1, 2, None).await.unwrap();
+        let second = provider
+            .create_fulfillment(&model::NewFulfillment::from(
+                model::FulfillmentType::StockPickUp,
+            ))
+            .await
+            .unwrap();
+        provider.create_line_item(second, 1, 2, None).await.unwrap();
+
+        for fulfillment_id in [first, second] {
+            provider
+                .set_fulfillment_status(
+                    &fulfillment_id,
+                    &model::FulfillmentStatus::Initialized.into(),
+                )
+                .await
+                .unwrap();
+        }
+        assert_eq!(provider.get_backorders(1).await.unwrap().len(), 2);
+
+        // the oldest backorder is served first
+        receive(&mut provider, 1, 3).await;
+        let backorders = provider.get_backorders(1).await.unwrap();
+        assert_eq!(backorders[0].data.quantity_reserved, 2);
+        assert_eq!(backorders[1].data.quantity_reserved, 1);
+
+        let level = provider.get_stock_level(1).await.unwrap();
+        assert_eq!(level.available, 0);
+
+        // initializing the backorder only needs the one unit still missing
+        receive(&mut provider, 1, 1).await;
+        let backorder_id = backorders[1].data.fulfillment_id;
+        provider
+            .set_fulfillment_status(&backorder_id, &model::FulfillmentStatus::Initialized.into())
+            .await
+            .unwrap();
+        assert_eq!(provider.get_backorders(1).await.unwrap().len(), 1);
+        assert_eq!(provider.get_stock_level(1).await.unwrap().reserved, 4);
+    }
+
+    #[tokio::test]
+    async fn test_kit_explodes_into_components() {
+        let mut provider = setup().await;
+        let mut component_ids = Vec::new();
+        for sku in ["BOLT", "NUT"] {
+            let product_id = provider
+                .create_product(&model::ProductDetails {
+                    sku: sku.to_string(),
+                    description: sku.to_lowercase(),
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+            component_ids.push(product_id);
+        }
+        let components = vec![
+            model::KitComponent {
+                product_id: component_ids[0],
+                quantity: 2,
+            },
+            model::KitComponent {
+                product_id: component_ids[1],
+                quantity: 4,
+            },
+        ];
+
+        // only kits can have components
+        assert!(provider
+            .create_product(&model::ProductDetails {
+                sku: "LOOSE".to_string(),
+                description: "not a kit".to_string(),
+                components: components.clone(),
+                ..Default::default()
+            })
+            .await
+            .is_err());
+        let kit_id = provider
+            .create_product(&model::ProductDetails {
+                sku: "KIT".to_string(),
+                description: "bolt kit".to_string(),
+                product_type: model::ProductType::Kit,
+                components,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        // kits don't nest, and components need a positive quantity of a real product
+        let kit = |components: Vec<(i64, i64)>| model::ProductDetails {
+            sku: "BIG-KIT".to_string(),
+            description: "kit of kits".to_string(),
+            product_type: model::ProductType::Kit,
+            components: components
+                .into_iter()
+                .map(|(product_id, quantity)| model::KitComponent {
+                    product_id,
+                    quantity,
+                })
+                .collect(),
+            ..Default::default()
+        };
+        for components in [
+            vec![(kit_id, 1)],
+            vec![(component_ids[0], 0)],
+            vec![(component_ids[0], 1), (component_ids[0], 1)],
+            vec![(99, 1)],
+            vec![],
+        ] {
+            assert!(provider.create_product(&kit(components)).await.is_err());
+        }
+
+        receive(&mut provider, component_ids[0], 7).await;
+        receive(&mut provider, component_ids[1], 20).await;
+        let level = provider.get_stock_level(kit_id).await.unwrap();
+        assert_eq!((level.on_hand, level.available), (3, 3));
+
+        let fulfillment_id = provider
+            .create_fulfillment(&model::NewFulfillment::from(
+                model::FulfillmentType::StockPickUp,
+            ))
+            .await
+            .unwrap();
+        let line_item_ids = provider
+            .create_line_item(fulfillment_id, kit_id, 2, None)
+            .await
+            .unwrap();
+        let lines = provider
+            .get_line_items_by_fulfillment_id(fulfillment_id)
+            .await
+            .unwrap();
+        assert_eq!(
+            line_item_ids,
+            lines.iter().map(|l| l.id).collect::<Vec<_>>()
+        );
+        let quantities: Vec<_> = lines
+            .iter()
+            .map(|l| (l.data.product_id, l.data.quantity))
+            .collect();
+        assert_eq!(
+            quantities,
+            vec![(component_ids[0], 4), (component_ids[1], 8)]
+        );
+
+        provider
+            .set_fulfillment_status(
+                &fulfillment_id,
+                &model::FulfillmentStatus::Initialized.into(),
+            )
+            .await
+            .unwrap();
+        let level = provider.get_stock_level(kit_id).await.unwrap();
+        assert_eq!((level.on_hand, level.reserved, level.available), (3, 2, 1));
+
+        // whichever component runs out first limits the kit
+        receive(&mut provider, component_ids[0], 10).await;
+        let level = provider.get_stock_level(kit_id).await.unwrap();
+        assert_eq!((level.on_hand, level.reserved, level.available), (5, 2, 3));
+    }
+
+    #[tokio::test]
+    async fn test_units_normalize_to_base() {
+        let mut provider = setup().await;
+        let case = model::UnitOfMeasure {
+            name: "case".to_string(),
+            factor: 12,
+        };
+
+        // an alternate unit can't shadow the base unit
+        assert!(provider
+            .create_product(&model::ProductDetails {
+                sku: "SCREW".to_string(),
+                description: "screw".to_string(),
+                base_unit: "case".to_string(),
+                units: vec![case.clone()],
+                ..Default::default()
+            })
+            .await
+            .is_err());
+        // nor can it be worth nothing, or less
+        for factor in [0, -12] {
+            assert!(provider
+                .create_product(&model::ProductDetails {
+                    sku: "SCREW".to_string(),
+                    description: "screw".to_string(),
+                    units: vec![model::UnitOfMeasure {
+                        name: "case".to_string(),
+                        factor,
+                    }],
+                    ..Default::default()
+                })
+                .await
+                .is_err());
+        }
+        let product_id = provider
+            .create_product(&model::ProductDetails {
+                sku: "SCREW".to_string(),
+                description: "screw".to_string(),
+                units: vec![case],
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let product = provider.get_product(&product_id).await.unwrap();
+        assert_eq!(product.data.base_unit, model::DEFAULT_UNIT);
+        receive(&mut provider, product_id, 30).await;
+
+        let fulfillment_id = provider
+            .create_fulfillment(&model::NewFulfillment::from(
+                model::FulfillmentType::StockPickUp,
+            ))
+            .await
+            .unwrap();
+        assert!(provider
+            .create_line_item(fulfillment_id, product_id, 1, Some("pallet"))
+            .await
+            .is_err());
+        for quantity in [0, -1, i64::MAX] {
+            assert!(provider
+                .create_line_item(fulfillment_id, product_id, quantity, Some("case"))
+                .await
+                .is_err());
+        }
+        let line_item_id = provider
+            .create_line_item(fulfillment_id, product_id, 3, Some("case"))
+            .await
+            .unwrap()[0];
+        let line = provider.get_line_item(line_item_id).await.unwrap().unwrap();
+        assert_eq!((line.data.quantity, line.data.unit_factor), (36, 12));
+
+        provider
+            .set_fulfillment_status(
+                &fulfillment_id,
+                &model::FulfillmentStatus::Initialized.into(),
+            )
+            .await
+            .unwrap();
+        let level = provider.get_stock_level(product_id).await.unwrap();
+        assert_eq!((level.reserved, level.available), (30, 0));
+
+        // the shortfall stays in base units but remembers what it was ordered in
+        let backorders = provider.get_backorders(product_id).await.unwrap();
+        assert_eq!(backorders[0].data.quantity, 6);
+        assert_eq!(backorders[0].data.unit, "case");
+    }
+}
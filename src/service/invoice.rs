@@ -0,0 +1,392 @@
+use std::fmt::Display;
+
+use axum::http::StatusCode;
+use chrono::Utc;
+use futures::TryStreamExt;
+use sqlx::{Row, SqliteConnection};
+
+use crate::{
+    model::{self, ToRecord},
+    provider::SqliteProvider,
+    service::{customer, order, product},
+};
+
+pub trait InvoiceService {
+    type Error: Display + Into<StatusCode>;
+
+    async fn init_provider(&mut self) -> Result<(), Self::Error>;
+
+    async fn create_invoice_sequence(
+        &mut self,
+        name: &str,
+        prefix: &str,
+    ) -> Result<model::InvoiceSequenceDetails, Self::Error>;
+
+    async fn get_invoice_sequences(
+        &mut self,
+    ) -> Result<Vec<model::InvoiceSequenceDetails>, Self::Error>;
+
+    async fn get_invoice_by_order_id(
+        &mut self,
+        order_id: i64,
+    ) -> Result<Option<model::Record<model::InvoiceDetails>>, Self::Error>;
+}
+
+impl InvoiceService for SqliteProvider {
+    type Error = super::Error;
+
+    async fn init_provider(&mut self) -> Result<(), Self::Error> {
+        let mut conn = self.connection.acquire().await?;
+        sqlx::query(sql_stmt::CREATE_SEQUENCE_TABLE)
+            .execute(&mut *conn)
+            .await?;
+        sqlx::query(sql_stmt::CREATE_INVOICE_TABLE)
+            .execute(&mut *conn)
+            .await?;
+        sqlx::query(sql_stmt::INSERT_SEQUENCE)
+            .bind(model::DEFAULT_INVOICE_SEQUENCE)
+            .bind("INV-")
+            .execute(&mut *conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn create_invoice_sequence(
+        &mut self,
+        name: &str,
+        prefix: &str,
+    ) -> Result<model::InvoiceSequenceDetails, Self::Error> {
+        let mut conn = self.connection.acquire().await?;
+        let result = sqlx::query(sql_stmt::INSERT_SEQUENCE)
+            .bind(name)
+            .bind(prefix)
+            .execute(&mut *conn)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(super::Error::BadInput(format!(
+                "invoice sequence {} already exists",
+                name
+            )));
+        }
+
+        Ok(model::InvoiceSequenceDetails {
+            name: name.to_string(),
+            prefix: prefix.to_string(),
+            next_number: 1,
+        })
+    }
+
+    async fn get_invoice_sequences(
+        &mut self,
+    ) -> Result<Vec<model::InvoiceSequenceDetails>, Self::Error> {
+        let mut conn = self.connection.acquire().await?;
+        let mut rows = sqlx::query(sql_stmt::SELECT_SEQUENCES).fetch(&mut *conn);
+
+        let mut sequences = Vec::new();
+        while let Some(row) = rows.try_next().await? {
+            sequences.push(model::InvoiceSequenceDetails {
+                name: row.try_get("name")?,
+                prefix: row.try_get("prefix")?,
+                next_number: row.try_get("nextNumber")?,
+            });
+        }
+
+        Ok(sequences)
+    }
+
+    async fn get_invoice_by_order_id(
+        &mut self,
+        order_id: i64,
+    ) -> Result<Option<model::Record<model::InvoiceDetails>>, Self::Error> {
+        let mut conn = self.connection.acquire().await?;
+        let result = sqlx::query(sql_stmt::SELECT_INVOICE_BY_ORDER_ID)
+            .bind(order_id)
+            .fetch_optional(&mut *conn)
+            .await?;
+
+        let Some(row) = result else {
+            return Ok(None);
+        };
+
+        let document: String = row.try_get("document")?;
+        let invoice: model::InvoiceDetails = serde_json::from_str(&document)
+            .map_err(|e| super::Error::ProviderFailure(format!("bad invoice document {}", e)))?;
+
+        Ok(Some(invoice.to_record(row.try_get("id")?)))
+    }
+}
+
+/// Builds the invoice document for an order as `conn` sees it, without a number.
+///
+/// Products and the customer's address are read through `conn` too, so an invoice built
+/// in the selling transaction captures them as of the sale.
+pub async fn build_invoice(
+    conn: &mut SqliteConnection,
+    order_id: i64,
+) -> Result<model::InvoiceDetails, super::Error> {
+    let Some(order) = order::order_details(conn, order_id).await? else {
+        return Err(super::Error::NotFound(format!("order {}", order_id)));
+    };
+    let order_lines = order::order_lines(conn, order_id).await?;
+
+    let priced: Vec<model::PricedLine> = order_lines
+        .iter()
+        .map(|line| model::PricedLine::from(&line.data))
+        .collect();
+    let (line_totals, totals) = model::compute_totals(
+        &priced,
+        order.data.discount.as_ref(),
+        model::minor_units(&order.data.currency),
+    );
+
+    let mut lines = Vec::new();
+    for (line, line_total) in order_lines.iter().zip(line_totals) {
+        let product = product::product_details(conn, line.data.product_id).await?;
+        lines.push(model::InvoiceLine {
+            product_id: line.data.product_id,
+            sku: product.data.sku,
+            description: product.data.description,
+            quantity: line.data.quantity,
+            unit_price: line.data.unit_price,
+            tax_rate: line.data.tax_rate,
+            subtotal: line_total.subtotal,
+            discount: line_total.discount,
+            tax: line_total.tax,
+            total: line_total.total,
+        });
+    }
+
+    let (customer, bill_to) = match order.data.customer_id {
+        Some(customer_id) => (
+            customer::customer_details(conn, customer_id)
+                .await?
+                .map(|c| c.data),
+            customer::default_address(conn, customer_id)
+                .await?
+                .map(|a| a.data.address),
+        ),
+        None => (None, None),
+    };
+
+    Ok(model::InvoiceDetails {
+        order_id,
+        sequence: model::DEFAULT_INVOICE_SEQUENCE.to_string(),
+        number: 0,
+        invoice_number: String::new(),
+        issued_at: Utc::now(),
+        currency: order.data.currency,
+        customer,
+        bill_to,
+        lines,
+        totals,
+        amount_paid: order.data.amount_paid,
+    })
+}
+
+/// Numbers and stores an invoice.
+///
+/// Must run in the same transaction that marks the order sold, the number is only
+/// taken from the sequence if everything else commits so numbering stays gapless.
+pub async fn issue_invoice(
+    conn: &mut SqliteConnection,
+    sequence: &str,
+    mut invoice: model::InvoiceDetails,
+) -> Result<model::Record<model::InvoiceDetails>, super::Error> {
+    let result = sqlx::query(sql_stmt::SELECT_SEQUENCE)
+        .bind(sequence)
+        .fetch_optional(&mut *conn)
+        .await?;
+
+    let Some(row) = result else {
+        return Err(super::Error::NotFound(format!(
+            "invoice sequence {}",
+            sequence
+        )));
+    };
+
+    let prefix: String = row.try_get("prefix")?;
+    let number: i64 = row.try_get("nextNumber")?;
+
+    sqlx::query(sql_stmt::ADVANCE_SEQUENCE)
+        .bind(sequence)
+        .execute(&mut *conn)
+        .await?;
+
+    invoice.sequence = sequence.to_string();
+    invoice.number = number;
+    invoice.invoice_number = model::format_invoice_number(&prefix, number);
+
+    let document = serde_json::to_string(&invoice)
+        .map_err(|e| super::Error::ProviderFailure(format!("can't store invoice {}", e)))?;
+
+    let result = sqlx::query(sql_stmt::INSERT_INVOICE)
+        .bind(invoice.order_id)
+        .bind(sequence)
+        .bind(number)
+        .bind(document)
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(invoice.to_record(result.last_insert_rowid()))
+}
+
+mod sql_stmt {
+    pub const CREATE_SEQUENCE_TABLE: &str = r#"
+        CREATE TABLE IF NOT EXISTS invoiceSequences (
+            name TEXT NOT NULL UNIQUE PRIMARY KEY,
+            prefix TEXT NOT NULL,
+            nextNumber INTEGER NOT NULL
+        );
+    "#;
+
+    pub const CREATE_INVOICE_TABLE: &str = r#"
+        CREATE TABLE IF NOT EXISTS invoices (
+            id INTEGER NOT NULL UNIQUE PRIMARY KEY,
+            orderId INTEGER NOT NULL UNIQUE,
+            sequence TEXT NOT NULL,
+            number INTEGER NOT NULL,
+            document TEXT NOT NULL,
+            UNIQUE (sequence, number)
+        );
+    "#;
+
+    pub const INSERT_SEQUENCE: &str = r#"
+        INSERT OR IGNORE INTO invoiceSequences (name, prefix, nextNumber) VALUES ( $1, $2, 1 );
+    "#;
+
+    pub const SELECT_SEQUENCES: &str = r#"
+        SELECT name, prefix, nextNumber FROM invoiceSequences ORDER BY name;
+    "#;
+
+    pub const SELECT_SEQUENCE: &str = r#"
+        SELECT prefix, nextNumber FROM invoiceSequences WHERE name=$1;
+    "#;
+
+    pub const ADVANCE_SEQUENCE: &str = r#"
+        UPDATE invoiceSequences SET nextNumber = nextNumber + 1 WHERE name=$1;
+    "#;
+
+    pub const INSERT_INVOICE: &str = r#"
+        INSERT INTO invoices (orderId, sequence, number, document) VALUES ( $1, $2, $3, $4 );
+    "#;
+
+    pub const SELECT_INVOICE_BY_ORDER_ID: &str = r#"
+        SELECT id, document FROM invoices WHERE orderId=$1;
+    "#;
+}
+
+#[cfg(test)]
+mod test {
+    use rust_decimal::Decimal;
+
+    use crate::{
+        model,
+        provider::SqliteProvider,
+        render,
+        service::{
+            currency::ExchangeRateService, invoice::InvoiceService, order::OrderService,
+            price_list::PriceListService, product::ProductService, tax::TaxService,
+        },
+    };
+
+    async fn setup() -> SqliteProvider {
+        let mut provider = SqliteProvider::new_memory().await.unwrap();
+        OrderService::init_provider(&mut provider).await.unwrap();
+        ProductService::init_provider(&mut provider).await.unwrap();
+        PriceListService::init_provider(&mut provider)
+            .await
+            .unwrap();
+        TaxService::init_provider(&mut provider).await.unwrap();
+        ExchangeRateService::init_provider(&mut provider)
+            .await
+            .unwrap();
+        InvoiceService::init_provider(&mut provider).await.unwrap();
+
+        provider
+            .create_product(&model::ProductDetails {
+                sku: "SKU-1".to_string(),
+                description: "widget <large>".to_string(),
+                base_price: Decimal::new(1000, 2),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        provider
+    }
+
+    async fn sell(provider: &mut SqliteProvider, sequence: Option<&str>) -> Result<i64, ()> {
+        let order_id = provider.create_order(None, None, "USD").await.unwrap();
+        provider.add_order_line(order_id, 1, 2, None).await.unwrap();
+        provider
+            .set_order_status(
+                order_id,
+                &model::OrderStatusChange {
+                    order_status: model::OrderStatus::Sold,
+                    override_unpaid: false,
+                    invoice_sequence: sequence.map(str::to_string),
+                },
+            )
+            .await
+            .map(|_| order_id)
+            .map_err(|_| ())
+    }
+
+    #[tokio::test]
+    async fn test_invoice_numbering() {
+        let mut provider = setup().await;
+        provider
+            .create_invoice_sequence("retail", "R-")
+            .await
+            .unwrap();
+        assert!(provider
+            .create_invoice_sequence("retail", "X-")
+            .await
+            .is_err());
+
+        let first = sell(&mut provider, None).await.unwrap();
+        // a sale against a missing sequence rolls back without using a number
+        assert!(sell(&mut provider, Some("missing")).await.is_err());
+        let second = sell(&mut provider, None).await.unwrap();
+        let retail = sell(&mut provider, Some("retail")).await.unwrap();
+
+        let invoice = provider
+            .get_invoice_by_order_id(first)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(invoice.data.invoice_number, "INV-000001");
+        assert_eq!(invoice.data.lines[0].sku, "SKU-1");
+        assert_eq!(invoice.data.totals.total, 2000);
+
+        let invoice = provider
+            .get_invoice_by_order_id(second)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(invoice.data.invoice_number, "INV-000002");
+
+        let invoice = provider
+            .get_invoice_by_order_id(retail)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(invoice.data.invoice_number, "R-000001");
+
+        let html = render::invoice::invoice_html(&invoice.data);
+        assert!(html.contains("widget &lt;large&gt;"));
+        let text = render::invoice::invoice_text(&invoice.data);
+        assert!(text.contains("INVOICE R-000001"));
+        assert!(text.contains("20.00 USD"));
+
+        // the failed sale's order stays a quote with no invoice
+        let order = provider.get_order(first + 1).await.unwrap().unwrap();
+        assert_eq!(order.data.status, model::OrderStatus::Quote);
+        assert!(provider
+            .get_invoice_by_order_id(first + 1)
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
@@ -14,12 +14,16 @@ pub trait LineItemService {
 
     async fn init_provider(&mut self) -> Result<(), Self::Error>;
 
+    /// Adds a line to a new fulfillment. Kits are exploded into a line for each of their
+    /// components, and every line added is returned. The quantity is in `unit`, or the
+    /// product's base unit when there isn't one, and is stored converted to the base unit.
     async fn create_line_item(
         &mut self,
         fulfillment_id: i64,
         product_id: i64,
         quantity: i64,
-    ) -> Result<i64, Self::Error>;
+        unit: Option<&str>,
+    ) -> Result<Vec<i64>, Self::Error>;
 
     async fn get_line_item(
         &mut self,
@@ -30,6 +34,13 @@ pub trait LineItemService {
         &mut self,
         fulfillment_id: i64,
     ) -> Result<Vec<model::Record<model::LineItemDetails>>, Self::Error>;
+
+    /// Records how much of a line has been picked, only while its fulfillment is in progress
+    async fn set_quantity_fulfilled(
+        &mut self,
+        line_item_id: i64,
+        quantity_fulfilled: i64,
+    ) -> Result<(), Self::Error>;
 }
 
 impl LineItemService for SqliteProvider {
@@ -51,25 +62,86 @@ impl LineItemService for SqliteProvider {
         fulfillment_id: i64,
         product_id: i64,
         quantity: i64,
-    ) -> Result<i64, Self::Error> {
-        let mut conn = self.connection.acquire().await?;
+        unit: Option<&str>,
+    ) -> Result<Vec<i64>, Self::Error> {
+        if quantity <= 0 {
+            return Err(super::Error::BadInput(
+                "line items need a positive quantity".to_string(),
+            ));
+        }
+        let too_many = || super::Error::BadInput(format!("{} is too many", quantity));
 
-        let result = sqlx::query(sql_stmt::INSERT_LINE_ITEM)
-            .bind(fulfillment_id)
+        let mut tx = self.connection.begin().await?;
+
+        let base_unit = match sqlx::query(sql_stmt::SELECT_BASE_UNIT)
             .bind(product_id)
-            .bind(quantity)
-            .execute(&mut *conn)
-            .await?;
+            .fetch_optional(&mut *tx)
+            .await?
+        {
+            Some(row) => row.try_get("baseUnit")?,
+            None => model::DEFAULT_UNIT.to_string(),
+        };
+        let unit = unit.unwrap_or(&base_unit);
+        let factor: i64 = if unit == base_unit {
+            1
+        } else {
+            let Some(row) = sqlx::query(sql_stmt::SELECT_UNIT_FACTOR)
+                .bind(product_id)
+                .bind(unit)
+                .fetch_optional(&mut *tx)
+                .await?
+            else {
+                return Err(super::Error::BadInput(format!(
+                    "{} isn't a unit of product {}",
+                    unit, product_id
+                )));
+            };
+            row.try_get("factor")?
+        };
+        let quantity = quantity.checked_mul(factor).ok_or_else(too_many)?;
 
-        if result.rows_affected() == 0 {
-            warn!("rows affected was 0 expected 1");
-            return Err(super::Error::BadInput(format!(
-                "can't add line item to fulfillment {}",
-                fulfillment_id
-            )));
+        // components are always in their own base unit
+        let mut lines = Vec::new();
+        for row in sqlx::query(sql_stmt::SELECT_KIT_COMPONENTS)
+            .bind(product_id)
+            .fetch_all(&mut *tx)
+            .await?
+        {
+            let per_kit: i64 = row.try_get("quantity")?;
+            lines.push((
+                row.try_get("productId")?,
+                quantity.checked_mul(per_kit).ok_or_else(too_many)?,
+                row.try_get("baseUnit")?,
+                1,
+            ));
+        }
+        if lines.is_empty() {
+            lines.push((product_id, quantity, unit.to_string(), factor));
+        }
+
+        let mut line_item_ids = Vec::new();
+        for (line_product_id, line_quantity, line_unit, line_factor) in lines {
+            let result = sqlx::query(sql_stmt::INSERT_LINE_ITEM)
+                .bind(fulfillment_id)
+                .bind(line_product_id)
+                .bind(line_quantity)
+                .bind(line_unit)
+                .bind(line_factor)
+                .execute(&mut *tx)
+                .await?;
+
+            if result.rows_affected() == 0 {
+                warn!("rows affected was 0 expected 1");
+                return Err(super::Error::BadInput(format!(
+                    "can't add line item to fulfillment {}",
+                    fulfillment_id
+                )));
+            }
+            line_item_ids.push(result.last_insert_rowid());
         }
 
-        Ok(result.last_insert_rowid())
+        tx.commit().await?;
+        Ok(line_item_ids)
     }
 
     async fn get_line_item(
@@ -91,7 +163,11 @@ impl LineItemService for SqliteProvider {
             product_id: row.try_get("productId")?,
             fulfillment_id: row.try_get("fulfillmentId")?,
             quantity: row.try_get("quantity")?,
-            quantity_fulfilled: 0,
+            quantity_fulfilled: row.try_get("quantityFulfilled")?,
+            quantity_reserved: row.try_get("quantityReserved")?,
+            is_backorder: row.try_get("isBackorder")?,
+            unit: row.try_get("unit")?,
+            unit_factor: row.try_get("unitFactor")?,
         }
         .to_record(line_item_id.to_owned());
 
@@ -116,7 +192,11 @@ impl LineItemService for SqliteProvider {
                     product_id: row.try_get("productId")?,
                     fulfillment_id: row.try_get("fulfillmentId")?,
                     quantity: row.try_get("quantity")?,
-                    quantity_fulfilled: 0,
+                    quantity_fulfilled: row.try_get("quantityFulfilled")?,
+                    quantity_reserved: row.try_get("quantityReserved")?,
+                    is_backorder: row.try_get("isBackorder")?,
+                    unit: row.try_get("unit")?,
+                    unit_factor: row.try_get("unitFactor")?,
                 }
                 .to_record(row.try_get("id")?),
             );
@@ -124,11 +204,42 @@ impl LineItemService for SqliteProvider {
 
         Ok(records)
     }
+
+    async fn set_quantity_fulfilled(
+        &mut self,
+        line_item_id: i64,
+        quantity_fulfilled: i64,
+    ) -> Result<(), Self::Error> {
+        if quantity_fulfilled < 0 {
+            return Err(super::Error::BadInput(
+                "quantity fulfilled can't be negative".to_string(),
+            ));
+        }
+
+        let mut conn = self.connection.acquire().await?;
+        let result = sqlx::query(sql_stmt::UPDATE_QUANTITY_FULFILLED)
+            .bind(quantity_fulfilled)
+            .bind(line_item_id)
+            .execute(&mut *conn)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(super::Error::BadInput(format!(
+                "can't fulfill {} of line item {}",
+                quantity_fulfilled, line_item_id
+            )));
+        }
+
+        Ok(())
+    }
 }
 
 mod sql_stmt {
     pub const SELECT_LINE_ITEM: &str = r#"
-        SELECT id, fulfillmentId, productId, quantity FROM lineItems WHERE id=$1;
+        SELECT
+            id, fulfillmentId, productId, quantity, quantityFulfilled, quantityReserved,
+            isBackorder, unit, unitFactor
+        FROM lineItems WHERE id=$1;
     "#;
 
     pub const CREATE_TABLE: &str = r#"
@@ -136,13 +247,18 @@ mod sql_stmt {
             id INTEGER NOT NULL UNIQUE PRIMARY KEY,
             fulfillmentId INTEGER NOT NULL,
             productId INTEGER NOT NULL,
-            quantity INTEGER NOT NULL
+            quantity INTEGER NOT NULL,
+            quantityFulfilled INTEGER NOT NULL DEFAULT 0,
+            quantityReserved INTEGER NOT NULL DEFAULT 0,
+            isBackorder INTEGER NOT NULL DEFAULT 0,
+            unit TEXT NOT NULL DEFAULT 'each',
+            unitFactor INTEGER NOT NULL DEFAULT 1
         );
     "#;
 
     pub const INSERT_LINE_ITEM: &str = r#"
-        INSERT INTO lineItems (fulfillmentId, productId, quantity)
-        SELECT $1, $2, $3
+        INSERT INTO lineItems (fulfillmentId, productId, quantity, unit, unitFactor)
+        SELECT $1, $2, $3, $4, $5
         WHERE EXISTS (
             SELECT 1
             FROM fulfillments
@@ -150,8 +266,36 @@ mod sql_stmt {
         );
     "#;
 
+    pub const SELECT_BASE_UNIT: &str = r#"
+        SELECT baseUnit FROM products WHERE id=$1;
+    "#;
+
+    pub const SELECT_UNIT_FACTOR: &str = r#"
+        SELECT factor FROM productUnits WHERE productId=$1 AND name=$2;
+    "#;
+
+    pub const SELECT_KIT_COMPONENTS: &str = r#"
+        SELECT kitComponents.productId, kitComponents.quantity, products.baseUnit
+        FROM kitComponents
+        JOIN products ON products.id = kitComponents.productId
+        WHERE kitId=$1 ORDER BY kitComponents.productId;
+    "#;
+
     pub const SELECT_BY_FULFILLMENT_ID: &str = r#"
-        SELECT id, fulfillmentId, productId, quantity FROM lineItems WHERE fulfillmentId=$1;
+        SELECT
+            id, fulfillmentId, productId, quantity, quantityFulfilled, quantityReserved,
+            isBackorder, unit, unitFactor
+        FROM lineItems WHERE fulfillmentId=$1;
+    "#;
+
+    pub const UPDATE_QUANTITY_FULFILLED: &str = r#"
+        UPDATE lineItems SET quantityFulfilled = $1
+        WHERE id = $2 AND quantity >= $1
+        AND EXISTS (
+            SELECT 1
+            FROM fulfillments
+            WHERE id = lineItems.fulfillmentId AND fulfillmentStatus = 'InProgress'
+        );
     "#;
 }
 
@@ -162,7 +306,9 @@ mod test_create_line_item {
     use crate::{
         model,
         provider::SqliteProvider,
-        service::{fulfillment::FulfillmentService, line_item::LineItemService},
+        service::{
+            fulfillment::FulfillmentService, line_item::LineItemService, product::ProductService,
+        },
     };
 
     #[test(tokio::test)]
@@ -170,7 +316,7 @@ mod test_create_line_item {
         // Line items should require both fulfillment & line item tables
         let mut provider = SqliteProvider::new_memory().await.unwrap();
 
-        assert!(provider.create_line_item(1, 1, 1).await.is_err());
+        assert!(provider.create_line_item(1, 1, 1, None).await.is_err());
     }
 
     #[test(tokio::test)]
@@ -182,8 +328,9 @@ mod test_create_line_item {
             .await
             .unwrap();
         LineItemService::init_provider(&mut provider).await.unwrap();
+        ProductService::init_provider(&mut provider).await.unwrap();
 
-        assert!(provider.create_line_item(1, 1, 1).await.is_err());
+        assert!(provider.create_line_item(1, 1, 1, None).await.is_err());
     }
 
     #[test(tokio::test)]
@@ -194,14 +341,17 @@ mod test_create_line_item {
             .await
             .unwrap();
         LineItemService::init_provider(&mut provider).await.unwrap();
+        ProductService::init_provider(&mut provider).await.unwrap();
 
         provider
-            .create_fulfillment(model::FulfillmentType::StockPickUp)
+            .create_fulfillment(&model::NewFulfillment::from(
+                model::FulfillmentType::StockPickUp,
+            ))
             .await
             .unwrap();
 
-        match provider.create_line_item(1, 1, 1).await {
-            Ok(id) => assert_eq!(id, 1),
+        match provider.create_line_item(1, 1, 1, None).await {
+            Ok(ids) => assert_eq!(ids, vec![1]),
             Err(e) => panic!("Expected Ok got {:?}", e),
         };
     }
@@ -239,7 +389,9 @@ mod test {
         let _db_init: () = {
             let mut conn = provider.connection.acquire().await.unwrap();
 
-            sqlx::query(r#"INSERT INTO fulfillments VALUES( NULL, ?1, ?2 );"#)
+            sqlx::query(
+                r#"INSERT INTO fulfillments (fulfillmentStatus, fulfillmentType) VALUES( ?1, ?2 );"#,
+            )
                 .bind(String::from(model::FulfillmentStatus::New))
                 .bind(String::from(model::FulfillmentType::StockPickUp))
                 .execute(&mut *conn)
@@ -249,6 +401,8 @@ mod test {
                 .bind(1)
                 .bind(1)
                 .bind(1)
+                .bind(model::DEFAULT_UNIT)
+                .bind(1)
                 .execute(&mut *conn)
                 .await
                 .unwrap();
@@ -270,7 +424,9 @@ mod test {
         let _db_init: () = {
             let mut conn = provider.connection.acquire().await.unwrap();
 
-            sqlx::query(r#"INSERT INTO fulfillments VALUES ( NULL, ?1, ?2 );"#)
+            sqlx::query(
+                r#"INSERT INTO fulfillments (fulfillmentStatus, fulfillmentType) VALUES ( ?1, ?2 );"#,
+            )
                 .bind(String::from(model::FulfillmentStatus::New))
                 .bind(String::from(model::FulfillmentType::StockPickUp))
                 .execute(&mut *conn)
@@ -282,6 +438,8 @@ mod test {
                     .bind(1)
                     .bind(1)
                     .bind(1)
+                    .bind(model::DEFAULT_UNIT)
+                    .bind(1)
                     .execute(&mut *conn)
                     .await
                     .unwrap();
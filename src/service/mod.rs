@@ -1,15 +1,32 @@
 use core::fmt;
 
 use axum::http::StatusCode;
+use rust_decimal::Decimal;
+use sqlx::{sqlite::SqliteRow, Row};
 
+use crate::model::Discount;
+
+pub mod category;
+pub mod currency;
+pub mod customer;
+pub mod delivery_run;
 pub mod fulfillment;
+pub mod inventory;
+pub mod invoice;
 pub mod line_item;
 pub mod order;
+pub mod pickup;
+pub mod price_list;
 pub mod product;
+pub mod rma;
+pub mod shipment;
+pub mod tax;
+pub mod wave;
 
 #[derive(Debug)]
 pub enum Error {
     BadInput(String),
+    NotFound(String),
     ProductNotFound(String),
     ProviderFailure(String),
 }
@@ -18,6 +35,7 @@ impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let message = match self {
             Self::BadInput(s) => format!("Bad input - {}", s),
+            Self::NotFound(s) => format!("Not found - {}", s),
             Self::ProductNotFound(s) => format!("Product not found - {}", s),
             Self::ProviderFailure(s) => format!("Provider error - {}", s),
         };
@@ -39,10 +57,36 @@ impl Into<StatusCode> for Error {
     fn into(self) -> StatusCode {
         match self {
             Error::BadInput(_) => StatusCode::BAD_REQUEST,
+            Error::NotFound(_) => StatusCode::NOT_FOUND,
             Error::ProductNotFound(_) => StatusCode::NOT_FOUND,
             Error::ProviderFailure(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
 }
 
+/// Money is stored as TEXT so amounts round trip without float error
+pub fn get_decimal(row: &SqliteRow, column: &str) -> Result<Decimal, Error> {
+    let value: String = row.try_get(column)?;
+    value
+        .parse()
+        .map_err(|_| Error::ProviderFailure(format!("bad decimal in {}: {}", column, value)))
+}
+
+/// Discounts are stored as a kind column and a value column, both NULL when absent
+pub fn get_discount(
+    row: &SqliteRow,
+    kind_column: &str,
+    value_column: &str,
+) -> Result<Option<Discount>, Error> {
+    let kind: Option<String> = row.try_get(kind_column)?;
+    let Some(kind) = kind else {
+        return Ok(None);
+    };
+
+    let value = get_decimal(row, value_column)?;
+    Discount::from_parts(&kind, value)
+        .map(Some)
+        .map_err(Error::ProviderFailure)
+}
+
 // TODO: set up error for (StatusCode, String or Json(ErrorMessage))
@@ -1,20 +1,351 @@
-use crate::provider::SqliteProvider;
+use std::fmt::Display;
+
+use axum::http::StatusCode;
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use log::warn;
+use rust_decimal::Decimal;
+use sqlx::{Row, SqliteConnection};
+
+use crate::{
+    model::{self, ToRecord},
+    provider::SqliteProvider,
+    service::{
+        currency::ExchangeRateService, customer::CustomerService, invoice,
+        price_list::PriceListService, tax::TaxService,
+    },
+};
 
 pub trait OrderService {
-    type Error;
+    type Error: Display + Into<StatusCode>;
 
     async fn init_provider(&mut self) -> Result<(), Self::Error>;
-    async fn create_order(&mut self) -> Result<i64, Self::Error>;
+    async fn create_order(
+        &mut self,
+        customer_id: Option<i64>,
+        price_list_id: Option<i64>,
+        currency: &str,
+    ) -> Result<i64, Self::Error>;
+
+    async fn get_order(
+        &mut self,
+        order_id: i64,
+    ) -> Result<Option<model::Record<model::OrderDetails>>, Self::Error>;
+
+    /// Adds a line to a quote, pricing it from the order's price list as of today.
+    ///
+    /// Prices in another currency are converted with the rate locked on the order for
+    /// that currency, locking the current rate the first time it's needed.
+    async fn add_order_line(
+        &mut self,
+        order_id: i64,
+        product_id: i64,
+        quantity: i64,
+        discount: Option<model::Discount>,
+    ) -> Result<model::Record<model::OrderLineDetails>, Self::Error>;
+
+    async fn set_order_line_discount(
+        &mut self,
+        order_id: i64,
+        order_line_id: i64,
+        discount: Option<model::Discount>,
+    ) -> Result<(), Self::Error>;
+
+    async fn remove_order_line(
+        &mut self,
+        order_id: i64,
+        order_line_id: i64,
+    ) -> Result<(), Self::Error>;
+
+    async fn set_order_discount(
+        &mut self,
+        order_id: i64,
+        discount: Option<model::Discount>,
+    ) -> Result<(), Self::Error>;
+
+    async fn get_order_lines(
+        &mut self,
+        order_id: i64,
+    ) -> Result<Vec<model::Record<model::OrderLineDetails>>, Self::Error>;
+
+    /// Moves an order along `Quote -> Sold -> Done`.
+    ///
+    /// An order can only be marked `Done` once it's paid in full, unless `override_unpaid`
+    /// is set. Selling an order issues its invoice from the requested sequence.
+    async fn set_order_status(
+        &mut self,
+        order_id: i64,
+        change: &model::OrderStatusChange,
+    ) -> Result<(), Self::Error>;
+
+    /// Records a payment against an order. Payments on quotes are kept as deposits and can
+    /// be taken before the quote is priced, once sold a payment can't exceed the balance due.
+    async fn record_payment(
+        &mut self,
+        order_id: i64,
+        method: model::PaymentMethod,
+        amount: Decimal,
+        reference: Option<String>,
+        received_at: Option<DateTime<Utc>>,
+    ) -> Result<model::Record<model::PaymentDetails>, Self::Error>;
+
+    async fn get_payments(
+        &mut self,
+        order_id: i64,
+    ) -> Result<Vec<model::Record<model::PaymentDetails>>, Self::Error>;
 }
 
 const CREATE_ORDER_TABLE_SQL: &str = r#"
     CREATE TABLE IF NOT EXISTS orders (
-        id INTEGER NOT NULL UNIQUE PRIMARY KEY
+        id INTEGER NOT NULL UNIQUE PRIMARY KEY,
+        orderStatus TEXT NOT NULL,
+        customerId INTEGER,
+        priceListId INTEGER,
+        currency TEXT NOT NULL,
+        discountKind TEXT,
+        discountValue TEXT,
+        subtotal INTEGER NOT NULL DEFAULT 0,
+        discountTotal INTEGER NOT NULL DEFAULT 0,
+        taxTotal INTEGER NOT NULL DEFAULT 0,
+        total INTEGER NOT NULL DEFAULT 0
+    );
+"#;
+
+const CREATE_ORDER_EXCHANGE_RATE_TABLE_SQL: &str = r#"
+    CREATE TABLE IF NOT EXISTS orderExchangeRates (
+        orderId INTEGER NOT NULL,
+        fromCurrency TEXT NOT NULL,
+        toCurrency TEXT NOT NULL,
+        rate TEXT NOT NULL,
+        PRIMARY KEY (orderId, fromCurrency)
+    );
+"#;
+
+const CREATE_PAYMENT_TABLE_SQL: &str = r#"
+    CREATE TABLE IF NOT EXISTS payments (
+        id INTEGER NOT NULL UNIQUE PRIMARY KEY,
+        orderId INTEGER NOT NULL,
+        method TEXT NOT NULL,
+        amount TEXT NOT NULL,
+        reference TEXT,
+        receivedAt TEXT NOT NULL,
+        isDeposit BOOLEAN NOT NULL
+    );
+"#;
+
+const CREATE_ORDER_LINE_TABLE_SQL: &str = r#"
+    CREATE TABLE IF NOT EXISTS orderLines (
+        id INTEGER NOT NULL UNIQUE PRIMARY KEY,
+        orderId INTEGER NOT NULL,
+        productId INTEGER NOT NULL,
+        quantity INTEGER NOT NULL,
+        unitPrice TEXT NOT NULL,
+        taxRate TEXT NOT NULL,
+        discountKind TEXT,
+        discountValue TEXT
     );
 "#;
 
 pub mod sql_stmt {
-    pub const NEW_ORDER: &str = r#"INSERT INTO orders VALUES( null );"#;
+    pub const NEW_ORDER: &str = r#"
+        INSERT INTO orders (orderStatus, customerId, priceListId, currency)
+        VALUES( $1, $2, $3, $4 );
+    "#;
+
+    pub const SELECT_ORDER: &str = r#"
+        SELECT * FROM orders WHERE id=$1;
+    "#;
+
+    pub const SELECT_LOCKED_RATES: &str = r#"
+        SELECT fromCurrency, toCurrency, rate FROM orderExchangeRates
+        WHERE orderId=$1
+        ORDER BY fromCurrency;
+    "#;
+
+    pub const SELECT_LOCKED_RATE: &str = r#"
+        SELECT rate FROM orderExchangeRates WHERE orderId=$1 AND fromCurrency=$2;
+    "#;
+
+    pub const LOCK_RATE: &str = r#"
+        INSERT OR IGNORE INTO orderExchangeRates (orderId, fromCurrency, toCurrency, rate)
+        VALUES ( $1, $2, $3, $4 );
+    "#;
+
+    pub const UPDATE_ORDER_DISCOUNT: &str = r#"
+        UPDATE orders SET discountKind = $1, discountValue = $2
+        WHERE id = $3 AND orderStatus = 'Quote';
+    "#;
+
+    pub const UPDATE_ORDER_TOTALS: &str = r#"
+        UPDATE orders SET subtotal = $1, discountTotal = $2, taxTotal = $3, total = $4
+        WHERE id = $5;
+    "#;
+
+    pub const INSERT_ORDER_LINE: &str = r#"
+        INSERT INTO orderLines (
+            orderId, productId, quantity, unitPrice, taxRate, discountKind, discountValue
+        )
+        SELECT $1, $2, $3, $4, $5, $6, $7
+        WHERE EXISTS (
+            SELECT 1
+            FROM orders
+            WHERE id = $1 AND orderStatus = 'Quote'
+        );
+    "#;
+
+    pub const SELECT_ORDER_LINES: &str = r#"
+        SELECT * FROM orderLines WHERE orderId=$1 ORDER BY id;
+    "#;
+
+    pub const UPDATE_ORDER_LINE_DISCOUNT: &str = r#"
+        UPDATE orderLines SET discountKind = $1, discountValue = $2
+        WHERE id = $3 AND orderId = $4 AND EXISTS (
+            SELECT 1 FROM orders WHERE id = $4 AND orderStatus = 'Quote'
+        );
+    "#;
+
+    pub const DELETE_ORDER_LINE: &str = r#"
+        DELETE FROM orderLines WHERE id = $1 AND orderId = $2 AND EXISTS (
+            SELECT 1 FROM orders WHERE id = $2 AND orderStatus = 'Quote'
+        );
+    "#;
+
+    pub const UPDATE_ORDER_STATUS: &str = r#"
+        UPDATE orders SET orderStatus = $1 WHERE id = $2 AND orderStatus = $3;
+    "#;
+
+    pub const INSERT_PAYMENT: &str = r#"
+        INSERT INTO payments (orderId, method, amount, reference, receivedAt, isDeposit)
+        VALUES ( $1, $2, $3, $4, $5, $6 );
+    "#;
+
+    pub const SELECT_PAYMENTS: &str = r#"
+        SELECT * FROM payments WHERE orderId=$1 ORDER BY receivedAt, id;
+    "#;
+}
+
+/// An order with its locked rates and payments, read through `conn` so it can be part of
+/// a transaction
+pub async fn order_details(
+    conn: &mut SqliteConnection,
+    order_id: i64,
+) -> Result<Option<model::Record<model::OrderDetails>>, super::Error> {
+    let result = sqlx::query(sql_stmt::SELECT_ORDER)
+        .bind(order_id)
+        .fetch_optional(&mut *conn)
+        .await?;
+
+    let Some(row) = result else {
+        warn!("Sql row not found");
+        return Ok(None);
+    };
+
+    let status: String = row.try_get("orderStatus")?;
+
+    let mut exchange_rates = Vec::new();
+    let mut rate_rows = sqlx::query(sql_stmt::SELECT_LOCKED_RATES)
+        .bind(order_id)
+        .fetch(&mut *conn);
+    while let Some(rate_row) = rate_rows.try_next().await? {
+        exchange_rates.push(model::LockedExchangeRate {
+            from_currency: rate_row.try_get("fromCurrency")?,
+            to_currency: rate_row.try_get("toCurrency")?,
+            rate: super::get_decimal(&rate_row, "rate")?,
+        });
+    }
+    drop(rate_rows);
+
+    let currency: String = row.try_get("currency")?;
+    let total: i64 = row.try_get("total")?;
+
+    let mut amount_paid = 0;
+    let mut payment_rows = sqlx::query(sql_stmt::SELECT_PAYMENTS)
+        .bind(order_id)
+        .fetch(&mut *conn);
+    while let Some(payment_row) = payment_rows.try_next().await? {
+        let amount = super::get_decimal(&payment_row, "amount")?;
+        amount_paid += model::round_minor(amount, model::minor_units(&currency));
+    }
+
+    Ok(Some(
+        model::OrderDetails {
+            status: status.parse().map_err(super::Error::ProviderFailure)?,
+            customer_id: row.try_get("customerId")?,
+            price_list_id: row.try_get("priceListId")?,
+            currency,
+            exchange_rates,
+            discount: super::get_discount(&row, "discountKind", "discountValue")?,
+            totals: model::OrderTotals {
+                subtotal: row.try_get("subtotal")?,
+                discount: row.try_get("discountTotal")?,
+                tax: row.try_get("taxTotal")?,
+                total,
+            },
+            amount_paid,
+            balance_due: total - amount_paid,
+        }
+        .to_record(order_id),
+    ))
+}
+
+/// Recomputes the totals stored on an order from its lines, in the transaction that
+/// changed them
+async fn refresh_order_totals(
+    conn: &mut SqliteConnection,
+    order_id: i64,
+) -> Result<(), super::Error> {
+    let Some(order) = order_details(conn, order_id).await? else {
+        return Err(super::Error::NotFound(format!("order {}", order_id)));
+    };
+    let lines: Vec<model::PricedLine> = order_lines(conn, order_id)
+        .await?
+        .iter()
+        .map(|line| model::PricedLine::from(&line.data))
+        .collect();
+
+    let (_, totals) = model::compute_totals(
+        &lines,
+        order.data.discount.as_ref(),
+        model::minor_units(&order.data.currency),
+    );
+
+    sqlx::query(sql_stmt::UPDATE_ORDER_TOTALS)
+        .bind(totals.subtotal)
+        .bind(totals.discount)
+        .bind(totals.tax)
+        .bind(totals.total)
+        .bind(order_id)
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn order_lines(
+    conn: &mut SqliteConnection,
+    order_id: i64,
+) -> Result<Vec<model::Record<model::OrderLineDetails>>, super::Error> {
+    let mut rows = sqlx::query(sql_stmt::SELECT_ORDER_LINES)
+        .bind(order_id)
+        .fetch(&mut *conn);
+
+    let mut records = Vec::new();
+
+    while let Some(row) = rows.try_next().await? {
+        records.push(
+            model::OrderLineDetails {
+                order_id: row.try_get("orderId")?,
+                product_id: row.try_get("productId")?,
+                quantity: row.try_get("quantity")?,
+                unit_price: super::get_decimal(&row, "unitPrice")?,
+                tax_rate: super::get_decimal(&row, "taxRate")?,
+                discount: super::get_discount(&row, "discountKind", "discountValue")?,
+            }
+            .to_record(row.try_get("id")?),
+        );
+    }
+
+    Ok(records)
 }
 
 impl OrderService for SqliteProvider {
@@ -25,25 +356,428 @@ impl OrderService for SqliteProvider {
         let _ = sqlx::query(CREATE_ORDER_TABLE_SQL)
             .execute(&mut *conn)
             .await?;
+        let _ = sqlx::query(CREATE_ORDER_LINE_TABLE_SQL)
+            .execute(&mut *conn)
+            .await?;
+        let _ = sqlx::query(CREATE_ORDER_EXCHANGE_RATE_TABLE_SQL)
+            .execute(&mut *conn)
+            .await?;
+        let _ = sqlx::query(CREATE_PAYMENT_TABLE_SQL)
+            .execute(&mut *conn)
+            .await?;
         Ok(())
     }
 
-    async fn create_order(&mut self) -> Result<i64, Self::Error> {
+    async fn create_order(
+        &mut self,
+        customer_id: Option<i64>,
+        price_list_id: Option<i64>,
+        currency: &str,
+    ) -> Result<i64, Self::Error> {
+        let currency = model::parse_currency_code(currency).map_err(super::Error::BadInput)?;
+
+        if let Some(customer_id) = customer_id {
+            if self.get_customer(customer_id).await?.is_none() {
+                return Err(super::Error::NotFound(format!("customer {}", customer_id)));
+            }
+        }
+
         let mut conn = self.connection.acquire().await?;
-        let result = sqlx::query(sql_stmt::NEW_ORDER).execute(&mut *conn).await?;
+        let result = sqlx::query(sql_stmt::NEW_ORDER)
+            .bind(String::from(model::OrderStatus::Quote))
+            .bind(customer_id)
+            .bind(price_list_id)
+            .bind(currency)
+            .execute(&mut *conn)
+            .await?;
         Ok(result.last_insert_rowid())
     }
+
+    async fn get_order(
+        &mut self,
+        order_id: i64,
+    ) -> Result<Option<model::Record<model::OrderDetails>>, Self::Error> {
+        let mut conn = self.connection.acquire().await?;
+        order_details(&mut conn, order_id).await
+    }
+
+    async fn add_order_line(
+        &mut self,
+        order_id: i64,
+        product_id: i64,
+        quantity: i64,
+        discount: Option<model::Discount>,
+    ) -> Result<model::Record<model::OrderLineDetails>, Self::Error> {
+        if quantity <= 0 {
+            return Err(super::Error::BadInput(
+                "line quantity must be positive".to_string(),
+            ));
+        }
+        validate_discount(discount.as_ref())?;
+
+        let order = self.get_quote(order_id).await?;
+
+        let today = Utc::now().date_naive();
+        let price = self
+            .resolve_price(product_id, order.data.price_list_id, today)
+            .await?;
+        let unit_price = if price.currency == order.data.currency {
+            price.amount
+        } else {
+            price.amount
+                * self
+                    .lock_exchange_rate(order_id, &price.currency, &order.data.currency)
+                    .await?
+        };
+        let tax_rate = self.get_product_tax_rate(product_id).await?;
+
+        let mut tx = self.connection.begin().await?;
+        let result = sqlx::query(sql_stmt::INSERT_ORDER_LINE)
+            .bind(order_id)
+            .bind(product_id)
+            .bind(quantity)
+            .bind(unit_price.to_string())
+            .bind(tax_rate.to_string())
+            .bind(discount.as_ref().map(|d| d.kind()))
+            .bind(discount.as_ref().map(|d| d.value().to_string()))
+            .execute(&mut *tx)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(super::Error::BadInput(format!(
+                "can't add line to order {}",
+                order_id
+            )));
+        }
+
+        refresh_order_totals(&mut tx, order_id).await?;
+        tx.commit().await?;
+
+        Ok(model::OrderLineDetails {
+            order_id,
+            product_id,
+            quantity,
+            unit_price,
+            tax_rate,
+            discount,
+        }
+        .to_record(result.last_insert_rowid()))
+    }
+
+    async fn set_order_line_discount(
+        &mut self,
+        order_id: i64,
+        order_line_id: i64,
+        discount: Option<model::Discount>,
+    ) -> Result<(), Self::Error> {
+        validate_discount(discount.as_ref())?;
+        self.get_quote(order_id).await?;
+
+        let mut tx = self.connection.begin().await?;
+        let result = sqlx::query(sql_stmt::UPDATE_ORDER_LINE_DISCOUNT)
+            .bind(discount.as_ref().map(|d| d.kind()))
+            .bind(discount.as_ref().map(|d| d.value().to_string()))
+            .bind(order_line_id)
+            .bind(order_id)
+            .execute(&mut *tx)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(super::Error::NotFound(format!(
+                "order line {} on order {}",
+                order_line_id, order_id
+            )));
+        }
+
+        refresh_order_totals(&mut tx, order_id).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn remove_order_line(
+        &mut self,
+        order_id: i64,
+        order_line_id: i64,
+    ) -> Result<(), Self::Error> {
+        self.get_quote(order_id).await?;
+
+        let mut tx = self.connection.begin().await?;
+        let result = sqlx::query(sql_stmt::DELETE_ORDER_LINE)
+            .bind(order_line_id)
+            .bind(order_id)
+            .execute(&mut *tx)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(super::Error::NotFound(format!(
+                "order line {} on order {}",
+                order_line_id, order_id
+            )));
+        }
+
+        refresh_order_totals(&mut tx, order_id).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn set_order_discount(
+        &mut self,
+        order_id: i64,
+        discount: Option<model::Discount>,
+    ) -> Result<(), Self::Error> {
+        validate_discount(discount.as_ref())?;
+        self.get_quote(order_id).await?;
+
+        let mut tx = self.connection.begin().await?;
+        sqlx::query(sql_stmt::UPDATE_ORDER_DISCOUNT)
+            .bind(discount.as_ref().map(|d| d.kind()))
+            .bind(discount.as_ref().map(|d| d.value().to_string()))
+            .bind(order_id)
+            .execute(&mut *tx)
+            .await?;
+
+        refresh_order_totals(&mut tx, order_id).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn get_order_lines(
+        &mut self,
+        order_id: i64,
+    ) -> Result<Vec<model::Record<model::OrderLineDetails>>, Self::Error> {
+        let mut conn = self.connection.acquire().await?;
+        order_lines(&mut conn, order_id).await
+    }
+
+    async fn set_order_status(
+        &mut self,
+        order_id: i64,
+        change: &model::OrderStatusChange,
+    ) -> Result<(), Self::Error> {
+        let order_status = change.order_status.clone();
+        let Some(order) = self.get_order(order_id).await? else {
+            return Err(super::Error::NotFound(format!("order {}", order_id)));
+        };
+
+        if !order_status.allowed_priors().contains(&order.data.status) {
+            return Err(super::Error::BadInput(
+                "bad order status transition".to_string(),
+            ));
+        }
+
+        if order_status == model::OrderStatus::Done
+            && order.data.balance_due > 0
+            && !change.override_unpaid
+        {
+            return Err(super::Error::BadInput(format!(
+                "order {} has {} outstanding",
+                order_id, order.data.balance_due
+            )));
+        }
+
+        let issues_invoice = order_status == model::OrderStatus::Sold;
+
+        let mut tx = self.connection.begin().await?;
+        let result = sqlx::query(sql_stmt::UPDATE_ORDER_STATUS)
+            .bind(String::from(order_status))
+            .bind(order_id)
+            .bind(String::from(order.data.status))
+            .execute(&mut *tx)
+            .await?;
+
+        match result.rows_affected() {
+            1 => {
+                // built after the status change so the lines and totals can't move under
+                // it, and numbered alongside it so a failed sale never burns a number
+                if issues_invoice {
+                    let invoice = invoice::build_invoice(&mut tx, order_id).await?;
+                    let sequence = change
+                        .invoice_sequence
+                        .as_deref()
+                        .unwrap_or(model::DEFAULT_INVOICE_SEQUENCE);
+                    invoice::issue_invoice(&mut tx, sequence, invoice).await?;
+                }
+                tx.commit().await?;
+                Ok(())
+            }
+            0 => Err(super::Error::BadInput(
+                "order status changed concurrently".to_string(),
+            )),
+            n => Err(super::Error::ProviderFailure(format!(
+                "{} rows affected, expected 1 or 0",
+                n
+            ))),
+        }
+    }
+
+    async fn record_payment(
+        &mut self,
+        order_id: i64,
+        method: model::PaymentMethod,
+        amount: Decimal,
+        reference: Option<String>,
+        received_at: Option<DateTime<Utc>>,
+    ) -> Result<model::Record<model::PaymentDetails>, Self::Error> {
+        let Some(order) = self.get_order(order_id).await? else {
+            return Err(super::Error::NotFound(format!("order {}", order_id)));
+        };
+
+        if order.data.status == model::OrderStatus::Done {
+            return Err(super::Error::BadInput(format!(
+                "order {} is already done",
+                order_id
+            )));
+        }
+
+        let amount_minor = model::round_minor(amount, model::minor_units(&order.data.currency));
+        if amount_minor <= 0 {
+            return Err(super::Error::BadInput(
+                "payment amount must be positive".to_string(),
+            ));
+        }
+        if order.data.status != model::OrderStatus::Quote && amount_minor > order.data.balance_due {
+            return Err(super::Error::BadInput(format!(
+                "payment exceeds the {} due on order {}",
+                order.data.balance_due, order_id
+            )));
+        }
+
+        let payment = model::PaymentDetails {
+            order_id,
+            method,
+            amount,
+            reference,
+            received_at: received_at.unwrap_or_else(Utc::now),
+            is_deposit: order.data.status == model::OrderStatus::Quote,
+        };
+
+        let mut conn = self.connection.acquire().await?;
+        let result = sqlx::query(sql_stmt::INSERT_PAYMENT)
+            .bind(order_id)
+            .bind(String::from(payment.method.clone()))
+            .bind(payment.amount.to_string())
+            .bind(&payment.reference)
+            .bind(payment.received_at)
+            .bind(payment.is_deposit)
+            .execute(&mut *conn)
+            .await?;
+
+        Ok(payment.to_record(result.last_insert_rowid()))
+    }
+
+    async fn get_payments(
+        &mut self,
+        order_id: i64,
+    ) -> Result<Vec<model::Record<model::PaymentDetails>>, Self::Error> {
+        let mut conn = self.connection.acquire().await?;
+        let mut rows = sqlx::query(sql_stmt::SELECT_PAYMENTS)
+            .bind(order_id)
+            .fetch(&mut *conn);
+
+        let mut records = Vec::new();
+        while let Some(row) = rows.try_next().await? {
+            let method: String = row.try_get("method")?;
+            records.push(
+                model::PaymentDetails {
+                    order_id: row.try_get("orderId")?,
+                    method: method.parse().map_err(super::Error::ProviderFailure)?,
+                    amount: super::get_decimal(&row, "amount")?,
+                    reference: row.try_get("reference")?,
+                    received_at: row.try_get("receivedAt")?,
+                    is_deposit: row.try_get("isDeposit")?,
+                }
+                .to_record(row.try_get("id")?),
+            );
+        }
+
+        Ok(records)
+    }
+}
+
+fn validate_discount(discount: Option<&model::Discount>) -> Result<(), super::Error> {
+    discount
+        .map_or(Ok(()), |d| d.validate())
+        .map_err(super::Error::BadInput)
+}
+
+impl SqliteProvider {
+    /// Fetches an order that can still be edited
+    async fn get_quote(
+        &mut self,
+        order_id: i64,
+    ) -> Result<model::Record<model::OrderDetails>, super::Error> {
+        let Some(order) = self.get_order(order_id).await? else {
+            return Err(super::Error::NotFound(format!("order {}", order_id)));
+        };
+
+        if order.data.status != model::OrderStatus::Quote {
+            return Err(super::Error::BadInput(format!(
+                "order {} is no longer a quote",
+                order_id
+            )));
+        }
+
+        Ok(order)
+    }
+
+    /// Rate converting `from` into the order currency, locking the current rate on first use
+    async fn lock_exchange_rate(
+        &mut self,
+        order_id: i64,
+        from: &str,
+        to: &str,
+    ) -> Result<Decimal, super::Error> {
+        let mut conn = self.connection.acquire().await?;
+        let locked = sqlx::query(sql_stmt::SELECT_LOCKED_RATE)
+            .bind(order_id)
+            .bind(from)
+            .fetch_optional(&mut *conn)
+            .await?;
+        drop(conn);
+
+        if let Some(row) = locked {
+            return super::get_decimal(&row, "rate");
+        }
+
+        let rate = self.get_conversion_rate(from, to).await?;
+
+        let mut conn = self.connection.acquire().await?;
+        sqlx::query(sql_stmt::LOCK_RATE)
+            .bind(order_id)
+            .bind(from)
+            .bind(to)
+            .bind(rate.to_string())
+            .execute(&mut *conn)
+            .await?;
+
+        // Another request may have locked a rate first, theirs stands
+        let row = sqlx::query(sql_stmt::SELECT_LOCKED_RATE)
+            .bind(order_id)
+            .bind(from)
+            .fetch_one(&mut *conn)
+            .await?;
+        super::get_decimal(&row, "rate")
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::{provider::SqliteProvider, service::order::OrderService};
+    use rust_decimal::Decimal;
+
+    use crate::{
+        model,
+        provider::SqliteProvider,
+        service::{
+            currency::ExchangeRateService, invoice::InvoiceService, order::OrderService,
+            price_list::PriceListService, product::ProductService, tax::TaxService,
+        },
+    };
 
     #[tokio::test]
     async fn test_create_order() {
         let mut provider = SqliteProvider::new_memory().await.unwrap();
         OrderService::init_provider(&mut provider).await.unwrap();
-        let result = provider.create_order().await.unwrap();
+        let result = provider.create_order(None, None, "USD").await.unwrap();
 
         assert_eq!(result, 1);
     }
@@ -55,4 +789,305 @@ mod test {
         //       marker for the end user
         todo!()
     }
+
+    fn change(order_status: model::OrderStatus, override_unpaid: bool) -> model::OrderStatusChange {
+        model::OrderStatusChange {
+            order_status,
+            override_unpaid,
+            invoice_sequence: None,
+        }
+    }
+
+    async fn setup() -> SqliteProvider {
+        let mut provider = SqliteProvider::new_memory().await.unwrap();
+        OrderService::init_provider(&mut provider).await.unwrap();
+        ProductService::init_provider(&mut provider).await.unwrap();
+        PriceListService::init_provider(&mut provider)
+            .await
+            .unwrap();
+        TaxService::init_provider(&mut provider).await.unwrap();
+        ExchangeRateService::init_provider(&mut provider)
+            .await
+            .unwrap();
+        InvoiceService::init_provider(&mut provider).await.unwrap();
+
+        let tax_class_id = provider
+            .create_tax_class("standard", &Decimal::new(2, 1))
+            .await
+            .unwrap();
+        provider
+            .create_product(&model::ProductDetails {
+                sku: "SKU-1".to_string(),
+                description: "widget".to_string(),
+                base_price: Decimal::new(1000, 2),
+                tax_class_id: Some(tax_class_id),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        provider
+            .create_product(&model::ProductDetails {
+                sku: "SKU-2".to_string(),
+                description: "untaxed widget".to_string(),
+                base_price: Decimal::new(500, 2),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        provider
+    }
+
+    #[tokio::test]
+    async fn test_add_priced_order_line() {
+        let mut provider = setup().await;
+
+        let list_id = provider
+            .create_price_list("wholesale", "USD")
+            .await
+            .unwrap();
+        provider
+            .set_product_price(model::PriceListEntryDetails {
+                price_list_id: list_id,
+                product_id: 1,
+                price: Decimal::new(899, 2),
+                effective_from: None,
+                effective_to: None,
+            })
+            .await
+            .unwrap();
+
+        let retail_order = provider.create_order(None, None, "USD").await.unwrap();
+        let wholesale_order = provider
+            .create_order(None, Some(list_id), "USD")
+            .await
+            .unwrap();
+
+        let line = provider
+            .add_order_line(retail_order, 1, 2, None)
+            .await
+            .unwrap();
+        assert_eq!(line.data.unit_price, Decimal::new(1000, 2));
+        assert_eq!(line.data.tax_rate, Decimal::new(2, 1));
+
+        let line = provider
+            .add_order_line(wholesale_order, 1, 2, None)
+            .await
+            .unwrap();
+        assert_eq!(line.data.unit_price, Decimal::new(899, 2));
+
+        assert!(provider
+            .add_order_line(wholesale_order, 3, 1, None)
+            .await
+            .is_err());
+        assert!(provider.add_order_line(42, 1, 1, None).await.is_err());
+
+        let lines = provider.get_order_lines(wholesale_order).await.unwrap();
+        assert_eq!(lines.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_totals_follow_line_changes() {
+        let mut provider = setup().await;
+        let order_id = provider.create_order(None, None, "USD").await.unwrap();
+
+        let taxed = provider.add_order_line(order_id, 1, 2, None).await.unwrap();
+        provider
+            .add_order_line(
+                order_id,
+                2,
+                1,
+                Some(model::Discount::Fixed(Decimal::new(100, 2))),
+            )
+            .await
+            .unwrap();
+
+        let order = provider.get_order(order_id).await.unwrap().unwrap();
+        assert_eq!(
+            order.data.totals,
+            model::OrderTotals {
+                subtotal: 2500,
+                discount: 100,
+                tax: 400,
+                total: 2800,
+            }
+        );
+
+        provider
+            .set_order_discount(order_id, Some(model::Discount::Percent(Decimal::from(10))))
+            .await
+            .unwrap();
+        let order = provider.get_order(order_id).await.unwrap().unwrap();
+        assert_eq!(order.data.totals.discount, 340);
+        assert_eq!(order.data.totals.tax, 360);
+        assert_eq!(order.data.totals.total, 2520);
+
+        provider
+            .remove_order_line(order_id, taxed.id)
+            .await
+            .unwrap();
+        let order = provider.get_order(order_id).await.unwrap().unwrap();
+        assert_eq!(order.data.totals.subtotal, 500);
+        assert_eq!(order.data.totals.total, 360);
+
+        assert!(provider
+            .set_order_line_discount(
+                order_id,
+                taxed.id,
+                Some(model::Discount::Percent(Decimal::from(5)))
+            )
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_exchange_rate_locked_on_quote() {
+        let mut provider = setup().await;
+        provider
+            .set_exchange_rate("USD", "EUR", &Decimal::new(9, 1))
+            .await
+            .unwrap();
+
+        let order_id = provider.create_order(None, None, "eur").await.unwrap();
+        let line = provider.add_order_line(order_id, 2, 1, None).await.unwrap();
+        assert_eq!(line.data.unit_price, Decimal::new(45, 1));
+
+        // later rate changes don't touch the quote
+        provider
+            .set_exchange_rate("USD", "EUR", &Decimal::new(5, 1))
+            .await
+            .unwrap();
+        let line = provider.add_order_line(order_id, 2, 1, None).await.unwrap();
+        assert_eq!(line.data.unit_price, Decimal::new(45, 1));
+
+        let order = provider.get_order(order_id).await.unwrap().unwrap();
+        assert_eq!(order.data.currency, "EUR");
+        assert_eq!(order.data.exchange_rates.len(), 1);
+        assert_eq!(order.data.exchange_rates[0].rate, Decimal::new(9, 1));
+        assert_eq!(order.data.totals.total, 900);
+
+        let order_id = provider.create_order(None, None, "JPY").await.unwrap();
+        assert!(provider.add_order_line(order_id, 2, 1, None).await.is_err());
+        assert!(provider.create_order(None, None, "euro").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_payments_gate_done() {
+        let mut provider = setup().await;
+        let order_id = provider.create_order(None, None, "USD").await.unwrap();
+        provider.add_order_line(order_id, 2, 2, None).await.unwrap();
+
+        let deposit = provider
+            .record_payment(
+                order_id,
+                model::PaymentMethod::Card,
+                Decimal::new(250, 2),
+                Some("auth-1".to_string()),
+                None,
+            )
+            .await
+            .unwrap();
+        assert!(deposit.data.is_deposit);
+
+        provider
+            .set_order_status(order_id, &change(model::OrderStatus::Sold, false))
+            .await
+            .unwrap();
+
+        // more than what's left over
+        assert!(provider
+            .record_payment(
+                order_id,
+                model::PaymentMethod::Cash,
+                Decimal::new(1000, 2),
+                None,
+                None
+            )
+            .await
+            .is_err());
+
+        let payment = provider
+            .record_payment(
+                order_id,
+                model::PaymentMethod::Cash,
+                Decimal::new(500, 2),
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert!(!payment.data.is_deposit);
+
+        let order = provider.get_order(order_id).await.unwrap().unwrap();
+        assert_eq!(order.data.amount_paid, 750);
+        assert_eq!(order.data.balance_due, 250);
+
+        assert!(provider
+            .set_order_status(order_id, &change(model::OrderStatus::Done, false))
+            .await
+            .is_err());
+
+        provider
+            .record_payment(
+                order_id,
+                model::PaymentMethod::BankTransfer,
+                Decimal::new(250, 2),
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        provider
+            .set_order_status(order_id, &change(model::OrderStatus::Done, false))
+            .await
+            .unwrap();
+
+        assert_eq!(provider.get_payments(order_id).await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_deposit_before_lines() {
+        let mut provider = setup().await;
+        let order_id = provider.create_order(None, None, "USD").await.unwrap();
+
+        // nothing is priced yet, the deposit still stands
+        let deposit = provider
+            .record_payment(
+                order_id,
+                model::PaymentMethod::Card,
+                Decimal::new(250, 2),
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert!(deposit.data.is_deposit);
+        let order = provider.get_order(order_id).await.unwrap().unwrap();
+        assert_eq!(order.data.balance_due, -250);
+
+        provider.add_order_line(order_id, 2, 2, None).await.unwrap();
+        let order = provider.get_order(order_id).await.unwrap().unwrap();
+        assert_eq!(order.data.balance_due, 750);
+    }
+
+    #[tokio::test]
+    async fn test_override_unpaid_done() {
+        let mut provider = setup().await;
+        let order_id = provider.create_order(None, None, "USD").await.unwrap();
+        provider.add_order_line(order_id, 2, 1, None).await.unwrap();
+
+        assert!(provider
+            .set_order_status(order_id, &change(model::OrderStatus::Done, true))
+            .await
+            .is_err());
+
+        provider
+            .set_order_status(order_id, &change(model::OrderStatus::Sold, false))
+            .await
+            .unwrap();
+        provider
+            .set_order_status(order_id, &change(model::OrderStatus::Done, true))
+            .await
+            .unwrap();
+    }
 }
@@ -0,0 +1,451 @@
+use std::fmt::Display;
+
+use axum::http::StatusCode;
+use chrono::{Duration, NaiveDate, NaiveTime, Utc};
+use sqlx::{sqlite::SqliteRow, Row, SqliteConnection};
+
+use crate::{
+    model::{self, ToRecord},
+    provider::SqliteProvider,
+    service::{fulfillment::FulfillmentService, inventory::InventoryService},
+};
+
+pub trait PickupService {
+    type Error: Display + Into<StatusCode>;
+
+    async fn init_provider(&mut self) -> Result<(), Self::Error>;
+
+    async fn create_pickup_slot(&mut self, slot: &model::NewPickupSlot)
+        -> Result<i64, Self::Error>;
+
+    async fn get_pickup_slot(
+        &mut self,
+        pickup_slot_id: i64,
+    ) -> Result<Option<model::Record<model::PickupSlotDetails>>, Self::Error>;
+
+    /// Slots at a location starting on `date` (UTC), with the pick-ups booked into them
+    async fn get_pickup_day(
+        &mut self,
+        location_id: i64,
+        date: NaiveDate,
+    ) -> Result<Vec<model::Record<model::PickupSlotDetails>>, Self::Error>;
+
+    /// Books a pick-up into a slot at the location it's collected from, booking again
+    /// reschedules it.
+    ///
+    /// The slot must not have ended yet or be full.
+    async fn book_pickup_slot(
+        &mut self,
+        fulfillment_id: i64,
+        pickup_slot_id: i64,
+    ) -> Result<(), Self::Error>;
+}
+
+impl PickupService for SqliteProvider {
+    type Error = super::Error;
+
+    async fn init_provider(&mut self) -> Result<(), Self::Error> {
+        let mut conn = self.connection.acquire().await?;
+        sqlx::query(sql_stmt::CREATE_PICKUP_SLOT_TABLE)
+            .execute(&mut *conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn create_pickup_slot(
+        &mut self,
+        slot: &model::NewPickupSlot,
+    ) -> Result<i64, Self::Error> {
+        if slot.ends_at <= slot.starts_at {
+            return Err(super::Error::BadInput(
+                "a pickup slot must end after it starts".to_string(),
+            ));
+        }
+        if slot.capacity <= 0 {
+            return Err(super::Error::BadInput(
+                "pickup slot capacity must be positive".to_string(),
+            ));
+        }
+        if self.get_location(slot.location_id).await?.is_none() {
+            return Err(super::Error::NotFound(format!(
+                "location {}",
+                slot.location_id
+            )));
+        }
+
+        let mut conn = self.connection.acquire().await?;
+        let result = sqlx::query(sql_stmt::INSERT_PICKUP_SLOT)
+            .bind(slot.location_id)
+            .bind(slot.starts_at)
+            .bind(slot.ends_at)
+            .bind(slot.capacity)
+            .execute(&mut *conn)
+            .await?;
+
+        Ok(result.last_insert_rowid())
+    }
+
+    async fn get_pickup_slot(
+        &mut self,
+        pickup_slot_id: i64,
+    ) -> Result<Option<model::Record<model::PickupSlotDetails>>, Self::Error> {
+        let mut conn = self.connection.acquire().await?;
+        let result = sqlx::query(sql_stmt::SELECT_PICKUP_SLOT)
+            .bind(pickup_slot_id)
+            .fetch_optional(&mut *conn)
+            .await?;
+
+        match result {
+            Some(row) => Ok(Some(slot_from_row(&mut conn, &row).await?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn get_pickup_day(
+        &mut self,
+        location_id: i64,
+        date: NaiveDate,
+    ) -> Result<Vec<model::Record<model::PickupSlotDetails>>, Self::Error> {
+        let starts_from = date.and_time(NaiveTime::MIN).and_utc();
+        let mut conn = self.connection.acquire().await?;
+        let rows = sqlx::query(sql_stmt::SELECT_PICKUP_SLOTS_BY_DAY)
+            .bind(location_id)
+            .bind(starts_from)
+            .bind(starts_from + Duration::days(1))
+            .fetch_all(&mut *conn)
+            .await?;
+
+        let mut slots = Vec::new();
+        for row in rows {
+            slots.push(slot_from_row(&mut conn, &row).await?);
+        }
+
+        Ok(slots)
+    }
+
+    async fn book_pickup_slot(
+        &mut self,
+        fulfillment_id: i64,
+        pickup_slot_id: i64,
+    ) -> Result<(), Self::Error> {
+        let Some(fulfillment) = self.get_fulfillment(&fulfillment_id).await? else {
+            return Err(super::Error::NotFound(format!(
+                "fulfillment {}",
+                fulfillment_id
+            )));
+        };
+        if fulfillment.data.fulfillment_type != model::FulfillmentType::StockPickUp {
+            return Err(super::Error::BadInput(format!(
+                "fulfillment {} isn't a pick-up",
+                fulfillment_id
+            )));
+        }
+        if fulfillment.data.status == model::FulfillmentStatus::Fulfilled {
+            return Err(super::Error::BadInput(format!(
+                "fulfillment {} has already been collected",
+                fulfillment_id
+            )));
+        }
+
+        let Some(slot) = self.get_pickup_slot(pickup_slot_id).await? else {
+            return Err(super::Error::NotFound(format!(
+                "pickup slot {}",
+                pickup_slot_id
+            )));
+        };
+        let location_id = fulfillment
+            .data
+            .from_location_id
+            .unwrap_or(model::DEFAULT_LOCATION_ID);
+        if slot.data.location_id != location_id {
+            return Err(super::Error::BadInput(format!(
+                "pickup slot {} isn't at location {}",
+                pickup_slot_id, location_id
+            )));
+        }
+        if slot.data.ends_at <= Utc::now() {
+            return Err(super::Error::BadInput(format!(
+                "pickup slot {} is over",
+                pickup_slot_id
+            )));
+        }
+
+        let mut tx = self.connection.begin().await?;
+        let row = sqlx::query(sql_stmt::SELECT_BOOKED_COUNT)
+            .bind(pickup_slot_id)
+            .bind(fulfillment_id)
+            .fetch_one(&mut *tx)
+            .await?;
+        let booked: i64 = row.try_get("booked")?;
+        if booked >= slot.data.capacity {
+            return Err(super::Error::BadInput(format!(
+                "pickup slot {} is full",
+                pickup_slot_id
+            )));
+        }
+
+        let result = sqlx::query(sql_stmt::UPDATE_FULFILLMENT_PICKUP_SLOT)
+            .bind(pickup_slot_id)
+            .bind(fulfillment_id)
+            .execute(&mut *tx)
+            .await?;
+        if result.rows_affected() != 1 {
+            return Err(super::Error::BadInput(format!(
+                "fulfillment {} changed while booking",
+                fulfillment_id
+            )));
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+async fn slot_from_row(
+    conn: &mut SqliteConnection,
+    row: &SqliteRow,
+) -> Result<model::Record<model::PickupSlotDetails>, super::Error> {
+    let pickup_slot_id: i64 = row.try_get("id")?;
+    let rows = sqlx::query(sql_stmt::SELECT_BOOKED_FULFILLMENTS)
+        .bind(pickup_slot_id)
+        .fetch_all(&mut *conn)
+        .await?;
+
+    let mut fulfillment_ids = Vec::new();
+    for booked in rows {
+        fulfillment_ids.push(booked.try_get("id")?);
+    }
+
+    Ok(model::PickupSlotDetails {
+        location_id: row.try_get("locationId")?,
+        starts_at: row.try_get("startsAt")?,
+        ends_at: row.try_get("endsAt")?,
+        capacity: row.try_get("capacity")?,
+        fulfillment_ids,
+    }
+    .to_record(pickup_slot_id))
+}
+
+mod sql_stmt {
+    pub const CREATE_PICKUP_SLOT_TABLE: &str = r#"
+        CREATE TABLE IF NOT EXISTS pickupSlots (
+            id INTEGER NOT NULL UNIQUE PRIMARY KEY,
+            locationId INTEGER NOT NULL,
+            startsAt TEXT NOT NULL,
+            endsAt TEXT NOT NULL,
+            capacity INTEGER NOT NULL
+        );
+    "#;
+
+    pub const INSERT_PICKUP_SLOT: &str = r#"
+        INSERT INTO pickupSlots (locationId, startsAt, endsAt, capacity)
+        VALUES ( $1, $2, $3, $4 );
+    "#;
+
+    pub const SELECT_PICKUP_SLOT: &str = r#"
+        SELECT * FROM pickupSlots WHERE id=$1;
+    "#;
+
+    pub const SELECT_PICKUP_SLOTS_BY_DAY: &str = r#"
+        SELECT * FROM pickupSlots
+        WHERE locationId=$1 AND startsAt >= $2 AND startsAt < $3
+        ORDER BY startsAt, id;
+    "#;
+
+    pub const SELECT_BOOKED_FULFILLMENTS: &str = r#"
+        SELECT id FROM fulfillments WHERE pickupSlotId=$1 ORDER BY id;
+    "#;
+
+    /// Rebooking into the same slot doesn't count against it, nor do collected pick-ups
+    pub const SELECT_BOOKED_COUNT: &str = r#"
+        SELECT COUNT(*) AS booked FROM fulfillments
+        WHERE pickupSlotId=$1 AND id != $2 AND fulfillmentStatus != 'Fulfilled';
+    "#;
+
+    pub const UPDATE_FULFILLMENT_PICKUP_SLOT: &str = r#"
+        UPDATE fulfillments SET pickupSlotId = $1
+        WHERE id = $2 AND fulfillmentStatus != 'Fulfilled';
+    "#;
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{Duration, Utc};
+
+    use crate::{
+        model,
+        provider::SqliteProvider,
+        service::{
+            fulfillment::FulfillmentService, inventory::InventoryService,
+            line_item::LineItemService, pickup::PickupService, product::ProductService,
+        },
+    };
+
+    async fn setup() -> SqliteProvider {
+        let mut provider = SqliteProvider::new_memory().await.unwrap();
+        FulfillmentService::init_provider(&mut provider)
+            .await
+            .unwrap();
+        LineItemService::init_provider(&mut provider).await.unwrap();
+        ProductService::init_provider(&mut provider).await.unwrap();
+        InventoryService::init_provider(&mut provider)
+            .await
+            .unwrap();
+        PickupService::init_provider(&mut provider).await.unwrap();
+        provider
+    }
+
+    async fn pickup(provider: &mut SqliteProvider) -> i64 {
+        provider
+            .create_fulfillment(&model::NewFulfillment::from(
+                model::FulfillmentType::StockPickUp,
+            ))
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_pickup_slot_capacity() {
+        let mut provider = setup().await;
+        let starts_at = Utc::now() + Duration::hours(2);
+        let slot = model::NewPickupSlot {
+            location_id: model::DEFAULT_LOCATION_ID,
+            starts_at,
+            ends_at: starts_at + Duration::hours(1),
+            capacity: 1,
+        };
+        assert!(provider
+            .create_pickup_slot(&model::NewPickupSlot {
+                ends_at: starts_at,
+                ..slot.clone()
+            })
+            .await
+            .is_err());
+        let first_slot = provider.create_pickup_slot(&slot).await.unwrap();
+        let second_slot = provider
+            .create_pickup_slot(&model::NewPickupSlot {
+                starts_at: slot.ends_at,
+                ends_at: slot.ends_at + Duration::hours(1),
+                ..slot.clone()
+            })
+            .await
+            .unwrap();
+
+        let first = pickup(&mut provider).await;
+        let second = pickup(&mut provider).await;
+        provider.book_pickup_slot(first, first_slot).await.unwrap();
+        // booking the same slot again doesn't take a second place
+        provider.book_pickup_slot(first, first_slot).await.unwrap();
+        assert!(provider.book_pickup_slot(second, first_slot).await.is_err());
+
+        // rescheduling frees the first slot up
+        provider.book_pickup_slot(first, second_slot).await.unwrap();
+        provider.book_pickup_slot(second, first_slot).await.unwrap();
+
+        let day = provider
+            .get_pickup_day(model::DEFAULT_LOCATION_ID, starts_at.date_naive())
+            .await
+            .unwrap();
+        let booked: Vec<Vec<i64>> = day
+            .iter()
+            .filter(|s| s.id == first_slot || s.id == second_slot)
+            .map(|s| s.data.fulfillment_ids.clone())
+            .collect();
+        assert!(booked.contains(&vec![second]));
+
+        let fulfillment = provider.get_fulfillment(&first).await.unwrap().unwrap();
+        assert_eq!(fulfillment.data.pickup_slot_id, Some(second_slot));
+    }
+
+    #[tokio::test]
+    async fn test_fulfilled_before_slot() {
+        let mut provider = setup().await;
+        let starts_at = Utc::now() + Duration::hours(2);
+        let slot_id = provider
+            .create_pickup_slot(&model::NewPickupSlot {
+                location_id: model::DEFAULT_LOCATION_ID,
+                starts_at,
+                ends_at: starts_at + Duration::hours(1),
+                capacity: 1,
+            })
+            .await
+            .unwrap();
+
+        let fulfillment_id = pickup(&mut provider).await;
+        provider
+            .create_line_item(fulfillment_id, 1, 1, None)
+            .await
+            .unwrap();
+        provider
+            .book_pickup_slot(fulfillment_id, slot_id)
+            .await
+            .unwrap();
+        for status in [
+            model::FulfillmentStatus::Initialized,
+            model::FulfillmentStatus::InProgress,
+        ] {
+            provider
+                .set_fulfillment_status(&fulfillment_id, &status.into())
+                .await
+                .unwrap();
+        }
+
+        assert!(provider
+            .set_fulfillment_status(&fulfillment_id, &model::FulfillmentStatus::Fulfilled.into())
+            .await
+            .is_err());
+        provider
+            .set_fulfillment_status(
+                &fulfillment_id,
+                &model::FulfillmentStatusChange {
+                    fulfillment_status: model::FulfillmentStatus::Fulfilled,
+                    override_pickup_slot: true,
+                },
+            )
+            .await
+            .unwrap();
+
+        // collected, so there's nothing left to reschedule and its place is free again
+        assert!(provider
+            .book_pickup_slot(fulfillment_id, slot_id)
+            .await
+            .is_err());
+        let next = pickup(&mut provider).await;
+        provider.book_pickup_slot(next, slot_id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_pickup_day() {
+        let mut provider = setup().await;
+        let day = (Utc::now() + Duration::days(2)).date_naive();
+        let midnight = day.and_hms_opt(0, 0, 0).unwrap().and_utc();
+        let mut slot_ids = Vec::new();
+        for starts_at in [
+            midnight - Duration::hours(1),
+            midnight,
+            midnight + Duration::hours(23),
+            midnight + Duration::days(1),
+        ] {
+            slot_ids.push(
+                provider
+                    .create_pickup_slot(&model::NewPickupSlot {
+                        location_id: model::DEFAULT_LOCATION_ID,
+                        starts_at,
+                        ends_at: starts_at + Duration::minutes(30),
+                        capacity: 1,
+                    })
+                    .await
+                    .unwrap(),
+            );
+        }
+
+        let slots: Vec<i64> = provider
+            .get_pickup_day(model::DEFAULT_LOCATION_ID, day)
+            .await
+            .unwrap()
+            .iter()
+            .map(|s| s.id)
+            .collect();
+        assert_eq!(slots, slot_ids[1..3]);
+    }
+}
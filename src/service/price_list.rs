@@ -0,0 +1,385 @@
+use std::fmt::Display;
+
+use axum::http::StatusCode;
+use chrono::NaiveDate;
+use futures::TryStreamExt;
+use log::warn;
+use rust_decimal::Decimal;
+use sqlx::Row;
+
+use crate::{
+    model::{self, ToRecord},
+    provider::SqliteProvider,
+};
+
+pub trait PriceListService {
+    type Error: Display + Into<StatusCode>;
+
+    async fn init_provider(&mut self) -> Result<(), Self::Error>;
+
+    async fn create_price_list(&mut self, name: &str, currency: &str) -> Result<i64, Self::Error>;
+
+    async fn get_price_list(
+        &mut self,
+        price_list_id: i64,
+    ) -> Result<Option<model::Record<model::PriceListDetails>>, Self::Error>;
+
+    async fn set_product_price(
+        &mut self,
+        entry: model::PriceListEntryDetails,
+    ) -> Result<i64, Self::Error>;
+
+    async fn get_price_list_entries(
+        &mut self,
+        price_list_id: i64,
+    ) -> Result<Vec<model::Record<model::PriceListEntryDetails>>, Self::Error>;
+
+    /// Resolves the unit price of a product on `date`.
+    ///
+    /// The entry on `price_list_id` with the latest effective start wins, falling back to
+    /// the product base price in the base currency when the list has no entry in effect.
+    async fn resolve_price(
+        &mut self,
+        product_id: i64,
+        price_list_id: Option<i64>,
+        date: NaiveDate,
+    ) -> Result<model::Price, Self::Error>;
+}
+
+impl PriceListService for SqliteProvider {
+    type Error = super::Error;
+
+    async fn init_provider(&mut self) -> Result<(), Self::Error> {
+        let mut conn = self.connection.acquire().await?;
+
+        sqlx::query(sql_stmt::CREATE_PRICE_LIST_TABLE)
+            .execute(&mut *conn)
+            .await?;
+        sqlx::query(sql_stmt::CREATE_ENTRY_TABLE)
+            .execute(&mut *conn)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn create_price_list(&mut self, name: &str, currency: &str) -> Result<i64, Self::Error> {
+        let currency = model::parse_currency_code(currency).map_err(super::Error::BadInput)?;
+        let mut conn = self.connection.acquire().await?;
+
+        let result = sqlx::query(sql_stmt::INSERT_PRICE_LIST)
+            .bind(name)
+            .bind(currency)
+            .execute(&mut *conn)
+            .await?;
+
+        Ok(result.last_insert_rowid())
+    }
+
+    async fn get_price_list(
+        &mut self,
+        price_list_id: i64,
+    ) -> Result<Option<model::Record<model::PriceListDetails>>, Self::Error> {
+        let mut conn = self.connection.acquire().await?;
+
+        let result = sqlx::query(sql_stmt::SELECT_PRICE_LIST)
+            .bind(price_list_id)
+            .fetch_optional(&mut *conn)
+            .await?;
+
+        let Some(row) = result else {
+            warn!("Sql row not found");
+            return Ok(None);
+        };
+
+        Ok(Some(
+            model::PriceListDetails {
+                name: row.try_get("name")?,
+                currency: row.try_get("currency")?,
+            }
+            .to_record(price_list_id),
+        ))
+    }
+
+    async fn set_product_price(
+        &mut self,
+        entry: model::PriceListEntryDetails,
+    ) -> Result<i64, Self::Error> {
+        if entry.price < Decimal::ZERO {
+            return Err(super::Error::BadInput(
+                "price can't be negative".to_string(),
+            ));
+        }
+        if let (Some(from), Some(to)) = (entry.effective_from, entry.effective_to) {
+            if to < from {
+                return Err(super::Error::BadInput(
+                    "effective range ends before it starts".to_string(),
+                ));
+            }
+        }
+
+        let mut conn = self.connection.acquire().await?;
+
+        let result = sqlx::query(sql_stmt::INSERT_ENTRY)
+            .bind(entry.price_list_id)
+            .bind(entry.product_id)
+            .bind(entry.price.to_string())
+            .bind(entry.effective_from)
+            .bind(entry.effective_to)
+            .execute(&mut *conn)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(super::Error::BadInput(format!(
+                "can't price product {} on price list {}",
+                entry.product_id, entry.price_list_id
+            )));
+        }
+
+        Ok(result.last_insert_rowid())
+    }
+
+    async fn get_price_list_entries(
+        &mut self,
+        price_list_id: i64,
+    ) -> Result<Vec<model::Record<model::PriceListEntryDetails>>, Self::Error> {
+        let mut conn = self.connection.acquire().await?;
+
+        let mut rows = sqlx::query(sql_stmt::SELECT_ENTRIES)
+            .bind(price_list_id)
+            .fetch(&mut *conn);
+
+        let mut records = Vec::new();
+
+        while let Some(row) = rows.try_next().await? {
+            records.push(
+                model::PriceListEntryDetails {
+                    price_list_id: row.try_get("priceListId")?,
+                    product_id: row.try_get("productId")?,
+                    price: super::get_decimal(&row, "price")?,
+                    effective_from: row.try_get("effectiveFrom")?,
+                    effective_to: row.try_get("effectiveTo")?,
+                }
+                .to_record(row.try_get("id")?),
+            );
+        }
+
+        Ok(records)
+    }
+
+    async fn resolve_price(
+        &mut self,
+        product_id: i64,
+        price_list_id: Option<i64>,
+        date: NaiveDate,
+    ) -> Result<model::Price, Self::Error> {
+        let mut conn = self.connection.acquire().await?;
+
+        let result = sqlx::query(sql_stmt::RESOLVE_PRICE)
+            .bind(product_id)
+            .bind(price_list_id)
+            .bind(date)
+            .bind(model::BASE_CURRENCY)
+            .fetch_optional(&mut *conn)
+            .await?;
+
+        let Some(row) = result else {
+            return Err(super::Error::ProductNotFound(format!(
+                "no product {} to price",
+                product_id
+            )));
+        };
+
+        Ok(model::Price {
+            amount: super::get_decimal(&row, "price")?,
+            currency: row.try_get("currency")?,
+        })
+    }
+}
+
+mod sql_stmt {
+    pub const CREATE_PRICE_LIST_TABLE: &str = r#"
+        CREATE TABLE IF NOT EXISTS priceLists (
+            id INTEGER NOT NULL UNIQUE PRIMARY KEY,
+            name TEXT NOT NULL UNIQUE,
+            currency TEXT NOT NULL
+        );
+    "#;
+
+    pub const CREATE_ENTRY_TABLE: &str = r#"
+        CREATE TABLE IF NOT EXISTS priceListEntries (
+            id INTEGER NOT NULL UNIQUE PRIMARY KEY,
+            priceListId INTEGER NOT NULL,
+            productId INTEGER NOT NULL,
+            price TEXT NOT NULL,
+            effectiveFrom TEXT,
+            effectiveTo TEXT
+        );
+    "#;
+
+    pub const INSERT_PRICE_LIST: &str = r#"
+        INSERT INTO priceLists (name, currency) VALUES ( $1, $2 );
+    "#;
+
+    pub const SELECT_PRICE_LIST: &str = r#"
+        SELECT id, name, currency FROM priceLists WHERE id=$1;
+    "#;
+
+    pub const INSERT_ENTRY: &str = r#"
+        INSERT INTO priceListEntries (priceListId, productId, price, effectiveFrom, effectiveTo)
+        SELECT $1, $2, $3, $4, $5
+        WHERE EXISTS ( SELECT 1 FROM priceLists WHERE id = $1 )
+        AND EXISTS ( SELECT 1 FROM products WHERE id = $2 );
+    "#;
+
+    pub const SELECT_ENTRIES: &str = r#"
+        SELECT id, priceListId, productId, price, effectiveFrom, effectiveTo
+        FROM priceListEntries
+        WHERE priceListId=$1
+        ORDER BY productId, effectiveFrom;
+    "#;
+
+    // Dates are stored as ISO 8601 text so they compare correctly as strings
+    pub const RESOLVE_PRICE: &str = r#"
+        SELECT price, currency FROM (
+            SELECT
+                priceListEntries.price AS price,
+                priceLists.currency AS currency,
+                0 AS fallback,
+                priceListEntries.effectiveFrom AS effectiveFrom,
+                priceListEntries.id AS id
+            FROM priceListEntries
+            JOIN priceLists ON priceLists.id = priceListEntries.priceListId
+            WHERE priceListEntries.productId = $1
+            AND priceListEntries.priceListId = $2
+            AND (priceListEntries.effectiveFrom IS NULL OR priceListEntries.effectiveFrom <= $3)
+            AND (priceListEntries.effectiveTo IS NULL OR priceListEntries.effectiveTo >= $3)
+            UNION ALL
+            SELECT basePrice, $4, 1, NULL, id FROM products WHERE id = $1
+        )
+        ORDER BY fallback, effectiveFrom IS NULL, effectiveFrom DESC, id DESC
+        LIMIT 1;
+    "#;
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::NaiveDate;
+    use rust_decimal::Decimal;
+
+    use crate::{
+        model,
+        provider::SqliteProvider,
+        service::{price_list::PriceListService, product::ProductService},
+    };
+
+    async fn setup() -> SqliteProvider {
+        let mut provider = SqliteProvider::new_memory().await.unwrap();
+        ProductService::init_provider(&mut provider).await.unwrap();
+        PriceListService::init_provider(&mut provider)
+            .await
+            .unwrap();
+        provider
+            .create_product(&model::ProductDetails {
+                sku: "SKU-1".to_string(),
+                description: "widget".to_string(),
+                base_price: Decimal::new(1000, 2),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        provider
+    }
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_resolve_base_price() {
+        let mut provider = setup().await;
+
+        let price = provider
+            .resolve_price(1, None, date(2024, 1, 1))
+            .await
+            .unwrap();
+        assert_eq!(price.amount, Decimal::new(1000, 2));
+        assert_eq!(price.currency, model::BASE_CURRENCY);
+
+        assert!(provider
+            .resolve_price(2, None, date(2024, 1, 1))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_resolve_effective_override() {
+        let mut provider = setup().await;
+        let list_id = provider
+            .create_price_list("wholesale", "USD")
+            .await
+            .unwrap();
+
+        provider
+            .set_product_price(model::PriceListEntryDetails {
+                price_list_id: list_id,
+                product_id: 1,
+                price: Decimal::new(800, 2),
+                effective_from: None,
+                effective_to: None,
+            })
+            .await
+            .unwrap();
+        provider
+            .set_product_price(model::PriceListEntryDetails {
+                price_list_id: list_id,
+                product_id: 1,
+                price: Decimal::new(750, 2),
+                effective_from: Some(date(2024, 6, 1)),
+                effective_to: Some(date(2024, 6, 30)),
+            })
+            .await
+            .unwrap();
+
+        let cases = [
+            (date(2024, 5, 31), Decimal::new(800, 2)),
+            (date(2024, 6, 1), Decimal::new(750, 2)),
+            (date(2024, 6, 30), Decimal::new(750, 2)),
+            (date(2024, 7, 1), Decimal::new(800, 2)),
+        ];
+        for (on, expected) in cases {
+            let price = provider.resolve_price(1, Some(list_id), on).await.unwrap();
+            assert_eq!(price.amount, expected, "price on {}", on);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reject_bad_entries() {
+        let mut provider = setup().await;
+        let list_id = provider.create_price_list("retail", "USD").await.unwrap();
+
+        let entry = model::PriceListEntryDetails {
+            price_list_id: list_id,
+            product_id: 1,
+            price: Decimal::new(-1, 0),
+            effective_from: None,
+            effective_to: None,
+        };
+        assert!(provider.set_product_price(entry.clone()).await.is_err());
+
+        let entry = model::PriceListEntryDetails {
+            price: Decimal::ONE,
+            effective_from: Some(date(2024, 2, 1)),
+            effective_to: Some(date(2024, 1, 1)),
+            ..entry
+        };
+        assert!(provider.set_product_price(entry.clone()).await.is_err());
+
+        let entry = model::PriceListEntryDetails {
+            product_id: 42,
+            effective_from: None,
+            effective_to: None,
+            ..entry
+        };
+        assert!(provider.set_product_price(entry).await.is_err());
+    }
+}
@@ -1,19 +1,216 @@
+use std::fmt::Display;
+
+use axum::http::StatusCode;
 use log::warn;
-use sqlx::Row;
+use rust_decimal::Decimal;
+use sqlx::{sqlite::SqliteRow, Row, SqliteConnection};
 
 use crate::{
     model::{self, ToRecord},
     provider::SqliteProvider,
+    service::category,
 };
 
 pub trait ProductService {
-    type Error;
+    type Error: Display + Into<StatusCode>;
     async fn init_provider(&mut self) -> Result<(), Self::Error>;
-    async fn create_product(&mut self, sku: &str, description: &str) -> Result<i64, Self::Error>;
+    async fn create_product(&mut self, product: &model::ProductDetails)
+        -> Result<i64, Self::Error>;
     async fn get_product(
         &mut self,
         id: &i64,
     ) -> Result<model::Record<model::ProductDetails>, Self::Error>;
+    /// Adds a barcode to a product, it must pass its check digit and not be on any other
+    /// product
+    async fn add_product_barcode(
+        &mut self,
+        product_id: i64,
+        barcode: &model::Barcode,
+    ) -> Result<(), Self::Error>;
+    /// Every product that isn't a variant, with its variants. A filtered entry keeps all its
+    /// variants when the product itself matches, otherwise only the variants that match.
+    async fn get_catalog(
+        &mut self,
+        filter: &model::ProductFilter,
+    ) -> Result<Vec<model::CatalogEntry>, Self::Error>;
+    /// Full text search over SKU, description and attribute values. Every word has to
+    /// prefix a term and the best matches come first.
+    async fn search_products(
+        &mut self,
+        query: &str,
+    ) -> Result<Vec<model::ProductSearchHit>, Self::Error>;
+    /// Resolves a scanned code to a product by its barcodes, then its SKU
+    async fn find_product_by_code(
+        &mut self,
+        code: &str,
+    ) -> Result<Option<model::Record<model::ProductDetails>>, Self::Error>;
+}
+
+async fn product_from_row(
+    conn: &mut SqliteConnection,
+    row: &SqliteRow,
+) -> Result<model::Record<model::ProductDetails>, super::Error> {
+    let product_id: i64 = row.try_get("id")?;
+    let rows = sqlx::query(
+        r#"
+            SELECT symbology, code FROM productBarcodes WHERE productId=?1 ORDER BY id;
+        "#,
+    )
+    .bind(product_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut barcodes = Vec::new();
+    for barcode in rows {
+        let symbology: String = barcode.try_get("symbology")?;
+        barcodes.push(model::Barcode {
+            symbology: symbology.parse().map_err(super::Error::ProviderFailure)?,
+            code: barcode.try_get("code")?,
+        });
+    }
+
+    let rows = sqlx::query(
+        r#"
+            SELECT productId, quantity FROM kitComponents WHERE kitId=?1 ORDER BY productId;
+        "#,
+    )
+    .bind(product_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut components = Vec::new();
+    for component in rows {
+        components.push(model::KitComponent {
+            product_id: component.try_get("productId")?,
+            quantity: component.try_get("quantity")?,
+        });
+    }
+
+    let rows = sqlx::query(
+        r#"
+            SELECT name, factor FROM productUnits WHERE productId=?1 ORDER BY factor, name;
+        "#,
+    )
+    .bind(product_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut units = Vec::new();
+    for unit in rows {
+        units.push(model::UnitOfMeasure {
+            name: unit.try_get("name")?,
+            factor: unit.try_get("factor")?,
+        });
+    }
+
+    let options: String = row.try_get("options")?;
+    let option_values: String = row.try_get("optionValues")?;
+    let product_type: String = row.try_get("productType")?;
+    let attributes: String = row.try_get("attributes")?;
+    Ok(model::ProductDetails {
+        sku: row.try_get("sku")?,
+        description: row.try_get("description")?,
+        base_price: super::get_decimal(row, "basePrice")?,
+        tax_class_id: row.try_get("taxClassId")?,
+        weight_grams: row.try_get("weightGrams")?,
+        barcodes,
+        options: serde_json::from_str(&options)
+            .map_err(|e| super::Error::ProviderFailure(e.to_string()))?,
+        parent_id: row.try_get("parentId")?,
+        option_values: serde_json::from_str(&option_values)
+            .map_err(|e| super::Error::ProviderFailure(e.to_string()))?,
+        product_type: product_type
+            .parse()
+            .map_err(super::Error::ProviderFailure)?,
+        components,
+        base_unit: row.try_get("baseUnit")?,
+        units,
+        category_id: row.try_get("categoryId")?,
+        attributes: serde_json::from_str(&attributes)
+            .map_err(|e| super::Error::ProviderFailure(e.to_string()))?,
+    }
+    .to_record(product_id))
+}
+
+pub async fn product_details(
+    conn: &mut SqliteConnection,
+    product_id: i64,
+) -> Result<model::Record<model::ProductDetails>, super::Error> {
+    let result = sqlx::query(
+        r#"
+            SELECT * FROM products WHERE id=?1;
+        "#,
+    )
+    .bind(product_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    let Some(row) = result else {
+        warn!("Sql row not found");
+        return Err(super::Error::ProductNotFound(String::new()));
+    };
+
+    product_from_row(conn, &row).await
+}
+
+/// Inserts a validated barcode, failing if another product already has it
+async fn insert_barcode(
+    conn: &mut SqliteConnection,
+    product_id: i64,
+    barcode: &model::Barcode,
+) -> Result<(), super::Error> {
+    let result = sqlx::query(
+        r#"
+            INSERT INTO productBarcodes (productId, symbology, code, lookupCode)
+            SELECT ?1, ?2, ?3, ?4
+            WHERE NOT EXISTS ( SELECT 1 FROM productBarcodes WHERE lookupCode = ?4 );
+        "#,
+    )
+    .bind(product_id)
+    .bind(String::from(barcode.symbology.clone()))
+    .bind(&barcode.code)
+    .bind(barcode.lookup_code())
+    .execute(&mut *conn)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(super::Error::BadInput(format!(
+            "barcode {} is already in use",
+            barcode.code
+        )));
+    }
+    Ok(())
+}
+
+/// A variant picks a value for every option on its parent, and has no options of its own
+fn check_variant(
+    variant: &model::ProductDetails,
+    parent: &model::ProductDetails,
+) -> Result<(), String> {
+    if parent.parent_id.is_some() {
+        return Err("variants can't have variants".to_string());
+    }
+    if parent.options.is_empty() {
+        return Err(format!("{} has no options to vary", parent.sku));
+    }
+    if !variant.options.is_empty() {
+        return Err("variants can't have options of their own".to_string());
+    }
+    let mut chosen: Vec<&String> = variant.option_values.keys().collect();
+    let mut wanted: Vec<&String> = parent.options.iter().collect();
+    chosen.sort();
+    wanted.sort();
+    if chosen != wanted {
+        return Err(format!(
+            "variants of {} need a value for each of {}",
+            parent.sku,
+            parent.options.join(", ")
+        ));
+    }
+    if variant.option_values.values().any(|v| v.is_empty()) {
+        return Err("option values can't be empty".to_string());
+    }
+    Ok(())
 }
 
 impl ProductService for SqliteProvider {
@@ -25,7 +222,87 @@ impl ProductService for SqliteProvider {
                 CREATE TABLE IF NOT EXISTS products (
                     id INTEGER NOT NULL UNIQUE PRIMARY KEY,
                     sku TEXT NOT NULL,
-                    description TEXT NOT NULL  
+                    description TEXT NOT NULL,
+                    basePrice TEXT NOT NULL DEFAULT '0',
+                    taxClassId INTEGER,
+                    weightGrams INTEGER,
+                    options TEXT NOT NULL DEFAULT '[]',
+                    parentId INTEGER,
+                    optionValues TEXT NOT NULL DEFAULT '{}',
+                    productType TEXT NOT NULL DEFAULT 'Standard',
+                    baseUnit TEXT NOT NULL DEFAULT 'each',
+                    categoryId INTEGER,
+                    attributes TEXT NOT NULL DEFAULT '{}'
+                );
+            "#,
+        )
+        .execute(&mut *conn)
+        .await?;
+        // the search index follows the products table through triggers, so it can't miss
+        // a write
+        sqlx::query(
+            r#"
+                CREATE VIRTUAL TABLE IF NOT EXISTS productSearch USING fts5 (
+                    sku, description, attributes, tokenize = 'unicode61'
+                );
+                CREATE TRIGGER IF NOT EXISTS productSearchInsert AFTER INSERT ON products BEGIN
+                    INSERT INTO productSearch (rowid, sku, description, attributes)
+                    SELECT new.id, new.sku, new.description, group_concat(
+                        key || ':' || CASE type WHEN 'true' THEN 'true'
+                            WHEN 'false' THEN 'false' ELSE value END,
+                        ' '
+                    )
+                    FROM json_each(new.attributes);
+                END;
+                CREATE TRIGGER IF NOT EXISTS productSearchUpdate AFTER UPDATE ON products BEGIN
+                    DELETE FROM productSearch WHERE rowid = old.id;
+                    INSERT INTO productSearch (rowid, sku, description, attributes)
+                    SELECT new.id, new.sku, new.description, group_concat(
+                        key || ':' || CASE type WHEN 'true' THEN 'true'
+                            WHEN 'false' THEN 'false' ELSE value END,
+                        ' '
+                    )
+                    FROM json_each(new.attributes);
+                END;
+                CREATE TRIGGER IF NOT EXISTS productSearchDelete AFTER DELETE ON products BEGIN
+                    DELETE FROM productSearch WHERE rowid = old.id;
+                END;
+            "#,
+        )
+        .execute(&mut *conn)
+        .await?;
+        sqlx::query(
+            r#"
+                CREATE TABLE IF NOT EXISTS kitComponents (
+                    kitId INTEGER NOT NULL,
+                    productId INTEGER NOT NULL,
+                    quantity INTEGER NOT NULL,
+                    PRIMARY KEY (kitId, productId)
+                );
+            "#,
+        )
+        .execute(&mut *conn)
+        .await?;
+        sqlx::query(
+            r#"
+                CREATE TABLE IF NOT EXISTS productUnits (
+                    productId INTEGER NOT NULL,
+                    name TEXT NOT NULL,
+                    factor INTEGER NOT NULL,
+                    PRIMARY KEY (productId, name)
+                );
+            "#,
+        )
+        .execute(&mut *conn)
+        .await?;
+        sqlx::query(
+            r#"
+                CREATE TABLE IF NOT EXISTS productBarcodes (
+                    id INTEGER NOT NULL UNIQUE PRIMARY KEY,
+                    productId INTEGER NOT NULL,
+                    symbology TEXT NOT NULL,
+                    code TEXT NOT NULL,
+                    lookupCode TEXT NOT NULL UNIQUE
                 );
             "#,
         )
@@ -33,18 +310,190 @@ impl ProductService for SqliteProvider {
         .await?;
         Ok(())
     }
-    async fn create_product(&mut self, sku: &str, description: &str) -> Result<i64, Self::Error> {
-        let mut conn = self.connection.acquire().await?;
+    async fn create_product(
+        &mut self,
+        product: &model::ProductDetails,
+    ) -> Result<i64, Self::Error> {
+        if product.weight_grams.is_some_and(|w| w < 0) {
+            return Err(super::Error::BadInput(
+                "weight can't be negative".to_string(),
+            ));
+        }
+        if product.base_price < Decimal::ZERO {
+            return Err(super::Error::BadInput(
+                "base price can't be negative".to_string(),
+            ));
+        }
+        for barcode in &product.barcodes {
+            barcode.validate().map_err(super::Error::BadInput)?;
+        }
+        let mut options = product.options.clone();
+        options.sort();
+        options.dedup();
+        if options.len() != product.options.len() || options.iter().any(|o| o.is_empty()) {
+            return Err(super::Error::BadInput(
+                "options must be named and can't repeat".to_string(),
+            ));
+        }
+        match product.parent_id {
+            Some(parent_id) => {
+                let parent = self.get_product(&parent_id).await?;
+                check_variant(product, &parent.data).map_err(super::Error::BadInput)?;
+            }
+            None if !product.option_values.is_empty() => {
+                return Err(super::Error::BadInput(
+                    "only variants have option values".to_string(),
+                ));
+            }
+            None => {}
+        }
+        match product.product_type {
+            model::ProductType::Kit => {
+                let mut component_ids: Vec<i64> =
+                    product.components.iter().map(|c| c.product_id).collect();
+                component_ids.sort();
+                component_ids.dedup();
+                if component_ids.is_empty() || component_ids.len() != product.components.len() {
+                    return Err(super::Error::BadInput(
+                        "a kit needs at least one component, each listed once".to_string(),
+                    ));
+                }
+                if product.components.iter().any(|c| c.quantity <= 0) {
+                    return Err(super::Error::BadInput(
+                        "component quantities must be positive".to_string(),
+                    ));
+                }
+                for component_id in component_ids {
+                    let component = self.get_product(&component_id).await?;
+                    if component.data.product_type == model::ProductType::Kit {
+                        return Err(super::Error::BadInput(format!(
+                            "kit {} can't be a component of another kit",
+                            component.data.sku
+                        )));
+                    }
+                }
+            }
+            model::ProductType::Standard if !product.components.is_empty() => {
+                return Err(super::Error::BadInput(
+                    "only kits have components".to_string(),
+                ));
+            }
+            model::ProductType::Standard => {}
+        }
+        let base_unit = match product.base_unit.trim() {
+            "" => model::DEFAULT_UNIT,
+            unit => unit,
+        };
+        let mut unit_names: Vec<&str> = product.units.iter().map(|u| u.name.trim()).collect();
+        unit_names.push(base_unit);
+        unit_names.sort();
+        unit_names.dedup();
+        if unit_names.len() != product.units.len() + 1 || unit_names.contains(&"") {
+            return Err(super::Error::BadInput(
+                "units must be named and can't repeat the base unit or each other".to_string(),
+            ));
+        }
+        if product.units.iter().any(|u| u.factor <= 0) {
+            return Err(super::Error::BadInput(
+                "unit factors must be positive".to_string(),
+            ));
+        }
+        if product.attributes.keys().any(|name| name.is_empty()) {
+            return Err(super::Error::BadInput("attributes need a name".to_string()));
+        }
+        if let Some(category_id) = product.category_id {
+            let mut conn = self.connection.acquire().await?;
+            let defined = category::category_attributes(&mut conn, category_id)
+                .await?
+                .ok_or_else(|| super::Error::BadInput(format!("no category {}", category_id)))?;
+            for definition in defined {
+                if let Some(value) = product.attributes.get(&definition.name) {
+                    definition.check(value).map_err(super::Error::BadInput)?;
+                }
+            }
+        }
+        let option_values = serde_json::to_string(&product.option_values)
+            .map_err(|e| super::Error::ProviderFailure(e.to_string()))?;
+
+        let mut tx = self.connection.begin().await?;
+        if let Some(parent_id) = product.parent_id {
+            let row = sqlx::query(
+                r#"
+                    SELECT COUNT(*) AS siblings FROM products
+                    WHERE parentId=?1 AND optionValues=?2;
+                "#,
+            )
+            .bind(parent_id)
+            .bind(&option_values)
+            .fetch_one(&mut *tx)
+            .await?;
+            let siblings: i64 = row.try_get("siblings")?;
+            if siblings > 0 {
+                return Err(super::Error::BadInput(format!(
+                    "product {} already has a variant with those options",
+                    parent_id
+                )));
+            }
+        }
+
         let result = sqlx::query(
             r#"
-                INSERT INTO products VALUES( NULL, ?1, ?2 );
+                INSERT INTO products VALUES( NULL, ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12 );
             "#,
         )
-        .bind(sku)
-        .bind(description)
-        .execute(&mut *conn)
+        .bind(&product.sku)
+        .bind(&product.description)
+        .bind(product.base_price.to_string())
+        .bind(product.tax_class_id)
+        .bind(product.weight_grams)
+        .bind(
+            serde_json::to_string(&product.options)
+                .map_err(|e| super::Error::ProviderFailure(e.to_string()))?,
+        )
+        .bind(product.parent_id)
+        .bind(option_values)
+        .bind(String::from(product.product_type.clone()))
+        .bind(base_unit)
+        .bind(product.category_id)
+        .bind(
+            serde_json::to_string(&product.attributes)
+                .map_err(|e| super::Error::ProviderFailure(e.to_string()))?,
+        )
+        .execute(&mut *tx)
         .await?;
-        Ok(result.last_insert_rowid())
+        let product_id = result.last_insert_rowid();
+
+        for barcode in &product.barcodes {
+            insert_barcode(&mut tx, product_id, barcode).await?;
+        }
+        for component in &product.components {
+            sqlx::query(
+                r#"
+                    INSERT INTO kitComponents (kitId, productId, quantity) VALUES ( ?1, ?2, ?3 );
+                "#,
+            )
+            .bind(product_id)
+            .bind(component.product_id)
+            .bind(component.quantity)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        for unit in &product.units {
+            sqlx::query(
+                r#"
+                    INSERT INTO productUnits (productId, name, factor) VALUES ( ?1, ?2, ?3 );
+                "#,
+            )
+            .bind(product_id)
+            .bind(unit.name.trim())
+            .bind(unit.factor)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(product_id)
     }
 
     async fn get_product(
@@ -52,27 +501,563 @@ impl ProductService for SqliteProvider {
         id: &i64,
     ) -> Result<model::Record<model::ProductDetails>, Self::Error> {
         let mut conn = self.connection.acquire().await?;
+        product_details(&mut conn, *id).await
+    }
 
-        let result = sqlx::query(
+    async fn add_product_barcode(
+        &mut self,
+        product_id: i64,
+        barcode: &model::Barcode,
+    ) -> Result<(), Self::Error> {
+        barcode.validate().map_err(super::Error::BadInput)?;
+        self.get_product(&product_id).await?;
+
+        let mut conn = self.connection.acquire().await?;
+        insert_barcode(&mut conn, product_id, barcode).await
+    }
+
+    async fn get_catalog(
+        &mut self,
+        filter: &model::ProductFilter,
+    ) -> Result<Vec<model::CatalogEntry>, Self::Error> {
+        let mut conn = self.connection.acquire().await?;
+
+        // products are listed when they match, when one of their variants does, or when
+        // they're the variant of a product that does, each product ahead of its variants
+        let rows = sqlx::query(
             r#"
-                SELECT * FROM products WHERE id=?1;
+                WITH RECURSIVE subtree(id) AS (
+                    SELECT id FROM categories WHERE id = ?1
+                    UNION ALL
+                    SELECT categories.id FROM categories
+                    JOIN subtree ON categories.parentId = subtree.id
+                ),
+                matching AS (
+                    SELECT id, parentId FROM products
+                    WHERE (?1 IS NULL OR categoryId IN (SELECT id FROM subtree))
+                    AND NOT EXISTS (
+                        SELECT 1 FROM json_each(?2) AS wanted
+                        WHERE NOT EXISTS (
+                            SELECT 1 FROM json_each(products.attributes) AS have
+                            WHERE have.key = wanted.key AND CASE have.type
+                                WHEN 'true' THEN json_extract(wanted.value, '$[1]') IS 1
+                                WHEN 'false' THEN json_extract(wanted.value, '$[1]') IS 0
+                                WHEN 'text' THEN have.value = json_extract(wanted.value, '$[0]')
+                                ELSE have.value = json_extract(wanted.value, '$[2]')
+                            END
+                        )
+                    )
+                )
+                SELECT * FROM products
+                WHERE id IN (SELECT id FROM matching)
+                OR id IN (SELECT parentId FROM matching)
+                OR parentId IN (SELECT id FROM matching)
+                ORDER BY coalesce(parentId, id), parentId IS NOT NULL, id;
             "#,
         )
-        .bind(id.to_owned())
-        .fetch_optional(&mut *conn)
+        .bind(filter.category_id)
+        .bind(filter.attributes_json())
+        .fetch_all(&mut *conn)
         .await?;
 
-        let Some(row) = result else {
-            warn!("Sql row not found");
-            return Err(super::Error::ProductNotFound(String::new()));
+        let mut catalog: Vec<model::CatalogEntry> = Vec::new();
+        for row in rows {
+            let product = product_from_row(&mut conn, &row).await?;
+            let Some(parent_id) = product.data.parent_id else {
+                catalog.push(model::CatalogEntry {
+                    product,
+                    variants: Vec::new(),
+                });
+                continue;
+            };
+            match catalog.last_mut() {
+                Some(entry) if entry.product.id == parent_id => entry.variants.push(product),
+                _ => {
+                    return Err(super::Error::ProviderFailure(format!(
+                        "variant {} listed without product {}",
+                        product.id, parent_id
+                    )))
+                }
+            }
+        }
+
+        Ok(catalog)
+    }
+
+    async fn search_products(
+        &mut self,
+        query: &str,
+    ) -> Result<Vec<model::ProductSearchHit>, Self::Error> {
+        let Some(query) = model::product_search_query(query) else {
+            return Err(super::Error::BadInput(
+                "search needs at least one word".to_string(),
+            ));
         };
 
-        let record = model::ProductDetails {
-            sku: row.try_get("sku")?,
-            description: row.try_get("description")?,
+        let mut conn = self.connection.acquire().await?;
+        // SKU matches count for more than description matches, attributes the least
+        let rows = sqlx::query(
+            r#"
+                SELECT
+                    products.*,
+                    bm25(productSearch, 10.0, 2.0, 1.0) AS searchRank,
+                    snippet(productSearch, -1, '<b>', '</b>', '…', 12) AS searchSnippet
+                FROM productSearch
+                JOIN products ON products.id = productSearch.rowid
+                WHERE productSearch MATCH ?1
+                ORDER BY searchRank, products.id
+                LIMIT 50;
+            "#,
+        )
+        .bind(query)
+        .fetch_all(&mut *conn)
+        .await?;
+
+        let mut hits = Vec::new();
+        for row in rows {
+            hits.push(model::ProductSearchHit {
+                product: product_from_row(&mut conn, &row).await?,
+                rank: row.try_get("searchRank")?,
+                snippet: row.try_get("searchSnippet")?,
+            });
         }
-        .to_record(id.to_owned());
+        Ok(hits)
+    }
+
+    async fn find_product_by_code(
+        &mut self,
+        code: &str,
+    ) -> Result<Option<model::Record<model::ProductDetails>>, Self::Error> {
+        let mut conn = self.connection.acquire().await?;
+
+        let mut result = None;
+        for lookup_code in model::Barcode::lookup_codes(code) {
+            result = sqlx::query(
+                r#"
+                    SELECT products.* FROM products
+                    JOIN productBarcodes ON productBarcodes.productId = products.id
+                    WHERE productBarcodes.lookupCode=?1;
+                "#,
+            )
+            .bind(lookup_code)
+            .fetch_optional(&mut *conn)
+            .await?;
+            if result.is_some() {
+                break;
+            }
+        }
+
+        if result.is_none() {
+            result = sqlx::query(
+                r#"
+                    SELECT * FROM products WHERE sku=?1 ORDER BY id LIMIT 1;
+                "#,
+            )
+            .bind(code)
+            .fetch_optional(&mut *conn)
+            .await?;
+        }
+
+        match result {
+            Some(row) => Ok(Some(product_from_row(&mut conn, &row).await?)),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        model,
+        provider::SqliteProvider,
+        service::{category::CategoryService, product::ProductService},
+    };
+
+    #[tokio::test]
+    async fn test_barcodes() {
+        let mut provider = SqliteProvider::new_memory().await.unwrap();
+        ProductService::init_provider(&mut provider).await.unwrap();
+
+        let upc = model::Barcode {
+            symbology: model::BarcodeSymbology::UpcA,
+            code: "036000291452".to_string(),
+        };
+        let product_id = provider
+            .create_product(&model::ProductDetails {
+                sku: "SKU-1".to_string(),
+                description: "widget".to_string(),
+                barcodes: vec![upc.clone()],
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        provider
+            .add_product_barcode(
+                product_id,
+                &model::Barcode {
+                    symbology: model::BarcodeSymbology::Code128,
+                    code: "BIN-A1".to_string(),
+                },
+            )
+            .await
+            .unwrap();
+
+        // the same GTIN as an EAN-13 can't go on another product
+        let other = provider
+            .create_product(&model::ProductDetails {
+                sku: "SKU-2".to_string(),
+                description: "gadget".to_string(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert!(provider
+            .add_product_barcode(
+                other,
+                &model::Barcode {
+                    symbology: model::BarcodeSymbology::Ean13,
+                    code: "0036000291452".to_string(),
+                },
+            )
+            .await
+            .is_err());
+        assert!(provider
+            .add_product_barcode(
+                other,
+                &model::Barcode {
+                    symbology: model::BarcodeSymbology::Ean13,
+                    code: "4006381333932".to_string(),
+                },
+            )
+            .await
+            .is_err());
+
+        for code in ["036000291452", "0036000291452", "BIN-A1", "SKU-1"] {
+            let product = provider.find_product_by_code(code).await.unwrap().unwrap();
+            assert_eq!(product.id, product_id);
+        }
+        assert!(provider
+            .find_product_by_code("4006381333931")
+            .await
+            .unwrap()
+            .is_none());
+
+        let product = provider.get_product(&product_id).await.unwrap();
+        assert_eq!(product.data.barcodes.len(), 2);
+        assert_eq!(product.data.barcodes[0], upc);
+    }
+
+    #[tokio::test]
+    async fn test_variants() {
+        let mut provider = SqliteProvider::new_memory().await.unwrap();
+        CategoryService::init_provider(&mut provider).await.unwrap();
+        ProductService::init_provider(&mut provider).await.unwrap();
+
+        let shirt = provider
+            .create_product(&model::ProductDetails {
+                sku: "SHIRT".to_string(),
+                description: "shirt".to_string(),
+                options: vec!["size".to_string(), "colour".to_string()],
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let variant = |size: &str, colour: &str| model::ProductDetails {
+            sku: format!("SHIRT-{}-{}", size, colour),
+            description: "shirt".to_string(),
+            parent_id: Some(shirt),
+            option_values: [("size", size), ("colour", colour)]
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            ..Default::default()
+        };
+
+        let small_red = provider.create_product(&variant("S", "red")).await.unwrap();
+        provider.create_product(&variant("M", "red")).await.unwrap();
+        // each combination once, and every option needs a value
+        assert!(provider.create_product(&variant("S", "red")).await.is_err());
+        let mut no_colour = variant("L", "red");
+        no_colour.option_values.remove("colour");
+        assert!(provider.create_product(&no_colour).await.is_err());
+        assert!(provider
+            .create_product(&model::ProductDetails {
+                parent_id: Some(small_red),
+                ..variant("L", "red")
+            })
+            .await
+            .is_err());
+
+        // the combination is what has to be unique, not the SKU
+        assert!(provider
+            .create_product(&model::ProductDetails {
+                sku: "SHIRT-SMALL-RED".to_string(),
+                ..variant("S", "red")
+            })
+            .await
+            .is_err());
+        assert!(provider.create_product(&variant("L", "")).await.is_err());
+        assert!(provider
+            .create_product(&model::ProductDetails {
+                options: vec!["fit".to_string()],
+                ..variant("L", "red")
+            })
+            .await
+            .is_err());
+
+        let mug = provider
+            .create_product(&model::ProductDetails {
+                sku: "MUG".to_string(),
+                description: "mug".to_string(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        // nothing to vary on a product without options, or one that doesn't exist
+        for parent_id in [mug, 99] {
+            assert!(provider
+                .create_product(&model::ProductDetails {
+                    parent_id: Some(parent_id),
+                    ..variant("L", "red")
+                })
+                .await
+                .is_err());
+        }
+
+        let catalog = provider
+            .get_catalog(&model::ProductFilter::default())
+            .await
+            .unwrap();
+        assert_eq!(catalog.len(), 2);
+        assert_eq!(catalog[0].product.id, shirt);
+        let skus: Vec<&str> = catalog[0]
+            .variants
+            .iter()
+            .map(|v| v.data.sku.as_str())
+            .collect();
+        assert_eq!(skus, vec!["SHIRT-S-red", "SHIRT-M-red"]);
+        assert!(catalog[1].variants.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_category_filters() {
+        let mut provider = SqliteProvider::new_memory().await.unwrap();
+        CategoryService::init_provider(&mut provider).await.unwrap();
+        ProductService::init_provider(&mut provider).await.unwrap();
+
+        let apparel = provider
+            .create_category(&model::CategoryDetails {
+                name: "Apparel".to_string(),
+                parent_id: None,
+                attributes: vec![model::AttributeDefinition {
+                    name: "size".to_string(),
+                    kind: model::AttributeKind::Enum,
+                    values: vec!["S".to_string(), "M".to_string()],
+                }],
+            })
+            .await
+            .unwrap();
+        let shirts = provider
+            .create_category(&model::CategoryDetails {
+                name: "Shirts".to_string(),
+                parent_id: Some(apparel),
+                attributes: vec![],
+            })
+            .await
+            .unwrap();
+
+        let product = |sku: &str, category_id, size: &str| model::ProductDetails {
+            sku: sku.to_string(),
+            description: sku.to_lowercase(),
+            category_id,
+            attributes: [
+                (
+                    "size".to_string(),
+                    model::AttributeValue::String(size.to_string()),
+                ),
+                ("organic".to_string(), model::AttributeValue::Bool(true)),
+            ]
+            .into(),
+            ..Default::default()
+        };
+
+        // size is typed by the parent category
+        assert!(provider
+            .create_product(&product("TEE", Some(shirts), "XL"))
+            .await
+            .is_err());
+        assert!(provider
+            .create_product(&product("TEE", Some(99), "S"))
+            .await
+            .is_err());
+        provider
+            .create_product(&product("TEE", Some(shirts), "S"))
+            .await
+            .unwrap();
+        provider
+            .create_product(&product("SCARF", Some(apparel), "M"))
+            .await
+            .unwrap();
+        // outside any category attributes are free-form
+        provider
+            .create_product(&product("MUG", None, "XL"))
+            .await
+            .unwrap();
+
+        let skus = |catalog: Vec<model::CatalogEntry>| -> Vec<String> {
+            catalog.into_iter().map(|e| e.product.data.sku).collect()
+        };
+        let filter = |category_id, attributes: &[(&str, &str)]| model::ProductFilter {
+            category_id,
+            attributes: attributes
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        };
+
+        let catalog = provider
+            .get_catalog(&filter(Some(apparel), &[]))
+            .await
+            .unwrap();
+        assert_eq!(skus(catalog), vec!["TEE", "SCARF"]);
+        let catalog = provider
+            .get_catalog(&filter(Some(shirts), &[]))
+            .await
+            .unwrap();
+        assert_eq!(skus(catalog), vec!["TEE"]);
+        let catalog = provider
+            .get_catalog(&filter(None, &[("size", "M")]))
+            .await
+            .unwrap();
+        assert_eq!(skus(catalog), vec!["SCARF"]);
+        let catalog = provider
+            .get_catalog(&filter(None, &[("organic", "true"), ("size", "XL")]))
+            .await
+            .unwrap();
+        assert_eq!(skus(catalog), vec!["MUG"]);
+
+        let sock = provider
+            .create_product(&model::ProductDetails {
+                sku: "SOCK".to_string(),
+                description: "sock".to_string(),
+                options: vec!["pack".to_string()],
+                attributes: [("wool".to_string(), model::AttributeValue::Bool(false))].into(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        for (pack, grams) in [("1", 40.0), ("3", 120.0)] {
+            provider
+                .create_product(&model::ProductDetails {
+                    sku: format!("SOCK-{}", pack),
+                    description: "sock".to_string(),
+                    parent_id: Some(sock),
+                    option_values: [("pack".to_string(), pack.to_string())].into(),
+                    attributes: [("grams".to_string(), model::AttributeValue::Number(grams))]
+                        .into(),
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+        }
+        let variant_skus = |entry: &model::CatalogEntry| -> Vec<String> {
+            entry.variants.iter().map(|v| v.data.sku.clone()).collect()
+        };
+
+        // a matching variant brings its product along, but not the other variants
+        let catalog = provider
+            .get_catalog(&filter(None, &[("grams", "120")]))
+            .await
+            .unwrap();
+        assert_eq!(catalog.len(), 1);
+        assert_eq!(variant_skus(&catalog[0]), vec!["SOCK-3"]);
+        // a matching product keeps all of them
+        let catalog = provider
+            .get_catalog(&filter(None, &[("wool", "false")]))
+            .await
+            .unwrap();
+        assert_eq!(catalog.len(), 1);
+        assert_eq!(variant_skus(&catalog[0]), vec!["SOCK-1", "SOCK-3"]);
+        assert!(provider
+            .get_catalog(&filter(None, &[("grams", "heavy")]))
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(provider
+            .get_catalog(&filter(Some(99), &[]))
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_search_products() {
+        let mut provider = SqliteProvider::new_memory().await.unwrap();
+        ProductService::init_provider(&mut provider).await.unwrap();
+
+        let mut ids = Vec::new();
+        for (sku, description, colour) in [
+            ("MUG-1", "stoneware coffee mug", "blue"),
+            ("BLUE-CUP", "espresso cup", "white"),
+            ("TEA-1", "loose leaf tea", "green"),
+        ] {
+            let id = provider
+                .create_product(&model::ProductDetails {
+                    sku: sku.to_string(),
+                    description: description.to_string(),
+                    attributes: [(
+                        "colour".to_string(),
+                        model::AttributeValue::String(colour.to_string()),
+                    )]
+                    .into(),
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+            ids.push(id);
+        }
+
+        let hits = provider.search_products("stone").await.unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].product.id, ids[0]);
+        assert!(hits[0].snippet.contains("<b>stoneware</b>"));
+
+        // a SKU match outranks an attribute match
+        let hits = provider.search_products("blue").await.unwrap();
+        let found: Vec<i64> = hits.iter().map(|h| h.product.id).collect();
+        assert_eq!(found, vec![ids[1], ids[0]]);
+
+        // attributes are indexed by name as well as value, flags as true or false
+        let mut conn = provider.connection.acquire().await.unwrap();
+        sqlx::query(r#"UPDATE products SET attributes = '{"organic":true}' WHERE id = ?1"#)
+            .bind(ids[2])
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        drop(conn);
+        for query in ["organic", "organic true", "true"] {
+            let hits = provider.search_products(query).await.unwrap();
+            let found: Vec<i64> = hits.iter().map(|h| h.product.id).collect();
+            assert_eq!(found, vec![ids[2]]);
+        }
+
+        // every word has to match
+        assert!(provider
+            .search_products("coffee tea")
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(provider.search_products("  ").await.is_err());
 
-        Ok(record)
+        let mut conn = provider.connection.acquire().await.unwrap();
+        sqlx::query("UPDATE products SET description = 'earl grey' WHERE id = ?1")
+            .bind(ids[2])
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        drop(conn);
+        assert!(provider.search_products("loose").await.unwrap().is_empty());
+        let hits = provider.search_products("earl").await.unwrap();
+        assert_eq!(hits[0].product.id, ids[2]);
     }
 }
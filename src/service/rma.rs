@@ -0,0 +1,726 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+};
+
+use axum::http::StatusCode;
+use chrono::Utc;
+use futures::TryStreamExt;
+use rust_decimal::Decimal;
+use sqlx::{Row, SqliteConnection};
+
+use crate::{
+    model::{self, ToRecord},
+    provider::SqliteProvider,
+    service::{
+        fulfillment::FulfillmentService, inventory, line_item::LineItemService, order::OrderService,
+    },
+};
+
+pub trait ReturnService {
+    type Error: Display + Into<StatusCode>;
+
+    async fn init_provider(&mut self) -> Result<(), Self::Error>;
+
+    /// Requests a return of `(line_item_id, quantity)` pairs from a fulfilled fulfillment.
+    ///
+    /// Across all returns that weren't rejected, no more of a line item can come back
+    /// than was fulfilled.
+    async fn create_return(
+        &mut self,
+        fulfillment_id: i64,
+        reason: Option<String>,
+        lines: &[(i64, i64)],
+    ) -> Result<i64, Self::Error>;
+
+    async fn get_return(
+        &mut self,
+        return_id: i64,
+    ) -> Result<Option<model::Record<model::ReturnDetails>>, Self::Error>;
+
+    async fn get_returns_by_order_id(
+        &mut self,
+        order_id: i64,
+    ) -> Result<Vec<model::Record<model::ReturnDetails>>, Self::Error>;
+
+    /// Moves a return along `Requested -> Approved -> Received -> Refunded`.
+    ///
+    /// Receiving a return puts goods that were picked back into stock and hands them to
+    /// waiting backorders, refunding it needs the `refund_amount`.
+    async fn set_return_status(
+        &mut self,
+        return_id: i64,
+        return_status: model::ReturnStatus,
+        refund_amount: Option<Decimal>,
+    ) -> Result<(), Self::Error>;
+}
+
+impl ReturnService for SqliteProvider {
+    type Error = super::Error;
+
+    async fn init_provider(&mut self) -> Result<(), Self::Error> {
+        let mut conn = self.connection.acquire().await?;
+        sqlx::query(sql_stmt::CREATE_RETURN_TABLE)
+            .execute(&mut *conn)
+            .await?;
+        sqlx::query(sql_stmt::CREATE_RETURN_LINE_TABLE)
+            .execute(&mut *conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn create_return(
+        &mut self,
+        fulfillment_id: i64,
+        reason: Option<String>,
+        lines: &[(i64, i64)],
+    ) -> Result<i64, Self::Error> {
+        let Some(fulfillment) = self.get_fulfillment(&fulfillment_id).await? else {
+            return Err(super::Error::NotFound(format!(
+                "fulfillment {}",
+                fulfillment_id
+            )));
+        };
+
+        if fulfillment.data.status != model::FulfillmentStatus::Fulfilled {
+            return Err(super::Error::BadInput(format!(
+                "fulfillment {} hasn't been fulfilled",
+                fulfillment_id
+            )));
+        }
+        if lines.is_empty() {
+            return Err(super::Error::BadInput(
+                "a return needs at least one line".to_string(),
+            ));
+        }
+
+        let mut requested: BTreeMap<i64, i64> = BTreeMap::new();
+        for (line_item_id, quantity) in lines {
+            if *quantity <= 0 {
+                return Err(super::Error::BadInput(
+                    "return quantity must be positive".to_string(),
+                ));
+            }
+            *requested.entry(*line_item_id).or_default() += quantity;
+        }
+
+        let line_items = self
+            .get_line_items_by_fulfillment_id(fulfillment_id)
+            .await?;
+
+        let mut tx = self.connection.begin().await?;
+        let mut return_lines = Vec::new();
+        for (line_item_id, quantity) in requested {
+            let Some(line_item) = line_items.iter().find(|l| l.id == line_item_id) else {
+                return Err(super::Error::BadInput(format!(
+                    "line item {} isn't on fulfillment {}",
+                    line_item_id, fulfillment_id
+                )));
+            };
+
+            let row = sqlx::query(sql_stmt::SELECT_RETURNED_QUANTITY)
+                .bind(line_item_id)
+                .fetch_one(&mut *tx)
+                .await?;
+            let returned: i64 = row.try_get("returned")?;
+
+            if returned + quantity > line_item.data.quantity_fulfilled {
+                return Err(super::Error::BadInput(format!(
+                    "only {} of line item {} can be returned",
+                    line_item.data.quantity_fulfilled - returned,
+                    line_item_id
+                )));
+            }
+
+            return_lines.push(model::ReturnLine {
+                line_item_id,
+                product_id: line_item.data.product_id,
+                quantity,
+            });
+        }
+
+        let result = sqlx::query(sql_stmt::INSERT_RETURN)
+            .bind(fulfillment_id)
+            .bind(fulfillment.data.order_id)
+            .bind(String::from(model::ReturnStatus::Requested))
+            .bind(reason)
+            .bind(Utc::now())
+            .execute(&mut *tx)
+            .await?;
+        let return_id = result.last_insert_rowid();
+
+        for line in return_lines {
+            sqlx::query(sql_stmt::INSERT_RETURN_LINE)
+                .bind(return_id)
+                .bind(line.line_item_id)
+                .bind(line.product_id)
+                .bind(line.quantity)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+        Ok(return_id)
+    }
+
+    async fn get_return(
+        &mut self,
+        return_id: i64,
+    ) -> Result<Option<model::Record<model::ReturnDetails>>, Self::Error> {
+        let mut conn = self.connection.acquire().await?;
+        let result = sqlx::query(sql_stmt::SELECT_RETURN)
+            .bind(return_id)
+            .fetch_optional(&mut *conn)
+            .await?;
+
+        let Some(row) = result else {
+            return Ok(None);
+        };
+
+        let details = return_from_row(&row)?;
+        Ok(Some(
+            with_lines(&mut conn, return_id, details)
+                .await?
+                .to_record(return_id),
+        ))
+    }
+
+    async fn get_returns_by_order_id(
+        &mut self,
+        order_id: i64,
+    ) -> Result<Vec<model::Record<model::ReturnDetails>>, Self::Error> {
+        let mut conn = self.connection.acquire().await?;
+        let rows = sqlx::query(sql_stmt::SELECT_RETURNS_BY_ORDER_ID)
+            .bind(order_id)
+            .fetch_all(&mut *conn)
+            .await?;
+
+        let mut records = Vec::new();
+        for row in rows {
+            let return_id: i64 = row.try_get("id")?;
+            let details = return_from_row(&row)?;
+            records.push(
+                with_lines(&mut conn, return_id, details)
+                    .await?
+                    .to_record(return_id),
+            );
+        }
+
+        Ok(records)
+    }
+
+    async fn set_return_status(
+        &mut self,
+        return_id: i64,
+        return_status: model::ReturnStatus,
+        refund_amount: Option<Decimal>,
+    ) -> Result<(), Self::Error> {
+        let Some(rma) = self.get_return(return_id).await? else {
+            return Err(super::Error::NotFound(format!("return {}", return_id)));
+        };
+
+        if !return_status.allowed_priors().contains(&rma.data.status) {
+            return Err(super::Error::BadInput(
+                "bad return status transition".to_string(),
+            ));
+        }
+
+        match (&return_status, refund_amount) {
+            (model::ReturnStatus::Refunded, None) => {
+                return Err(super::Error::BadInput(
+                    "refunding a return needs the amount refunded".to_string(),
+                ))
+            }
+            (model::ReturnStatus::Refunded, Some(amount)) => {
+                if amount < Decimal::ZERO {
+                    return Err(super::Error::BadInput(
+                        "refund can't be negative".to_string(),
+                    ));
+                }
+                if let Some(order_id) = rma.data.order_id {
+                    self.check_refund(order_id, return_id, amount).await?;
+                }
+            }
+            (_, Some(_)) => {
+                return Err(super::Error::BadInput(
+                    "only refunded returns have a refund amount".to_string(),
+                ))
+            }
+            (_, None) => {}
+        }
+
+        // goods go back to where they were shipped from, drop-ships and services never
+        // came off our shelves so there's nothing to restock
+        let restock_at = match self.get_fulfillment(&rma.data.fulfillment_id).await? {
+            Some(fulfillment) if fulfillment.data.fulfillment_type.is_picked() => Some(
+                fulfillment
+                    .data
+                    .from_location_id
+                    .unwrap_or(model::DEFAULT_LOCATION_ID),
+            ),
+            _ => None,
+        };
+
+        let mut tx = self.connection.begin().await?;
+        let result = sqlx::query(sql_stmt::UPDATE_RETURN_STATUS)
+            .bind(String::from(return_status.clone()))
+            .bind(refund_amount.map(|a| a.to_string()))
+            .bind(return_id)
+            .bind(String::from(rma.data.status))
+            .execute(&mut *tx)
+            .await?;
+
+        match result.rows_affected() {
+            1 => {}
+            0 => {
+                return Err(super::Error::BadInput(
+                    "return status changed concurrently".to_string(),
+                ))
+            }
+            n => {
+                return Err(super::Error::ProviderFailure(format!(
+                    "{} rows affected, expected 1 or 0",
+                    n
+                )))
+            }
+        }
+
+        if let (model::ReturnStatus::Received, Some(location_id)) = (&return_status, restock_at) {
+            let mut product_ids = BTreeSet::new();
+            for line in &rma.data.lines {
+                inventory::insert_stock_movement(
+                    &mut tx,
+                    line.product_id,
+                    location_id,
+                    line.quantity,
+                    model::StockMovementReason::Return,
+                    Some(format!("return:{}", return_id)),
+                )
+                .await?;
+                product_ids.insert(line.product_id);
+            }
+            for product_id in product_ids {
+                inventory::allocate_backorders(&mut tx, product_id).await?;
+            }
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+impl SqliteProvider {
+    /// Refunds on an order can't add up to more than was paid on it
+    async fn check_refund(
+        &mut self,
+        order_id: i64,
+        return_id: i64,
+        amount: Decimal,
+    ) -> Result<(), super::Error> {
+        let Some(order) = self.get_order(order_id).await? else {
+            return Err(super::Error::NotFound(format!("order {}", order_id)));
+        };
+
+        let refunded: Decimal = self
+            .get_returns_by_order_id(order_id)
+            .await?
+            .iter()
+            .filter(|r| r.id != return_id)
+            .filter_map(|r| r.data.refund_amount)
+            .sum();
+
+        let minor_units = model::minor_units(&order.data.currency);
+        if model::round_minor(refunded + amount, minor_units) > order.data.amount_paid {
+            return Err(super::Error::BadInput(format!(
+                "refunds would exceed the {} paid on order {}",
+                order.data.amount_paid, order_id
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+fn return_from_row(row: &sqlx::sqlite::SqliteRow) -> Result<model::ReturnDetails, super::Error> {
+    let status: String = row.try_get("returnStatus")?;
+    let refund_amount: Option<String> = row.try_get("refundAmount")?;
+
+    Ok(model::ReturnDetails {
+        fulfillment_id: row.try_get("fulfillmentId")?,
+        order_id: row.try_get("orderId")?,
+        status: status.parse().map_err(super::Error::ProviderFailure)?,
+        reason: row.try_get("reason")?,
+        requested_at: row.try_get("requestedAt")?,
+        refund_amount: match refund_amount {
+            Some(_) => Some(super::get_decimal(row, "refundAmount")?),
+            None => None,
+        },
+        lines: Vec::new(),
+    })
+}
+
+async fn with_lines(
+    conn: &mut SqliteConnection,
+    return_id: i64,
+    mut details: model::ReturnDetails,
+) -> Result<model::ReturnDetails, super::Error> {
+    let mut rows = sqlx::query(sql_stmt::SELECT_RETURN_LINES)
+        .bind(return_id)
+        .fetch(&mut *conn);
+
+    while let Some(row) = rows.try_next().await? {
+        details.lines.push(model::ReturnLine {
+            line_item_id: row.try_get("lineItemId")?,
+            product_id: row.try_get("productId")?,
+            quantity: row.try_get("quantity")?,
+        });
+    }
+
+    Ok(details)
+}
+
+mod sql_stmt {
+    pub const CREATE_RETURN_TABLE: &str = r#"
+        CREATE TABLE IF NOT EXISTS returns (
+            id INTEGER NOT NULL UNIQUE PRIMARY KEY,
+            fulfillmentId INTEGER NOT NULL,
+            orderId INTEGER,
+            returnStatus TEXT NOT NULL,
+            reason TEXT,
+            requestedAt TEXT NOT NULL,
+            refundAmount TEXT
+        );
+    "#;
+
+    pub const CREATE_RETURN_LINE_TABLE: &str = r#"
+        CREATE TABLE IF NOT EXISTS returnLines (
+            id INTEGER NOT NULL UNIQUE PRIMARY KEY,
+            returnId INTEGER NOT NULL,
+            lineItemId INTEGER NOT NULL,
+            productId INTEGER NOT NULL,
+            quantity INTEGER NOT NULL
+        );
+    "#;
+
+    pub const INSERT_RETURN: &str = r#"
+        INSERT INTO returns (fulfillmentId, orderId, returnStatus, reason, requestedAt)
+        VALUES ( $1, $2, $3, $4, $5 );
+    "#;
+
+    pub const INSERT_RETURN_LINE: &str = r#"
+        INSERT INTO returnLines (returnId, lineItemId, productId, quantity)
+        VALUES ( $1, $2, $3, $4 );
+    "#;
+
+    pub const SELECT_RETURNED_QUANTITY: &str = r#"
+        SELECT COALESCE(SUM(returnLines.quantity), 0) AS returned
+        FROM returnLines
+        JOIN returns ON returns.id = returnLines.returnId
+        WHERE returnLines.lineItemId = $1 AND returns.returnStatus != 'Rejected';
+    "#;
+
+    pub const SELECT_RETURN: &str = r#"
+        SELECT * FROM returns WHERE id=$1;
+    "#;
+
+    pub const SELECT_RETURNS_BY_ORDER_ID: &str = r#"
+        SELECT * FROM returns WHERE orderId=$1 ORDER BY id;
+    "#;
+
+    pub const SELECT_RETURN_LINES: &str = r#"
+        SELECT lineItemId, productId, quantity FROM returnLines WHERE returnId=$1 ORDER BY id;
+    "#;
+
+    pub const UPDATE_RETURN_STATUS: &str = r#"
+        UPDATE returns SET returnStatus = $1, refundAmount = COALESCE($2, refundAmount)
+        WHERE id = $3 AND returnStatus = $4;
+    "#;
+}
+
+#[cfg(test)]
+mod test {
+    use rust_decimal::Decimal;
+
+    use crate::{
+        model,
+        provider::SqliteProvider,
+        service::{
+            currency::ExchangeRateService, fulfillment::FulfillmentService,
+            inventory::InventoryService, line_item::LineItemService, order::OrderService,
+            price_list::PriceListService, product::ProductService, rma::ReturnService,
+            tax::TaxService,
+        },
+    };
+
+    /// Paid order with a fulfilled fulfillment of two units of product 1
+    async fn setup() -> (SqliteProvider, i64, i64, i64) {
+        let mut provider = SqliteProvider::new_memory().await.unwrap();
+        OrderService::init_provider(&mut provider).await.unwrap();
+        ProductService::init_provider(&mut provider).await.unwrap();
+        PriceListService::init_provider(&mut provider)
+            .await
+            .unwrap();
+        TaxService::init_provider(&mut provider).await.unwrap();
+        ExchangeRateService::init_provider(&mut provider)
+            .await
+            .unwrap();
+        FulfillmentService::init_provider(&mut provider)
+            .await
+            .unwrap();
+        LineItemService::init_provider(&mut provider).await.unwrap();
+        InventoryService::init_provider(&mut provider)
+            .await
+            .unwrap();
+        ReturnService::init_provider(&mut provider).await.unwrap();
+
+        provider
+            .create_product(&model::ProductDetails {
+                sku: "SKU-1".to_string(),
+                description: "widget".to_string(),
+                base_price: Decimal::new(1000, 2),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        provider
+            .record_stock_movement(1, None, 5, model::StockMovementReason::Receipt, None)
+            .await
+            .unwrap();
+
+        let order_id = provider.create_order(None, None, "USD").await.unwrap();
+        provider.add_order_line(order_id, 1, 2, None).await.unwrap();
+        provider
+            .record_payment(
+                order_id,
+                model::PaymentMethod::Cash,
+                Decimal::new(2000, 2),
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+
+        let fulfillment_id = provider
+            .create_fulfillment(&model::NewFulfillment {
+                order_id: Some(order_id),
+                ..model::NewFulfillment::from(model::FulfillmentType::StockPickUp)
+            })
+            .await
+            .unwrap();
+        let line_item_id = provider
+            .create_line_item(fulfillment_id, 1, 2, None)
+            .await
+            .unwrap()[0];
+
+        // nothing can be picked until work on the fulfillment starts
+        assert!(provider
+            .set_quantity_fulfilled(line_item_id, 2)
+            .await
+            .is_err());
+
+        for status in [
+            model::FulfillmentStatus::Initialized,
+            model::FulfillmentStatus::InProgress,
+        ] {
+            provider
+                .set_fulfillment_status(&fulfillment_id, &status.into())
+                .await
+                .unwrap();
+        }
+        assert!(provider
+            .set_quantity_fulfilled(line_item_id, 3)
+            .await
+            .is_err());
+        provider
+            .set_quantity_fulfilled(line_item_id, 2)
+            .await
+            .unwrap();
+        provider
+            .set_fulfillment_status(&fulfillment_id, &model::FulfillmentStatus::Fulfilled.into())
+            .await
+            .unwrap();
+
+        (provider, order_id, fulfillment_id, line_item_id)
+    }
+
+    #[tokio::test]
+    async fn test_return_quantities() {
+        let (mut provider, _, fulfillment_id, line_item_id) = setup().await;
+        assert_eq!(provider.get_stock_level(1).await.unwrap().on_hand, 3);
+
+        assert!(provider
+            .create_return(fulfillment_id, None, &[(line_item_id, 3)])
+            .await
+            .is_err());
+        assert!(provider
+            .create_return(fulfillment_id, None, &[(line_item_id + 1, 1)])
+            .await
+            .is_err());
+
+        let first = provider
+            .create_return(fulfillment_id, None, &[(line_item_id, 1)])
+            .await
+            .unwrap();
+        // split across lines of the same request still counts together
+        assert!(provider
+            .create_return(
+                fulfillment_id,
+                None,
+                &[(line_item_id, 1), (line_item_id, 1)]
+            )
+            .await
+            .is_err());
+
+        // rejected returns free their quantity up again
+        provider
+            .set_return_status(first, model::ReturnStatus::Rejected, None)
+            .await
+            .unwrap();
+        provider
+            .create_return(fulfillment_id, None, &[(line_item_id, 2)])
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_return_flow() {
+        let (mut provider, order_id, fulfillment_id, line_item_id) = setup().await;
+
+        let return_id = provider
+            .create_return(
+                fulfillment_id,
+                Some("damaged".to_string()),
+                &[(line_item_id, 1)],
+            )
+            .await
+            .unwrap();
+
+        assert!(provider
+            .set_return_status(return_id, model::ReturnStatus::Received, None)
+            .await
+            .is_err());
+        provider
+            .set_return_status(return_id, model::ReturnStatus::Approved, None)
+            .await
+            .unwrap();
+        provider
+            .set_return_status(return_id, model::ReturnStatus::Received, None)
+            .await
+            .unwrap();
+        assert_eq!(provider.get_stock_level(1).await.unwrap().on_hand, 4);
+
+        assert!(provider
+            .set_return_status(return_id, model::ReturnStatus::Refunded, None)
+            .await
+            .is_err());
+        assert!(provider
+            .set_return_status(
+                return_id,
+                model::ReturnStatus::Refunded,
+                Some(Decimal::new(2500, 2))
+            )
+            .await
+            .is_err());
+        provider
+            .set_return_status(
+                return_id,
+                model::ReturnStatus::Refunded,
+                Some(Decimal::new(1000, 2)),
+            )
+            .await
+            .unwrap();
+
+        let returns = provider.get_returns_by_order_id(order_id).await.unwrap();
+        assert_eq!(returns.len(), 1);
+        assert_eq!(returns[0].data.status, model::ReturnStatus::Refunded);
+        assert_eq!(returns[0].data.refund_amount, Some(Decimal::new(1000, 2)));
+        assert_eq!(returns[0].data.lines[0].quantity, 1);
+    }
+
+    #[tokio::test]
+    async fn test_received_returns_fill_backorders() {
+        let (mut provider, _, fulfillment_id, line_item_id) = setup().await;
+
+        // three left on the shelf, so one of these four waits on a backorder
+        let waiting_id = provider
+            .create_fulfillment(&model::NewFulfillment::from(
+                model::FulfillmentType::StockPickUp,
+            ))
+            .await
+            .unwrap();
+        provider
+            .create_line_item(waiting_id, 1, 4, None)
+            .await
+            .unwrap();
+        provider
+            .set_fulfillment_status(&waiting_id, &model::FulfillmentStatus::Initialized.into())
+            .await
+            .unwrap();
+        assert_eq!(
+            provider.get_backorders(1).await.unwrap()[0]
+                .data
+                .quantity_reserved,
+            0
+        );
+
+        let return_id = provider
+            .create_return(fulfillment_id, None, &[(line_item_id, 1)])
+            .await
+            .unwrap();
+        for status in [model::ReturnStatus::Approved, model::ReturnStatus::Received] {
+            provider
+                .set_return_status(return_id, status, None)
+                .await
+                .unwrap();
+        }
+        assert_eq!(
+            provider.get_backorders(1).await.unwrap()[0]
+                .data
+                .quantity_reserved,
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unpicked_returns_skip_stock() {
+        let (mut provider, _, _, _) = setup().await;
+
+        let fulfillment_id = provider
+            .create_fulfillment(&model::NewFulfillment::from(
+                model::FulfillmentType::Service,
+            ))
+            .await
+            .unwrap();
+        let line_item_id = provider
+            .create_line_item(fulfillment_id, 1, 1, None)
+            .await
+            .unwrap()[0];
+        provider
+            .set_fulfillment_status(
+                &fulfillment_id,
+                &model::FulfillmentStatus::InProgress.into(),
+            )
+            .await
+            .unwrap();
+        provider
+            .set_quantity_fulfilled(line_item_id, 1)
+            .await
+            .unwrap();
+        provider
+            .set_fulfillment_status(&fulfillment_id, &model::FulfillmentStatus::Fulfilled.into())
+            .await
+            .unwrap();
+
+        let return_id = provider
+            .create_return(fulfillment_id, None, &[(line_item_id, 1)])
+            .await
+            .unwrap();
+        for status in [model::ReturnStatus::Approved, model::ReturnStatus::Received] {
+            provider
+                .set_return_status(return_id, status, None)
+                .await
+                .unwrap();
+        }
+        assert_eq!(provider.get_stock_level(1).await.unwrap().on_hand, 3);
+    }
+}
@@ -0,0 +1,1182 @@
+use std::{collections::BTreeMap, fmt::Display, sync::Arc};
+
+use axum::http::StatusCode;
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use log::warn;
+use sqlx::{Acquire, Row, SqliteConnection};
+
+use crate::{
+    carrier::Carrier,
+    model::{self, ToRecord},
+    provider::SqliteProvider,
+    service::{
+        fulfillment::{self, FulfillmentService},
+        line_item::LineItemService,
+    },
+};
+
+pub trait ShipmentService {
+    type Error: Display + Into<StatusCode>;
+
+    async fn init_provider(&mut self) -> Result<(), Self::Error>;
+
+    /// Packs line quantities from a delivery or drop-ship fulfillment into a shipment.
+    ///
+    /// Across all its shipments no more of a line can be shipped than was picked, or
+    /// ordered for drop-ships.
+    async fn create_shipment(&mut self, shipment: &model::NewShipment) -> Result<i64, Self::Error>;
+
+    async fn get_shipment(
+        &mut self,
+        shipment_id: i64,
+    ) -> Result<Option<model::Record<model::ShipmentDetails>>, Self::Error>;
+
+    async fn get_shipments_by_fulfillment_id(
+        &mut self,
+        fulfillment_id: i64,
+    ) -> Result<Vec<model::Record<model::ShipmentDetails>>, Self::Error>;
+
+    /// Moves a shipment along `Pending -> Shipped -> Delivered`, `occurred_at` defaults
+    /// to now.
+    ///
+    /// Once every line on the fulfillment has been delivered the fulfillment is moved
+    /// to `Fulfilled` in the same transaction, if its workflow allows that step from
+    /// where it is. If that fails the delivery is still recorded and the failure logged.
+    async fn record_shipment_event(
+        &mut self,
+        shipment_id: i64,
+        shipment_status: model::ShipmentStatus,
+        occurred_at: Option<DateTime<Utc>>,
+    ) -> Result<(), Self::Error>;
+
+    /// Rates the shipment's carrier integration offers for its package
+    async fn quote_shipment_rates(
+        &mut self,
+        shipment_id: i64,
+    ) -> Result<Vec<model::RateQuote>, Self::Error>;
+
+    /// Buys a label for a pending shipment through its carrier integration and records
+    /// the tracking number, at the cheapest rate unless a service level is given.
+    async fn buy_shipment_label(
+        &mut self,
+        shipment_id: i64,
+        service_level: Option<String>,
+    ) -> Result<model::Label, Self::Error>;
+
+    /// Voids a bought label while the shipment hasn't gone out yet
+    async fn void_shipment_label(&mut self, shipment_id: i64) -> Result<(), Self::Error>;
+
+    /// Fetches tracking from the carrier and records the events the shipment is missing
+    async fn sync_shipment_tracking(
+        &mut self,
+        shipment_id: i64,
+    ) -> Result<model::Record<model::ShipmentDetails>, Self::Error>;
+}
+
+impl ShipmentService for SqliteProvider {
+    type Error = super::Error;
+
+    async fn init_provider(&mut self) -> Result<(), Self::Error> {
+        let mut conn = self.connection.acquire().await?;
+        sqlx::query(sql_stmt::CREATE_SHIPMENT_TABLE)
+            .execute(&mut *conn)
+            .await?;
+        sqlx::query(sql_stmt::CREATE_SHIPMENT_LINE_TABLE)
+            .execute(&mut *conn)
+            .await?;
+        sqlx::query(sql_stmt::CREATE_SHIPMENT_EVENT_TABLE)
+            .execute(&mut *conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn create_shipment(&mut self, shipment: &model::NewShipment) -> Result<i64, Self::Error> {
+        let fulfillment_id = shipment.fulfillment_id;
+        let Some(fulfillment) = self.get_fulfillment(&fulfillment_id).await? else {
+            return Err(super::Error::NotFound(format!(
+                "fulfillment {}",
+                fulfillment_id
+            )));
+        };
+
+        let fulfillment_type = fulfillment.data.fulfillment_type;
+        if !fulfillment_type.ships_to_customer() {
+            return Err(super::Error::BadInput(format!(
+                "{:?} fulfillments aren't shipped",
+                fulfillment_type
+            )));
+        }
+        if matches!(
+            fulfillment.data.status,
+            model::FulfillmentStatus::New | model::FulfillmentStatus::Fulfilled
+        ) {
+            return Err(super::Error::BadInput(format!(
+                "fulfillment {} isn't under way",
+                fulfillment_id
+            )));
+        }
+
+        if shipment.carrier.trim().is_empty() {
+            return Err(super::Error::BadInput(
+                "a shipment needs a carrier".to_string(),
+            ));
+        }
+        let package = [
+            shipment.weight_grams,
+            shipment.length_mm,
+            shipment.width_mm,
+            shipment.height_mm,
+        ];
+        if package.iter().flatten().any(|v| *v <= 0) {
+            return Err(super::Error::BadInput(
+                "package weight and dimensions must be positive".to_string(),
+            ));
+        }
+        if shipment.lines.is_empty() {
+            return Err(super::Error::BadInput(
+                "a shipment needs at least one line".to_string(),
+            ));
+        }
+
+        let mut requested: BTreeMap<i64, i64> = BTreeMap::new();
+        for line in &shipment.lines {
+            if line.quantity <= 0 {
+                return Err(super::Error::BadInput(
+                    "shipment quantity must be positive".to_string(),
+                ));
+            }
+            *requested.entry(line.line_item_id).or_default() += line.quantity;
+        }
+
+        let line_items = self
+            .get_line_items_by_fulfillment_id(fulfillment_id)
+            .await?;
+
+        let mut tx = self.connection.begin().await?;
+        let mut shipment_lines = Vec::new();
+        for (line_item_id, quantity) in requested {
+            let Some(line_item) = line_items.iter().find(|l| l.id == line_item_id) else {
+                return Err(super::Error::BadInput(format!(
+                    "line item {} isn't on fulfillment {}",
+                    line_item_id, fulfillment_id
+                )));
+            };
+
+            let row = sqlx::query(sql_stmt::SELECT_SHIPPED_QUANTITY)
+                .bind(line_item_id)
+                .fetch_one(&mut *tx)
+                .await?;
+            let shipped: i64 = row.try_get("shipped")?;
+
+            // stock has to be picked before it can go out
+            let shippable = match fulfillment_type.uses_stock() {
+                true => line_item.data.quantity_fulfilled,
+                false => line_item.data.quantity,
+            };
+            if shipped + quantity > shippable {
+                return Err(super::Error::BadInput(format!(
+                    "only {} of line item {} can be shipped",
+                    shippable - shipped,
+                    line_item_id
+                )));
+            }
+
+            shipment_lines.push(model::ShipmentLine {
+                line_item_id,
+                product_id: line_item.data.product_id,
+                quantity,
+            });
+        }
+
+        let result = sqlx::query(sql_stmt::INSERT_SHIPMENT)
+            .bind(fulfillment_id)
+            .bind(shipment.carrier.trim())
+            .bind(&shipment.service_level)
+            .bind(&shipment.tracking_number)
+            .bind(shipment.weight_grams)
+            .bind(shipment.length_mm)
+            .bind(shipment.width_mm)
+            .bind(shipment.height_mm)
+            .bind(String::from(model::ShipmentStatus::Pending))
+            .execute(&mut *tx)
+            .await?;
+        let shipment_id = result.last_insert_rowid();
+
+        for line in shipment_lines {
+            sqlx::query(sql_stmt::INSERT_SHIPMENT_LINE)
+                .bind(shipment_id)
+                .bind(line.line_item_id)
+                .bind(line.product_id)
+                .bind(line.quantity)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        sqlx::query(sql_stmt::INSERT_SHIPMENT_EVENT)
+            .bind(shipment_id)
+            .bind(String::from(model::ShipmentStatus::Pending))
+            .bind(Utc::now())
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(shipment_id)
+    }
+
+    async fn get_shipment(
+        &mut self,
+        shipment_id: i64,
+    ) -> Result<Option<model::Record<model::ShipmentDetails>>, Self::Error> {
+        let mut conn = self.connection.acquire().await?;
+        let result = sqlx::query(sql_stmt::SELECT_SHIPMENT)
+            .bind(shipment_id)
+            .fetch_optional(&mut *conn)
+            .await?;
+
+        let Some(row) = result else {
+            return Ok(None);
+        };
+
+        let details = shipment_from_row(&row)?;
+        Ok(Some(
+            with_lines_and_events(&mut conn, shipment_id, details)
+                .await?
+                .to_record(shipment_id),
+        ))
+    }
+
+    async fn get_shipments_by_fulfillment_id(
+        &mut self,
+        fulfillment_id: i64,
+    ) -> Result<Vec<model::Record<model::ShipmentDetails>>, Self::Error> {
+        let mut conn = self.connection.acquire().await?;
+        let rows = sqlx::query(sql_stmt::SELECT_SHIPMENTS_BY_FULFILLMENT_ID)
+            .bind(fulfillment_id)
+            .fetch_all(&mut *conn)
+            .await?;
+
+        let mut records = Vec::new();
+        for row in rows {
+            let shipment_id: i64 = row.try_get("id")?;
+            let details = shipment_from_row(&row)?;
+            records.push(
+                with_lines_and_events(&mut conn, shipment_id, details)
+                    .await?
+                    .to_record(shipment_id),
+            );
+        }
+
+        Ok(records)
+    }
+
+    async fn record_shipment_event(
+        &mut self,
+        shipment_id: i64,
+        shipment_status: model::ShipmentStatus,
+        occurred_at: Option<DateTime<Utc>>,
+    ) -> Result<(), Self::Error> {
+        let Some(shipment) = self.get_shipment(shipment_id).await? else {
+            return Err(super::Error::NotFound(format!("shipment {}", shipment_id)));
+        };
+
+        if !shipment_status
+            .allowed_priors()
+            .contains(&shipment.data.status)
+        {
+            return Err(super::Error::BadInput(
+                "bad shipment status transition".to_string(),
+            ));
+        }
+
+        let occurred_at = occurred_at.unwrap_or_else(Utc::now);
+        if shipment.data.shipped_at.is_some_and(|s| occurred_at < s) {
+            return Err(super::Error::BadInput(
+                "a shipment can't be delivered before it shipped".to_string(),
+            ));
+        }
+
+        let fulfillment = match shipment_status {
+            model::ShipmentStatus::Delivered => {
+                self.fulfillment_to_close(shipment.data.fulfillment_id)
+                    .await?
+            }
+            _ => None,
+        };
+
+        let mut tx = self.connection.begin().await?;
+        let result = sqlx::query(sql_stmt::UPDATE_SHIPMENT_STATUS)
+            .bind(String::from(shipment_status.clone()))
+            .bind(shipment_id)
+            .bind(String::from(shipment.data.status))
+            .execute(&mut *tx)
+            .await?;
+
+        match result.rows_affected() {
+            1 => {}
+            0 => {
+                return Err(super::Error::BadInput(
+                    "shipment status changed concurrently".to_string(),
+                ))
+            }
+            n => {
+                return Err(super::Error::ProviderFailure(format!(
+                    "{} rows affected, expected 1 or 0",
+                    n
+                )))
+            }
+        }
+
+        sqlx::query(sql_stmt::INSERT_SHIPMENT_EVENT)
+            .bind(shipment_id)
+            .bind(String::from(shipment_status.clone()))
+            .bind(occurred_at)
+            .execute(&mut *tx)
+            .await?;
+
+        if let Some(fulfillment) = fulfillment {
+            fulfill_if_delivered(&mut tx, &fulfillment).await?;
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn quote_shipment_rates(
+        &mut self,
+        shipment_id: i64,
+    ) -> Result<Vec<model::RateQuote>, Self::Error> {
+        let (_, carrier, request) = self.carrier_request(shipment_id).await?;
+        carrier
+            .quote_rates(&request)
+            .await
+            .map_err(|e| carrier_error(carrier.as_ref(), e))
+    }
+
+    async fn buy_shipment_label(
+        &mut self,
+        shipment_id: i64,
+        service_level: Option<String>,
+    ) -> Result<model::Label, Self::Error> {
+        let (shipment, carrier, request) = self.carrier_request(shipment_id).await?;
+
+        if shipment.data.status != model::ShipmentStatus::Pending {
+            return Err(super::Error::BadInput(format!(
+                "shipment {} has already gone out",
+                shipment_id
+            )));
+        }
+        if shipment.data.tracking_number.is_some() {
+            return Err(super::Error::BadInput(format!(
+                "shipment {} already has a tracking number",
+                shipment_id
+            )));
+        }
+
+        let service_level = match service_level {
+            Some(service_level) => service_level,
+            None => {
+                let quotes = carrier
+                    .quote_rates(&request)
+                    .await
+                    .map_err(|e| carrier_error(carrier.as_ref(), e))?;
+                let Some(cheapest) = quotes.into_iter().min_by_key(|q| q.amount) else {
+                    return Err(super::Error::BadInput(format!(
+                        "{} has no rates for shipment {}",
+                        carrier.name(),
+                        shipment_id
+                    )));
+                };
+                cheapest.service_level
+            }
+        };
+
+        let label = carrier
+            .buy_label(&request, &service_level)
+            .await
+            .map_err(|e| carrier_error(carrier.as_ref(), e))?;
+
+        let mut conn = self.connection.acquire().await?;
+        let result = sqlx::query(sql_stmt::UPDATE_SHIPMENT_LABEL)
+            .bind(&label.tracking_number)
+            .bind(&label.service_level)
+            .bind(label.amount.to_string())
+            .bind(&label.currency)
+            .bind(shipment_id)
+            .execute(&mut *conn)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            carrier
+                .void_label(&label.tracking_number)
+                .await
+                .map_err(|e| carrier_error(carrier.as_ref(), e))?;
+            return Err(super::Error::BadInput(format!(
+                "shipment {} changed while buying its label",
+                shipment_id
+            )));
+        }
+
+        Ok(label)
+    }
+
+    async fn void_shipment_label(&mut self, shipment_id: i64) -> Result<(), Self::Error> {
+        let (shipment, carrier, _) = self.carrier_request(shipment_id).await?;
+
+        if shipment.data.status != model::ShipmentStatus::Pending {
+            return Err(super::Error::BadInput(format!(
+                "shipment {} has already gone out",
+                shipment_id
+            )));
+        }
+        let (Some(tracking_number), Some(_)) =
+            (shipment.data.tracking_number, shipment.data.label_cost)
+        else {
+            return Err(super::Error::BadInput(format!(
+                "shipment {} has no label bought through {}",
+                shipment_id,
+                carrier.name()
+            )));
+        };
+
+        carrier
+            .void_label(&tracking_number)
+            .await
+            .map_err(|e| carrier_error(carrier.as_ref(), e))?;
+
+        let mut conn = self.connection.acquire().await?;
+        sqlx::query(sql_stmt::CLEAR_SHIPMENT_LABEL)
+            .bind(shipment_id)
+            .execute(&mut *conn)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn sync_shipment_tracking(
+        &mut self,
+        shipment_id: i64,
+    ) -> Result<model::Record<model::ShipmentDetails>, Self::Error> {
+        let (shipment, carrier, _) = self.carrier_request(shipment_id).await?;
+
+        let Some(tracking_number) = shipment.data.tracking_number else {
+            return Err(super::Error::BadInput(format!(
+                "shipment {} has no tracking number",
+                shipment_id
+            )));
+        };
+
+        let events = carrier
+            .track(&tracking_number)
+            .await
+            .map_err(|e| carrier_error(carrier.as_ref(), e))?;
+
+        let mut status = shipment.data.status;
+        for event in events {
+            if event.status.allowed_priors().contains(&status) {
+                self.record_shipment_event(
+                    shipment_id,
+                    event.status.clone(),
+                    Some(event.occurred_at),
+                )
+                .await?;
+                status = event.status;
+            }
+        }
+
+        match self.get_shipment(shipment_id).await? {
+            Some(shipment) => Ok(shipment),
+            None => Err(super::Error::NotFound(format!("shipment {}", shipment_id))),
+        }
+    }
+}
+
+/// Moves a fulfillment to `Fulfilled` once everything on it has been delivered, inside the
+/// transaction recording the delivery. If the stock can't be issued the delivery still
+/// stands and the fulfillment is left for someone to close by hand.
+async fn fulfill_if_delivered(
+    conn: &mut SqliteConnection,
+    fulfillment: &model::Record<model::FulfillmentDetails>,
+) -> Result<(), super::Error> {
+    let row = sqlx::query(sql_stmt::SELECT_UNDELIVERED_LINE_COUNT)
+        .bind(fulfillment.id)
+        .fetch_one(&mut *conn)
+        .await?;
+    let undelivered: i64 = row.try_get("undelivered")?;
+    if undelivered > 0 {
+        return Ok(());
+    }
+
+    let mut savepoint = conn.begin().await?;
+    match fulfillment::apply_fulfillment_status(
+        &mut savepoint,
+        fulfillment,
+        &model::FulfillmentStatus::Fulfilled,
+    )
+    .await
+    {
+        Ok(_) => savepoint.commit().await?,
+        Err(e) => {
+            warn!("{}", e);
+            warn!("delivery couldn't fulfill fulfillment {}", fulfillment.id);
+            savepoint.rollback().await?;
+        }
+    }
+    Ok(())
+}
+
+impl SqliteProvider {
+    /// Shipment with its carrier integration and the package it's asking about
+    async fn carrier_request(
+        &mut self,
+        shipment_id: i64,
+    ) -> Result<
+        (
+            model::Record<model::ShipmentDetails>,
+            Arc<dyn Carrier>,
+            model::RateRequest,
+        ),
+        super::Error,
+    > {
+        let Some(shipment) = self.get_shipment(shipment_id).await? else {
+            return Err(super::Error::NotFound(format!("shipment {}", shipment_id)));
+        };
+        let Some(carrier) = self.carriers.get(&shipment.data.carrier) else {
+            return Err(super::Error::BadInput(format!(
+                "no integration for carrier {}",
+                shipment.data.carrier
+            )));
+        };
+
+        let ship_to = match self.get_fulfillment(&shipment.data.fulfillment_id).await? {
+            Some(fulfillment) => fulfillment.data.ship_to,
+            None => None,
+        };
+        let Some(ship_to) = ship_to else {
+            return Err(super::Error::ProviderFailure(format!(
+                "shipment {} has nowhere to go",
+                shipment_id
+            )));
+        };
+
+        let request = model::RateRequest {
+            ship_to,
+            weight_grams: shipment.data.weight_grams,
+            length_mm: shipment.data.length_mm,
+            width_mm: shipment.data.width_mm,
+            height_mm: shipment.data.height_mm,
+        };
+        Ok((shipment, carrier, request))
+    }
+
+    /// Fulfillment a delivery could complete, if it can move to `Fulfilled` at all. A
+    /// delivery is recorded whatever happens to its fulfillment, so a failed check is only
+    /// logged.
+    async fn fulfillment_to_close(
+        &mut self,
+        fulfillment_id: i64,
+    ) -> Result<Option<model::Record<model::FulfillmentDetails>>, super::Error> {
+        let Some(fulfillment) = self.get_fulfillment(&fulfillment_id).await? else {
+            return Ok(None);
+        };
+
+        let can_fulfill = self
+            .workflows
+            .get(&fulfillment.data.fulfillment_type)
+            .and_then(|w| {
+                w.transition(
+                    &fulfillment.data.status,
+                    &model::FulfillmentStatus::Fulfilled,
+                )
+            })
+            .is_some();
+        if !can_fulfill {
+            return Ok(None);
+        }
+
+        match self
+            .check_status_change(&fulfillment, &model::FulfillmentStatus::Fulfilled.into())
+            .await
+        {
+            Ok(_) => Ok(Some(fulfillment)),
+            Err(e) => {
+                warn!("{}", e);
+                warn!("fulfillment {} can't be closed by delivery", fulfillment_id);
+                Ok(None)
+            }
+        }
+    }
+}
+
+fn carrier_error(carrier: &dyn Carrier, message: String) -> super::Error {
+    super::Error::BadInput(format!("{} - {}", carrier.name(), message))
+}
+
+fn shipment_from_row(
+    row: &sqlx::sqlite::SqliteRow,
+) -> Result<model::ShipmentDetails, super::Error> {
+    let status: String = row.try_get("shipmentStatus")?;
+    let label_cost: Option<String> = row.try_get("labelCost")?;
+
+    Ok(model::ShipmentDetails {
+        fulfillment_id: row.try_get("fulfillmentId")?,
+        carrier: row.try_get("carrier")?,
+        service_level: row.try_get("serviceLevel")?,
+        tracking_number: row.try_get("trackingNumber")?,
+        label_cost: match label_cost {
+            Some(_) => Some(super::get_decimal(row, "labelCost")?),
+            None => None,
+        },
+        label_currency: row.try_get("labelCurrency")?,
+        weight_grams: row.try_get("weightGrams")?,
+        length_mm: row.try_get("lengthMm")?,
+        width_mm: row.try_get("widthMm")?,
+        height_mm: row.try_get("heightMm")?,
+        status: status.parse().map_err(super::Error::ProviderFailure)?,
+        shipped_at: None,
+        delivered_at: None,
+        lines: Vec::new(),
+        events: Vec::new(),
+    })
+}
+
+async fn with_lines_and_events(
+    conn: &mut SqliteConnection,
+    shipment_id: i64,
+    mut details: model::ShipmentDetails,
+) -> Result<model::ShipmentDetails, super::Error> {
+    let mut rows = sqlx::query(sql_stmt::SELECT_SHIPMENT_LINES)
+        .bind(shipment_id)
+        .fetch(&mut *conn);
+
+    while let Some(row) = rows.try_next().await? {
+        details.lines.push(model::ShipmentLine {
+            line_item_id: row.try_get("lineItemId")?,
+            product_id: row.try_get("productId")?,
+            quantity: row.try_get("quantity")?,
+        });
+    }
+    drop(rows);
+
+    let mut rows = sqlx::query(sql_stmt::SELECT_SHIPMENT_EVENTS)
+        .bind(shipment_id)
+        .fetch(&mut *conn);
+
+    while let Some(row) = rows.try_next().await? {
+        let status: String = row.try_get("shipmentStatus")?;
+        let event = model::ShipmentEvent {
+            status: status.parse().map_err(super::Error::ProviderFailure)?,
+            occurred_at: row.try_get("occurredAt")?,
+        };
+        match event.status {
+            model::ShipmentStatus::Shipped => details.shipped_at = Some(event.occurred_at),
+            model::ShipmentStatus::Delivered => details.delivered_at = Some(event.occurred_at),
+            model::ShipmentStatus::Pending => {}
+        }
+        details.events.push(event);
+    }
+
+    Ok(details)
+}
+
+mod sql_stmt {
+    pub const CREATE_SHIPMENT_TABLE: &str = r#"
+        CREATE TABLE IF NOT EXISTS shipments (
+            id INTEGER NOT NULL UNIQUE PRIMARY KEY,
+            fulfillmentId INTEGER NOT NULL,
+            carrier TEXT NOT NULL,
+            serviceLevel TEXT,
+            trackingNumber TEXT,
+            labelCost TEXT,
+            labelCurrency TEXT,
+            weightGrams INTEGER,
+            lengthMm INTEGER,
+            widthMm INTEGER,
+            heightMm INTEGER,
+            shipmentStatus TEXT NOT NULL
+        );
+    "#;
+
+    pub const CREATE_SHIPMENT_LINE_TABLE: &str = r#"
+        CREATE TABLE IF NOT EXISTS shipmentLines (
+            id INTEGER NOT NULL UNIQUE PRIMARY KEY,
+            shipmentId INTEGER NOT NULL,
+            lineItemId INTEGER NOT NULL,
+            productId INTEGER NOT NULL,
+            quantity INTEGER NOT NULL
+        );
+    "#;
+
+    pub const CREATE_SHIPMENT_EVENT_TABLE: &str = r#"
+        CREATE TABLE IF NOT EXISTS shipmentEvents (
+            id INTEGER NOT NULL UNIQUE PRIMARY KEY,
+            shipmentId INTEGER NOT NULL,
+            shipmentStatus TEXT NOT NULL,
+            occurredAt TEXT NOT NULL
+        );
+    "#;
+
+    pub const INSERT_SHIPMENT: &str = r#"
+        INSERT INTO shipments (
+            fulfillmentId, carrier, serviceLevel, trackingNumber,
+            weightGrams, lengthMm, widthMm, heightMm, shipmentStatus
+        )
+        VALUES ( $1, $2, $3, $4, $5, $6, $7, $8, $9 );
+    "#;
+
+    pub const INSERT_SHIPMENT_LINE: &str = r#"
+        INSERT INTO shipmentLines (shipmentId, lineItemId, productId, quantity)
+        VALUES ( $1, $2, $3, $4 );
+    "#;
+
+    pub const INSERT_SHIPMENT_EVENT: &str = r#"
+        INSERT INTO shipmentEvents (shipmentId, shipmentStatus, occurredAt)
+        VALUES ( $1, $2, $3 );
+    "#;
+
+    pub const SELECT_SHIPPED_QUANTITY: &str = r#"
+        SELECT COALESCE(SUM(quantity), 0) AS shipped
+        FROM shipmentLines WHERE lineItemId = $1;
+    "#;
+
+    pub const SELECT_UNDELIVERED_LINE_COUNT: &str = r#"
+        SELECT COUNT(*) AS undelivered
+        FROM lineItems
+        WHERE lineItems.fulfillmentId = $1
+        AND lineItems.quantity > (
+            SELECT COALESCE(SUM(shipmentLines.quantity), 0)
+            FROM shipmentLines
+            JOIN shipments ON shipments.id = shipmentLines.shipmentId
+            WHERE shipmentLines.lineItemId = lineItems.id
+            AND shipments.shipmentStatus = 'Delivered'
+        );
+    "#;
+
+    pub const SELECT_SHIPMENT: &str = r#"
+        SELECT * FROM shipments WHERE id=$1;
+    "#;
+
+    pub const SELECT_SHIPMENTS_BY_FULFILLMENT_ID: &str = r#"
+        SELECT * FROM shipments WHERE fulfillmentId=$1 ORDER BY id;
+    "#;
+
+    pub const SELECT_SHIPMENT_LINES: &str = r#"
+        SELECT lineItemId, productId, quantity FROM shipmentLines WHERE shipmentId=$1 ORDER BY id;
+    "#;
+
+    pub const SELECT_SHIPMENT_EVENTS: &str = r#"
+        SELECT shipmentStatus, occurredAt FROM shipmentEvents WHERE shipmentId=$1 ORDER BY id;
+    "#;
+
+    pub const UPDATE_SHIPMENT_LABEL: &str = r#"
+        UPDATE shipments
+        SET trackingNumber = $1, serviceLevel = $2, labelCost = $3, labelCurrency = $4
+        WHERE id = $5 AND shipmentStatus = 'Pending' AND trackingNumber IS NULL;
+    "#;
+
+    pub const CLEAR_SHIPMENT_LABEL: &str = r#"
+        UPDATE shipments SET trackingNumber = NULL, labelCost = NULL, labelCurrency = NULL
+        WHERE id = $1;
+    "#;
+
+    pub const UPDATE_SHIPMENT_STATUS: &str = r#"
+        UPDATE shipments SET shipmentStatus = $1
+        WHERE id = $2 AND shipmentStatus = $3;
+    "#;
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{Duration, Utc};
+    use rust_decimal::Decimal;
+
+    use crate::{
+        model,
+        provider::SqliteProvider,
+        service::{
+            customer::CustomerService, fulfillment::FulfillmentService,
+            inventory::InventoryService, line_item::LineItemService, product::ProductService,
+            shipment::ShipmentService,
+        },
+    };
+
+    /// Delivery fulfillment for three units of product 1, under way with two picked
+    async fn setup() -> (SqliteProvider, i64, i64) {
+        let mut provider = SqliteProvider::new_memory().await.unwrap();
+        ProductService::init_provider(&mut provider).await.unwrap();
+        CustomerService::init_provider(&mut provider).await.unwrap();
+        FulfillmentService::init_provider(&mut provider)
+            .await
+            .unwrap();
+        LineItemService::init_provider(&mut provider).await.unwrap();
+        InventoryService::init_provider(&mut provider)
+            .await
+            .unwrap();
+        ShipmentService::init_provider(&mut provider).await.unwrap();
+
+        provider
+            .create_product(&model::ProductDetails {
+                sku: "SKU-1".to_string(),
+                description: "widget".to_string(),
+                base_price: Decimal::new(1000, 2),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        provider
+            .record_stock_movement(1, None, 5, model::StockMovementReason::Receipt, None)
+            .await
+            .unwrap();
+
+        let customer_id = provider
+            .create_customer(&model::CustomerDetails {
+                name: "Ada".to_string(),
+                email: None,
+                phone: None,
+            })
+            .await
+            .unwrap();
+        let address = provider
+            .add_address(&model::AddressDetails {
+                customer_id,
+                label: None,
+                is_default: true,
+                address: model::Address {
+                    recipient: "Ada".to_string(),
+                    line1: "1 Analytical Row".to_string(),
+                    line2: None,
+                    city: "London".to_string(),
+                    region: None,
+                    postal_code: "N1 1AA".to_string(),
+                    country: "GB".to_string(),
+                    latitude: None,
+                    longitude: None,
+                },
+            })
+            .await
+            .unwrap();
+
+        let fulfillment_id = provider
+            .create_fulfillment(&model::NewFulfillment {
+                ship_to_address_id: Some(address.id),
+                ..model::NewFulfillment::from(model::FulfillmentType::StockDelivery)
+            })
+            .await
+            .unwrap();
+        let line_item_id = provider
+            .create_line_item(fulfillment_id, 1, 3, None)
+            .await
+            .unwrap()[0];
+        for status in [
+            model::FulfillmentStatus::Initialized,
+            model::FulfillmentStatus::InProgress,
+        ] {
+            provider
+                .set_fulfillment_status(&fulfillment_id, &status.into())
+                .await
+                .unwrap();
+        }
+        provider
+            .set_quantity_fulfilled(line_item_id, 2)
+            .await
+            .unwrap();
+
+        (provider, fulfillment_id, line_item_id)
+    }
+
+    fn package(fulfillment_id: i64, line_item_id: i64, quantity: i64) -> model::NewShipment {
+        model::NewShipment {
+            fulfillment_id,
+            carrier: "Parcelforce".to_string(),
+            service_level: Some("Express".to_string()),
+            tracking_number: Some("PF123".to_string()),
+            weight_grams: Some(1200),
+            length_mm: Some(300),
+            width_mm: Some(200),
+            height_mm: Some(100),
+            lines: vec![model::ShipmentItem {
+                line_item_id,
+                quantity,
+            }],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_shipment_quantities() {
+        let (mut provider, fulfillment_id, line_item_id) = setup().await;
+
+        // only picked stock can go out
+        assert!(provider
+            .create_shipment(&package(fulfillment_id, line_item_id, 3))
+            .await
+            .is_err());
+        assert!(provider
+            .create_shipment(&package(fulfillment_id, line_item_id + 1, 1))
+            .await
+            .is_err());
+        assert!(provider
+            .create_shipment(&model::NewShipment {
+                weight_grams: Some(0),
+                ..package(fulfillment_id, line_item_id, 1)
+            })
+            .await
+            .is_err());
+
+        provider
+            .create_shipment(&package(fulfillment_id, line_item_id, 1))
+            .await
+            .unwrap();
+        provider
+            .create_shipment(&package(fulfillment_id, line_item_id, 1))
+            .await
+            .unwrap();
+        assert!(provider
+            .create_shipment(&package(fulfillment_id, line_item_id, 1))
+            .await
+            .is_err());
+
+        let shipments = provider
+            .get_shipments_by_fulfillment_id(fulfillment_id)
+            .await
+            .unwrap();
+        assert_eq!(shipments.len(), 2);
+        assert_eq!(shipments[0].data.status, model::ShipmentStatus::Pending);
+        assert_eq!(shipments[0].data.lines[0].product_id, 1);
+    }
+
+    #[tokio::test]
+    async fn test_delivery_fulfills() {
+        let (mut provider, fulfillment_id, line_item_id) = setup().await;
+        provider
+            .set_quantity_fulfilled(line_item_id, 3)
+            .await
+            .unwrap();
+
+        let first = provider
+            .create_shipment(&package(fulfillment_id, line_item_id, 2))
+            .await
+            .unwrap();
+        let second = provider
+            .create_shipment(&package(fulfillment_id, line_item_id, 1))
+            .await
+            .unwrap();
+
+        assert!(provider
+            .record_shipment_event(first, model::ShipmentStatus::Delivered, None)
+            .await
+            .is_err());
+
+        let shipped_at = Utc::now() - Duration::days(2);
+        for shipment_id in [first, second] {
+            provider
+                .record_shipment_event(
+                    shipment_id,
+                    model::ShipmentStatus::Shipped,
+                    Some(shipped_at),
+                )
+                .await
+                .unwrap();
+        }
+        assert!(provider
+            .record_shipment_event(
+                first,
+                model::ShipmentStatus::Delivered,
+                Some(shipped_at - Duration::days(1))
+            )
+            .await
+            .is_err());
+
+        provider
+            .record_shipment_event(first, model::ShipmentStatus::Delivered, None)
+            .await
+            .unwrap();
+        let fulfillment = provider
+            .get_fulfillment(&fulfillment_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            fulfillment.data.status,
+            model::FulfillmentStatus::InProgress
+        );
+
+        // the last package arriving completes the fulfillment and issues the stock
+        provider
+            .record_shipment_event(second, model::ShipmentStatus::Delivered, None)
+            .await
+            .unwrap();
+        let fulfillment = provider
+            .get_fulfillment(&fulfillment_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(fulfillment.data.status, model::FulfillmentStatus::Fulfilled);
+        assert_eq!(provider.get_stock_level(1).await.unwrap().on_hand, 2);
+
+        let shipment = provider.get_shipment(first).await.unwrap().unwrap();
+        assert_eq!(shipment.data.shipped_at, Some(shipped_at));
+        assert!(shipment.data.delivered_at.is_some());
+        assert_eq!(shipment.data.events.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_delivery_stands_when_fulfilling_fails() {
+        let (mut provider, fulfillment_id, line_item_id) = setup().await;
+        provider
+            .set_quantity_fulfilled(line_item_id, 3)
+            .await
+            .unwrap();
+        let shipment_id = provider
+            .create_shipment(&package(fulfillment_id, line_item_id, 3))
+            .await
+            .unwrap();
+        provider
+            .record_shipment_event(shipment_id, model::ShipmentStatus::Shipped, None)
+            .await
+            .unwrap();
+
+        // a count correction leaves too little to issue
+        provider
+            .record_stock_movement(1, None, -4, model::StockMovementReason::Adjustment, None)
+            .await
+            .unwrap();
+        provider
+            .record_shipment_event(shipment_id, model::ShipmentStatus::Delivered, None)
+            .await
+            .unwrap();
+
+        let shipment = provider.get_shipment(shipment_id).await.unwrap().unwrap();
+        assert_eq!(shipment.data.status, model::ShipmentStatus::Delivered);
+        let fulfillment = provider
+            .get_fulfillment(&fulfillment_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            fulfillment.data.status,
+            model::FulfillmentStatus::InProgress
+        );
+        assert_eq!(provider.get_stock_level(1).await.unwrap().on_hand, 1);
+
+        // once the stock is put right it can be closed by hand
+        provider
+            .record_stock_movement(1, None, 4, model::StockMovementReason::Adjustment, None)
+            .await
+            .unwrap();
+        provider
+            .set_fulfillment_status(&fulfillment_id, &model::FulfillmentStatus::Fulfilled.into())
+            .await
+            .unwrap();
+        assert_eq!(provider.get_stock_level(1).await.unwrap().on_hand, 2);
+    }
+
+    #[tokio::test]
+    async fn test_carrier_labels() {
+        let (mut provider, fulfillment_id, line_item_id) = setup().await;
+        provider
+            .set_quantity_fulfilled(line_item_id, 3)
+            .await
+            .unwrap();
+
+        // no integration for hand entered carriers
+        let manual = provider
+            .create_shipment(&package(fulfillment_id, line_item_id, 1))
+            .await
+            .unwrap();
+        assert!(provider.quote_shipment_rates(manual).await.is_err());
+
+        let shipment_id = provider
+            .create_shipment(&model::NewShipment {
+                carrier: "Local".to_string(),
+                service_level: None,
+                tracking_number: None,
+                ..package(fulfillment_id, line_item_id, 2)
+            })
+            .await
+            .unwrap();
+
+        let quotes = provider.quote_shipment_rates(shipment_id).await.unwrap();
+        let economy = quotes
+            .iter()
+            .find(|q| q.service_level == "Economy")
+            .unwrap();
+        assert_eq!(economy.amount, Decimal::new(500, 2));
+
+        let label = provider
+            .buy_shipment_label(shipment_id, None)
+            .await
+            .unwrap();
+        assert_eq!(label.service_level, "Economy");
+        assert_eq!(label.tracking_number, "LC00000001");
+        assert!(provider
+            .buy_shipment_label(shipment_id, None)
+            .await
+            .is_err());
+
+        provider.void_shipment_label(shipment_id).await.unwrap();
+        let shipment = provider.get_shipment(shipment_id).await.unwrap().unwrap();
+        assert_eq!(shipment.data.tracking_number, None);
+        assert!(provider.void_shipment_label(shipment_id).await.is_err());
+
+        // same day labels show as delivered as soon as they're tracked
+        provider
+            .buy_shipment_label(shipment_id, Some("SameDay".to_string()))
+            .await
+            .unwrap();
+        let shipment = provider.sync_shipment_tracking(shipment_id).await.unwrap();
+        assert_eq!(shipment.data.status, model::ShipmentStatus::Delivered);
+        assert_eq!(shipment.data.label_cost, Some(Decimal::new(1900, 2)));
+        assert!(provider.void_shipment_label(shipment_id).await.is_err());
+
+        let fulfillment = provider
+            .get_fulfillment(&fulfillment_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            fulfillment.data.status,
+            model::FulfillmentStatus::InProgress
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tracking_fulfills() {
+        let (mut provider, fulfillment_id, line_item_id) = setup().await;
+        provider
+            .set_quantity_fulfilled(line_item_id, 3)
+            .await
+            .unwrap();
+        let shipment_id = provider
+            .create_shipment(&model::NewShipment {
+                carrier: "Local".to_string(),
+                service_level: None,
+                tracking_number: None,
+                ..package(fulfillment_id, line_item_id, 3)
+            })
+            .await
+            .unwrap();
+        provider
+            .buy_shipment_label(shipment_id, Some("Express".to_string()))
+            .await
+            .unwrap();
+
+        // express labels spend a lookup in transit
+        let shipment = provider.sync_shipment_tracking(shipment_id).await.unwrap();
+        assert_eq!(shipment.data.status, model::ShipmentStatus::Shipped);
+        let shipment = provider.sync_shipment_tracking(shipment_id).await.unwrap();
+        assert_eq!(shipment.data.status, model::ShipmentStatus::Delivered);
+        assert_eq!(shipment.data.events.len(), 3);
+
+        // tracking again doesn't record anything new
+        let shipment = provider.sync_shipment_tracking(shipment_id).await.unwrap();
+        assert_eq!(shipment.data.events.len(), 3);
+
+        let fulfillment = provider
+            .get_fulfillment(&fulfillment_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(fulfillment.data.status, model::FulfillmentStatus::Fulfilled);
+        assert_eq!(provider.get_stock_level(1).await.unwrap().on_hand, 2);
+    }
+}
@@ -0,0 +1,117 @@
+use std::fmt::Display;
+
+use axum::http::StatusCode;
+use futures::TryStreamExt;
+use rust_decimal::Decimal;
+use sqlx::Row;
+
+use crate::{
+    model::{self, ToRecord},
+    provider::SqliteProvider,
+};
+
+pub trait TaxService {
+    type Error: Display + Into<StatusCode>;
+
+    async fn init_provider(&mut self) -> Result<(), Self::Error>;
+
+    async fn create_tax_class(&mut self, name: &str, rate: &Decimal) -> Result<i64, Self::Error>;
+
+    async fn get_tax_classes(
+        &mut self,
+    ) -> Result<Vec<model::Record<model::TaxClassDetails>>, Self::Error>;
+
+    /// Tax rate of the product's tax class, zero when it has none
+    async fn get_product_tax_rate(&mut self, product_id: i64) -> Result<Decimal, Self::Error>;
+}
+
+impl TaxService for SqliteProvider {
+    type Error = super::Error;
+
+    async fn init_provider(&mut self) -> Result<(), Self::Error> {
+        let mut conn = self.connection.acquire().await?;
+        sqlx::query(sql_stmt::CREATE_TABLE)
+            .execute(&mut *conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn create_tax_class(&mut self, name: &str, rate: &Decimal) -> Result<i64, Self::Error> {
+        if *rate < Decimal::ZERO {
+            return Err(super::Error::BadInput(
+                "tax rate can't be negative".to_string(),
+            ));
+        }
+
+        let mut conn = self.connection.acquire().await?;
+        let result = sqlx::query(sql_stmt::INSERT_TAX_CLASS)
+            .bind(name)
+            .bind(rate.to_string())
+            .execute(&mut *conn)
+            .await?;
+
+        Ok(result.last_insert_rowid())
+    }
+
+    async fn get_tax_classes(
+        &mut self,
+    ) -> Result<Vec<model::Record<model::TaxClassDetails>>, Self::Error> {
+        let mut conn = self.connection.acquire().await?;
+        let mut rows = sqlx::query(sql_stmt::SELECT_TAX_CLASSES).fetch(&mut *conn);
+
+        let mut records = Vec::new();
+        while let Some(row) = rows.try_next().await? {
+            records.push(
+                model::TaxClassDetails {
+                    name: row.try_get("name")?,
+                    rate: super::get_decimal(&row, "rate")?,
+                }
+                .to_record(row.try_get("id")?),
+            );
+        }
+
+        Ok(records)
+    }
+
+    async fn get_product_tax_rate(&mut self, product_id: i64) -> Result<Decimal, Self::Error> {
+        let mut conn = self.connection.acquire().await?;
+        let result = sqlx::query(sql_stmt::SELECT_PRODUCT_TAX_RATE)
+            .bind(product_id)
+            .fetch_optional(&mut *conn)
+            .await?;
+
+        let Some(row) = result else {
+            return Err(super::Error::ProductNotFound(format!(
+                "no product {} to tax",
+                product_id
+            )));
+        };
+
+        super::get_decimal(&row, "rate")
+    }
+}
+
+mod sql_stmt {
+    pub const CREATE_TABLE: &str = r#"
+        CREATE TABLE IF NOT EXISTS taxClasses (
+            id INTEGER NOT NULL UNIQUE PRIMARY KEY,
+            name TEXT NOT NULL UNIQUE,
+            rate TEXT NOT NULL
+        );
+    "#;
+
+    pub const INSERT_TAX_CLASS: &str = r#"
+        INSERT INTO taxClasses (name, rate) VALUES ( $1, $2 );
+    "#;
+
+    pub const SELECT_TAX_CLASSES: &str = r#"
+        SELECT id, name, rate FROM taxClasses ORDER BY id;
+    "#;
+
+    pub const SELECT_PRODUCT_TAX_RATE: &str = r#"
+        SELECT COALESCE(taxClasses.rate, '0') AS rate
+        FROM products
+        LEFT JOIN taxClasses ON taxClasses.id = products.taxClassId
+        WHERE products.id = $1;
+    "#;
+}
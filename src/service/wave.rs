@@ -0,0 +1,503 @@
+use std::{collections::BTreeMap, fmt::Display};
+
+use axum::http::StatusCode;
+use chrono::Utc;
+use sqlx::{Row, SqliteConnection};
+
+use crate::{
+    model::{self, ToRecord},
+    provider::SqliteProvider,
+    service::{
+        fulfillment::{self, FulfillmentService},
+        line_item::LineItemService,
+        product::ProductService,
+    },
+};
+
+pub trait WaveService {
+    type Error: Display + Into<StatusCode>;
+
+    async fn init_provider(&mut self) -> Result<(), Self::Error>;
+
+    /// Groups initialized fulfillments into a wave to be picked together.
+    ///
+    /// Only fulfillments picked from our own shelves can join, and each can only be in
+    /// one wave that's open or started.
+    async fn create_wave(&mut self, fulfillment_ids: &[i64]) -> Result<i64, Self::Error>;
+
+    async fn get_wave(
+        &mut self,
+        wave_id: i64,
+    ) -> Result<Option<model::Record<model::WaveDetails>>, Self::Error>;
+
+    /// What's left to pick across the wave, by location then product
+    async fn get_pick_list(&mut self, wave_id: i64) -> Result<model::PickList, Self::Error>;
+
+    /// Moves every fulfillment in the wave to `InProgress`, all of them or none
+    async fn start_wave(&mut self, wave_id: i64) -> Result<(), Self::Error>;
+}
+
+impl WaveService for SqliteProvider {
+    type Error = super::Error;
+
+    async fn init_provider(&mut self) -> Result<(), Self::Error> {
+        let mut conn = self.connection.acquire().await?;
+        sqlx::query(sql_stmt::CREATE_WAVE_TABLE)
+            .execute(&mut *conn)
+            .await?;
+        sqlx::query(sql_stmt::CREATE_WAVE_FULFILLMENT_TABLE)
+            .execute(&mut *conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn create_wave(&mut self, fulfillment_ids: &[i64]) -> Result<i64, Self::Error> {
+        let mut fulfillment_ids = fulfillment_ids.to_vec();
+        fulfillment_ids.sort();
+        fulfillment_ids.dedup();
+
+        if fulfillment_ids.is_empty() {
+            return Err(super::Error::BadInput(
+                "a wave needs at least one fulfillment".to_string(),
+            ));
+        }
+
+        // checked inside the transaction so nothing can move on or join another wave
+        // between the check and the insert
+        let mut tx = self.connection.begin().await?;
+        for fulfillment_id in &fulfillment_ids {
+            let Some(row) = sqlx::query(sql_stmt::SELECT_FULFILLMENT_STATE)
+                .bind(fulfillment_id)
+                .fetch_optional(&mut *tx)
+                .await?
+            else {
+                return Err(super::Error::NotFound(format!(
+                    "fulfillment {}",
+                    fulfillment_id
+                )));
+            };
+            let fulfillment_type: model::FulfillmentType = row
+                .try_get::<String, _>("fulfillmentType")?
+                .parse()
+                .map_err(super::Error::ProviderFailure)?;
+            let status: model::FulfillmentStatus = row
+                .try_get::<String, _>("fulfillmentStatus")?
+                .parse()
+                .map_err(super::Error::ProviderFailure)?;
+            let waves: i64 = row.try_get("waves")?;
+
+            if !fulfillment_type.is_picked() {
+                return Err(super::Error::BadInput(format!(
+                    "{:?} fulfillments aren't picked",
+                    fulfillment_type
+                )));
+            }
+            if status != model::FulfillmentStatus::Initialized {
+                return Err(super::Error::BadInput(format!(
+                    "fulfillment {} isn't initialized",
+                    fulfillment_id
+                )));
+            }
+            if waves > 0 {
+                return Err(super::Error::BadInput(format!(
+                    "fulfillment {} is already in a wave",
+                    fulfillment_id
+                )));
+            }
+        }
+
+        let result = sqlx::query(sql_stmt::INSERT_WAVE)
+            .bind(String::from(model::WaveStatus::Open))
+            .bind(Utc::now())
+            .execute(&mut *tx)
+            .await?;
+        let wave_id = result.last_insert_rowid();
+
+        for fulfillment_id in &fulfillment_ids {
+            sqlx::query(sql_stmt::INSERT_WAVE_FULFILLMENT)
+                .bind(wave_id)
+                .bind(fulfillment_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+        Ok(wave_id)
+    }
+
+    async fn get_wave(
+        &mut self,
+        wave_id: i64,
+    ) -> Result<Option<model::Record<model::WaveDetails>>, Self::Error> {
+        let mut conn = self.connection.acquire().await?;
+        let result = sqlx::query(sql_stmt::SELECT_WAVE)
+            .bind(wave_id)
+            .fetch_optional(&mut *conn)
+            .await?;
+
+        let Some(row) = result else {
+            return Ok(None);
+        };
+
+        let status: String = row.try_get("waveStatus")?;
+        Ok(Some(
+            model::WaveDetails {
+                status: status.parse().map_err(super::Error::ProviderFailure)?,
+                fulfillment_ids: wave_fulfillment_ids(&mut conn, wave_id).await?,
+                created_at: row.try_get("createdAt")?,
+                started_at: row.try_get("startedAt")?,
+            }
+            .to_record(wave_id),
+        ))
+    }
+
+    async fn get_pick_list(&mut self, wave_id: i64) -> Result<model::PickList, Self::Error> {
+        let Some(wave) = self.get_wave(wave_id).await? else {
+            return Err(super::Error::NotFound(format!("wave {}", wave_id)));
+        };
+
+        let mut lines: BTreeMap<(i64, i64), model::PickListLine> = BTreeMap::new();
+        for fulfillment_id in wave.data.fulfillment_ids {
+            let Some(fulfillment) = self.get_fulfillment(&fulfillment_id).await? else {
+                return Err(super::Error::ProviderFailure(format!(
+                    "wave {} has missing fulfillment {}",
+                    wave_id, fulfillment_id
+                )));
+            };
+            let location_id = fulfillment
+                .data
+                .from_location_id
+                .unwrap_or(model::DEFAULT_LOCATION_ID);
+
+            for line_item in self
+                .get_line_items_by_fulfillment_id(fulfillment_id)
+                .await?
+            {
+                let remaining = line_item.data.quantity - line_item.data.quantity_fulfilled;
+                if remaining <= 0 {
+                    continue;
+                }
+
+                let product_id = line_item.data.product_id;
+                let line = match lines.get_mut(&(location_id, product_id)) {
+                    Some(line) => line,
+                    None => {
+                        let product = self.get_product(&product_id).await?;
+                        lines
+                            .entry((location_id, product_id))
+                            .or_insert(model::PickListLine {
+                                location_id,
+                                product_id,
+                                sku: product.data.sku,
+                                description: product.data.description,
+                                quantity: 0,
+                                fulfillments: Vec::new(),
+                            })
+                    }
+                };
+                line.quantity += remaining;
+                line.fulfillments.push(model::PickAllocation {
+                    fulfillment_id,
+                    line_item_id: line_item.id,
+                    quantity: remaining,
+                });
+            }
+        }
+
+        Ok(model::PickList {
+            wave_id,
+            lines: lines.into_values().collect(),
+        })
+    }
+
+    async fn start_wave(&mut self, wave_id: i64) -> Result<(), Self::Error> {
+        let Some(wave) = self.get_wave(wave_id).await? else {
+            return Err(super::Error::NotFound(format!("wave {}", wave_id)));
+        };
+        if wave.data.status != model::WaveStatus::Open {
+            return Err(super::Error::BadInput(format!(
+                "wave {} has already started",
+                wave_id
+            )));
+        }
+
+        let mut fulfillments = Vec::new();
+        for fulfillment_id in &wave.data.fulfillment_ids {
+            let Some(fulfillment) = self.get_fulfillment(fulfillment_id).await? else {
+                return Err(super::Error::ProviderFailure(format!(
+                    "wave {} has missing fulfillment {}",
+                    wave_id, fulfillment_id
+                )));
+            };
+            self.check_transition(&fulfillment, &model::FulfillmentStatus::InProgress)
+                .await?;
+            fulfillments.push(fulfillment);
+        }
+
+        let mut tx = self.connection.begin().await?;
+        for fulfillment in &fulfillments {
+            fulfillment::update_fulfillment_status(
+                &mut tx,
+                fulfillment.id,
+                &fulfillment.data.status,
+                &model::FulfillmentStatus::InProgress,
+            )
+            .await?;
+        }
+
+        let result = sqlx::query(sql_stmt::UPDATE_WAVE_STARTED)
+            .bind(String::from(model::WaveStatus::Started))
+            .bind(Utc::now())
+            .bind(wave_id)
+            .bind(String::from(model::WaveStatus::Open))
+            .execute(&mut *tx)
+            .await?;
+        if result.rows_affected() != 1 {
+            return Err(super::Error::BadInput(
+                "wave status changed concurrently".to_string(),
+            ));
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+async fn wave_fulfillment_ids(
+    conn: &mut SqliteConnection,
+    wave_id: i64,
+) -> Result<Vec<i64>, super::Error> {
+    let rows = sqlx::query(sql_stmt::SELECT_WAVE_FULFILLMENTS)
+        .bind(wave_id)
+        .fetch_all(&mut *conn)
+        .await?;
+
+    let mut fulfillment_ids = Vec::new();
+    for row in rows {
+        fulfillment_ids.push(row.try_get("fulfillmentId")?);
+    }
+    Ok(fulfillment_ids)
+}
+
+mod sql_stmt {
+    pub const CREATE_WAVE_TABLE: &str = r#"
+        CREATE TABLE IF NOT EXISTS waves (
+            id INTEGER NOT NULL UNIQUE PRIMARY KEY,
+            waveStatus TEXT NOT NULL,
+            createdAt TEXT NOT NULL,
+            startedAt TEXT
+        );
+    "#;
+
+    pub const CREATE_WAVE_FULFILLMENT_TABLE: &str = r#"
+        CREATE TABLE IF NOT EXISTS waveFulfillments (
+            waveId INTEGER NOT NULL,
+            fulfillmentId INTEGER NOT NULL,
+            PRIMARY KEY (waveId, fulfillmentId)
+        );
+    "#;
+
+    pub const INSERT_WAVE: &str = r#"
+        INSERT INTO waves (waveStatus, createdAt) VALUES ( $1, $2 );
+    "#;
+
+    pub const INSERT_WAVE_FULFILLMENT: &str = r#"
+        INSERT INTO waveFulfillments (waveId, fulfillmentId) VALUES ( $1, $2 );
+    "#;
+
+    /// Open and started waves both still hold their fulfillments
+    pub const SELECT_FULFILLMENT_STATE: &str = r#"
+        SELECT
+            fulfillments.fulfillmentType,
+            fulfillments.fulfillmentStatus,
+            (
+                SELECT COUNT(*)
+                FROM waveFulfillments
+                JOIN waves ON waves.id = waveFulfillments.waveId
+                WHERE waveFulfillments.fulfillmentId = fulfillments.id
+                AND waves.waveStatus IN ('Open', 'Started')
+            ) AS waves
+        FROM fulfillments
+        WHERE fulfillments.id = $1;
+    "#;
+
+    pub const SELECT_WAVE: &str = r#"
+        SELECT * FROM waves WHERE id=$1;
+    "#;
+
+    pub const SELECT_WAVE_FULFILLMENTS: &str = r#"
+        SELECT fulfillmentId FROM waveFulfillments WHERE waveId=$1 ORDER BY fulfillmentId;
+    "#;
+
+    pub const UPDATE_WAVE_STARTED: &str = r#"
+        UPDATE waves SET waveStatus = $1, startedAt = $2
+        WHERE id = $3 AND waveStatus = $4;
+    "#;
+}
+
+#[cfg(test)]
+mod test {
+    use rust_decimal::Decimal;
+
+    use crate::{
+        model,
+        provider::SqliteProvider,
+        service::{
+            fulfillment::FulfillmentService, inventory::InventoryService,
+            line_item::LineItemService, product::ProductService, wave::WaveService,
+        },
+    };
+
+    /// Two products in stock and three initialized pick-ups for them
+    async fn setup() -> (SqliteProvider, Vec<i64>) {
+        let mut provider = SqliteProvider::new_memory().await.unwrap();
+        ProductService::init_provider(&mut provider).await.unwrap();
+        FulfillmentService::init_provider(&mut provider)
+            .await
+            .unwrap();
+        LineItemService::init_provider(&mut provider).await.unwrap();
+        InventoryService::init_provider(&mut provider)
+            .await
+            .unwrap();
+        WaveService::init_provider(&mut provider).await.unwrap();
+
+        for sku in ["SKU-1", "SKU-2"] {
+            let product_id = provider
+                .create_product(&model::ProductDetails {
+                    sku: sku.to_string(),
+                    description: "widget".to_string(),
+                    base_price: Decimal::new(1000, 2),
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+            provider
+                .record_stock_movement(
+                    product_id,
+                    None,
+                    10,
+                    model::StockMovementReason::Receipt,
+                    None,
+                )
+                .await
+                .unwrap();
+        }
+
+        let mut fulfillment_ids = Vec::new();
+        for lines in [vec![(1, 2), (2, 1)], vec![(1, 3)], vec![(2, 4)]] {
+            let fulfillment_id = provider
+                .create_fulfillment(&model::NewFulfillment::from(
+                    model::FulfillmentType::StockPickUp,
+                ))
+                .await
+                .unwrap();
+            for (product_id, quantity) in lines {
+                provider
+                    .create_line_item(fulfillment_id, product_id, quantity, None)
+                    .await
+                    .unwrap();
+            }
+            provider
+                .set_fulfillment_status(
+                    &fulfillment_id,
+                    &model::FulfillmentStatus::Initialized.into(),
+                )
+                .await
+                .unwrap();
+            fulfillment_ids.push(fulfillment_id);
+        }
+
+        (provider, fulfillment_ids)
+    }
+
+    #[tokio::test]
+    async fn test_pick_list() {
+        let (mut provider, fulfillment_ids) = setup().await;
+
+        let wave_id = provider.create_wave(&fulfillment_ids).await.unwrap();
+        // a fulfillment can only be in one wave at a time
+        assert!(provider.create_wave(&fulfillment_ids[..1]).await.is_err());
+
+        let pick_list = provider.get_pick_list(wave_id).await.unwrap();
+        let totals: Vec<(i64, i64)> = pick_list
+            .lines
+            .iter()
+            .map(|l| (l.product_id, l.quantity))
+            .collect();
+        assert_eq!(totals, vec![(1, 5), (2, 5)]);
+        assert_eq!(pick_list.lines[0].sku, "SKU-1");
+        assert_eq!(pick_list.lines[1].fulfillments.len(), 2);
+        assert_eq!(
+            pick_list.lines[1].fulfillments[1].fulfillment_id,
+            fulfillment_ids[2]
+        );
+
+        let new_fulfillment = provider
+            .create_fulfillment(&model::NewFulfillment::from(
+                model::FulfillmentType::StockPickUp,
+            ))
+            .await
+            .unwrap();
+        assert!(provider.create_wave(&[new_fulfillment]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_start_wave() {
+        let (mut provider, fulfillment_ids) = setup().await;
+        let wave_id = provider.create_wave(&fulfillment_ids).await.unwrap();
+
+        // one fulfillment moving on alone stops the whole wave from starting
+        provider
+            .set_fulfillment_status(
+                &fulfillment_ids[1],
+                &model::FulfillmentStatus::InProgress.into(),
+            )
+            .await
+            .unwrap();
+        assert!(provider.start_wave(wave_id).await.is_err());
+        let first = provider
+            .get_fulfillment(&fulfillment_ids[0])
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(first.data.status, model::FulfillmentStatus::Initialized);
+        let wave = provider.get_wave(wave_id).await.unwrap().unwrap();
+        assert_eq!(wave.data.status, model::WaveStatus::Open);
+    }
+
+    #[tokio::test]
+    async fn test_wave_moves_fulfillments() {
+        let (mut provider, fulfillment_ids) = setup().await;
+        let wave_id = provider.create_wave(&fulfillment_ids).await.unwrap();
+
+        provider.start_wave(wave_id).await.unwrap();
+        assert!(provider.start_wave(wave_id).await.is_err());
+
+        for fulfillment_id in &fulfillment_ids {
+            let fulfillment = provider
+                .get_fulfillment(fulfillment_id)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(
+                fulfillment.data.status,
+                model::FulfillmentStatus::InProgress
+            );
+        }
+
+        let wave = provider.get_wave(wave_id).await.unwrap().unwrap();
+        assert_eq!(wave.data.status, model::WaveStatus::Started);
+        assert!(wave.data.started_at.is_some());
+
+        // a started wave still holds its fulfillments, even one sent back to be re-picked
+        let mut conn = provider.connection.acquire().await.unwrap();
+        sqlx::query(r#"UPDATE fulfillments SET fulfillmentStatus = ?1 WHERE id = ?2;"#)
+            .bind(String::from(model::FulfillmentStatus::Initialized))
+            .bind(fulfillment_ids[0])
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        drop(conn);
+        assert!(provider.create_wave(&fulfillment_ids[..1]).await.is_err());
+    }
+}